@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use ethers::{
     prelude::Middleware,
     providers::PubsubClient,
-    types::{U256, U64},
+    types::{H256, U256, U64},
 };
 use std::sync::Arc;
 use tokio_stream::StreamExt;
@@ -23,6 +23,7 @@ pub struct NewBlock {
     pub gas_limit: U256,
     pub base_fee_per_gas: U256,
     pub timestamp: U256,
+    pub mix_hash: H256,
 }
 
 impl<M> BlockCollector<M> {
@@ -49,6 +50,7 @@ where
                 gas_used: block.gas_used,
                 base_fee_per_gas: block.base_fee_per_gas.unwrap_or_default(),
                 timestamp: block.timestamp,
+                mix_hash: block.mix_hash.unwrap_or_default(),
             }),
             None => None,
         });
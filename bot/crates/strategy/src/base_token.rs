@@ -0,0 +1,118 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, U256};
+
+use crate::constants::WETH_ADDRESS;
+
+/// Token that sandwiches start and end with (frontrun input & backrun output)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaseToken {
+    pub address: Address,
+    /// Storage slot of the token's `balanceOf` mapping, used to find a victim's trade direction
+    /// from state diffs and to fund simulations
+    pub balance_slot: U256,
+}
+
+impl BaseToken {
+    pub fn weth() -> Self {
+        Self {
+            address: *WETH_ADDRESS,
+            balance_slot: U256::from(3),
+        }
+    }
+
+    pub fn is_weth(&self) -> bool {
+        self.address == *WETH_ADDRESS
+    }
+
+    /// Storage key holding `owner`'s balance
+    pub fn balance_storage_key(&self, owner: Address) -> [u8; 32] {
+        ethers::utils::keccak256(ethers::abi::encode(&[
+            ethers::abi::Token::Address(owner),
+            ethers::abi::Token::Uint(self.balance_slot),
+        ]))
+    }
+}
+
+/// Parses `<address>:<balance slot>`
+impl FromStr for BaseToken {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, slot) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Malformed base token \"{}\"", s))?;
+
+        Ok(Self {
+            address: address.parse().map_err(|e| {
+                anyhow!("Failed to parse base token address \"{}\": {}", address, e)
+            })?,
+            balance_slot: U256::from_dec_str(slot)
+                .map_err(|e| anyhow!("Failed to parse balance slot \"{}\": {}", slot, e))?,
+        })
+    }
+}
+
+/// Tokens that the bot holds inventory of and sandwiches pools against
+/// Weth is always a base token (bribes and gas are paid in eth)
+#[derive(Debug, Clone)]
+pub struct BaseTokenConfig {
+    tokens: Vec<BaseToken>,
+}
+
+impl BaseTokenConfig {
+    /// Weth plus `tokens`, earlier tokens are preferred when a pool pairs two base tokens
+    pub fn new(tokens: Vec<BaseToken>) -> Self {
+        let mut all = vec![BaseToken::weth()];
+        all.extend(tokens.into_iter().filter(|t| !t.is_weth()));
+        Self { tokens: all }
+    }
+
+    pub fn get_tokens(&self) -> &Vec<BaseToken> {
+        &self.tokens
+    }
+
+    pub fn get(&self, address: Address) -> Option<BaseToken> {
+        self.tokens.iter().find(|t| t.address == address).copied()
+    }
+}
+
+/// Weth, USDC, USDT and DAI
+impl Default for BaseTokenConfig {
+    fn default() -> Self {
+        let stables = [
+            // USDC
+            ("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", 9u64),
+            // USDT
+            ("0xdAC17F958D2ee523a2206206994597C13D831ec7", 2),
+            // DAI
+            ("0x6B175474E89094C44Da98b954EedeAC495271d0F", 2),
+        ];
+
+        Self::new(
+            stables
+                .into_iter()
+                .map(|(address, slot)| BaseToken {
+                    address: address.parse().unwrap(),
+                    balance_slot: U256::from(slot),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Parses a comma separated list of `<address>:<balance slot>`
+impl FromStr for BaseTokenConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = s
+            .split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(BaseToken::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(tokens))
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{anyhow, Result};
+use ethers::types::U256;
+use foundry_evm::revm::primitives::{BlockEnv, CfgEnv, SpecId, U256 as rU256};
+use std::str::FromStr;
+
+use crate::{constants::COINBASE, types::BlockInfo};
+
+/// Gas limit assumed for blocks whose limit isn't known (mainnet's default)
+const DEFAULT_GAS_LIMIT: u64 = 30_000_000;
+
+/// Chain that sandwiches are simulated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    /// Hardfork the chain's builders execute blocks with
+    pub spec_id: SpecId,
+    /// Seconds between two blocks
+    pub block_time: u64,
+}
+
+impl Default for ChainConfig {
+    /// Ethereum mainnet
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            spec_id: SpecId::SHANGHAI,
+            block_time: 12,
+        }
+    }
+}
+
+impl ChainConfig {
+    /// Predicts the block built on top of `parent`
+    /// Base fee follows EIP-1559, gas limit & prevrandao are carried over from the parent (the
+    /// real prevrandao is only revealed by the proposer)
+    pub fn next_block(&self, parent: &BlockInfo) -> BlockInfo {
+        BlockInfo {
+            number: parent.number + 1,
+            base_fee_per_gas: parent.next_base_fee(),
+            timestamp: parent.timestamp + self.block_time,
+            gas_used: None,
+            gas_limit: parent.gas_limit,
+            prevrandao: parent.prevrandao,
+        }
+    }
+
+    /// Chain settings of the evm
+    pub fn cfg_env(&self) -> CfgEnv {
+        CfgEnv {
+            chain_id: rU256::from(self.chain_id),
+            spec_id: self.spec_id,
+            ..Default::default()
+        }
+    }
+
+    /// Every field of the environment `block` executes in
+    pub fn block_env(&self, block: &BlockInfo) -> BlockEnv {
+        BlockEnv {
+            number: rU256::from(block.number.as_u64()),
+            // use something other than default (builders' coinbase isn't known ahead of time)
+            coinbase: *COINBASE,
+            timestamp: block.timestamp.into(),
+            basefee: block.base_fee_per_gas.into(),
+            gas_limit: block
+                .gas_limit
+                .unwrap_or_else(|| U256::from(DEFAULT_GAS_LIMIT))
+                .into(),
+            // post merge difficulty is zero and `mixHash` holds the beacon chain's randao
+            difficulty: rU256::ZERO,
+            prevrandao: match SpecId::enabled(self.spec_id, SpecId::MERGE) {
+                true => Some(block.prevrandao.0.into()),
+                false => None,
+            },
+        }
+    }
+}
+
+/// Parses hardfork names (case insensitive), e.g. `london`, `merge`/`paris`, `shanghai`, `cancun`
+pub fn parse_spec_id(s: &str) -> Result<SpecId> {
+    match s.to_lowercase().as_str() {
+        "berlin" => Ok(SpecId::BERLIN),
+        "london" => Ok(SpecId::LONDON),
+        "arrow-glacier" => Ok(SpecId::ARROW_GLACIER),
+        "gray-glacier" => Ok(SpecId::GRAY_GLACIER),
+        "merge" | "paris" => Ok(SpecId::MERGE),
+        "shanghai" => Ok(SpecId::SHANGHAI),
+        "cancun" => Ok(SpecId::CANCUN),
+        "latest" => Ok(SpecId::LATEST),
+        _ => Err(anyhow!("unknown hardfork \"{}\"", s)),
+    }
+}
+
+/// Config parsed from strings of the form `<chain id>:<hardfork>:<block time>`
+/// e.g. `1:shanghai:12`
+impl FromStr for ChainConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        match parts.as_slice() {
+            [chain_id, spec_id, block_time] => Ok(Self {
+                chain_id: chain_id
+                    .parse()
+                    .map_err(|e| anyhow!("invalid chain id \"{}\": {}", chain_id, e))?,
+                spec_id: parse_spec_id(spec_id)?,
+                block_time: block_time
+                    .parse()
+                    .map_err(|e| anyhow!("invalid block time \"{}\": {}", block_time, e))?,
+            }),
+            _ => Err(anyhow!(
+                "expected <chain id>:<hardfork>:<block time>, got \"{}\"",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{H256, U64};
+
+    use super::*;
+
+    /// chain configs parse from `<chain id>:<hardfork>:<block time>` and predict the next block from
+    /// their parent's fields
+    #[test]
+    fn chain_config_builds_next_block() {
+        let chain: ChainConfig = "1:Shanghai:12".parse().unwrap();
+        assert_eq!(chain, ChainConfig::default());
+        assert_eq!(
+            "5:paris:12".parse::<ChainConfig>().unwrap().spec_id,
+            SpecId::MERGE
+        );
+        assert!("1:shanghai".parse::<ChainConfig>().is_err());
+        assert!("1:frontier-ish:12".parse::<ChainConfig>().is_err());
+
+        let parent = BlockInfo {
+            number: U64::from(17_000_000),
+            base_fee_per_gas: U256::from(20_000_000_000u64),
+            timestamp: U256::from(1_681_000_000),
+            gas_used: Some(U256::from(15_000_000)),
+            gas_limit: Some(U256::from(30_000_000)),
+            prevrandao: H256::repeat_byte(0x42),
+        };
+        let next_block = chain.next_block(&parent);
+        assert_eq!(next_block.number, parent.number + 1);
+        assert_eq!(next_block.timestamp, parent.timestamp + 12);
+        // block exactly at target keeps its base fee
+        assert_eq!(next_block.base_fee_per_gas, parent.base_fee_per_gas);
+        assert_eq!(next_block.gas_limit, parent.gas_limit);
+
+        let block_env = chain.block_env(&next_block);
+        assert_eq!(block_env.gas_limit, rU256::from(30_000_000));
+        assert_eq!(block_env.prevrandao, Some(parent.prevrandao.0.into()));
+        assert_eq!(chain.cfg_env().spec_id, SpecId::SHANGHAI);
+    }
+}
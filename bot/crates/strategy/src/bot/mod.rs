@@ -1,24 +1,54 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use artemis_core::{collectors::block_collector::NewBlock, types::Strategy};
 use async_trait::async_trait;
-use cfmms::pool::Pool::{UniswapV2, UniswapV3};
+use cfmms::pool::{Pool, UniswapV2Pool};
 use colored::Colorize;
-use ethers::{providers::Middleware, types::Transaction};
-use foundry_evm::executor::fork::{BlockchainDb, BlockchainDbMeta, SharedBackend};
+use ethers::{
+    providers::Middleware,
+    signers::Signer,
+    types::{Address, Eip1559TransactionRequest, Transaction, TxHash, U256, U64},
+};
+use ethers_flashbots::BundleRequest;
 use log::{error, info};
-use std::{collections::BTreeSet, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    constants::WETH_ADDRESS,
+    base_token::BaseToken,
+    block_env::ChainConfig,
+    bribe_policy::{get_bribeable_revenue, BribePayment, BribePolicy, BribeStats},
+    constants::REBALANCE_PRIORITY_FEE,
+    guardrails::Guardrails,
+    helpers::{access_list_to_ethers, sign_eip1559},
+    inventory::{InventoryConfig, Rebalance},
     log_error, log_info_cyan, log_new_block_info, log_not_sandwichable, log_opportunity,
     managers::{
-        block_manager::BlockManager, pool_manager::PoolManager,
+        block_manager::BlockManager,
+        fork_manager::ForkManager,
+        opportunity_book_manager::OpportunityBookManager,
+        opportunity_scheduler_manager::{Footprint, OpportunitySchedulerManager},
+        pending_approval_manager::PendingApprovalManager,
+        pending_tx_manager::PendingTxManager,
+        pending_victim_manager::PendingVictimManager,
+        pool_manager::PoolManager,
         sandwich_state_manager::SandwichStateManager,
+        searcher_nonce_manager::{Landed, SearcherNonceManager},
+    },
+    simulator::{
+        huff_sandwich::{create_rebalance_tx, create_recipe},
+        lil_router::find_optimal_inputs,
+        search_strategy::SearchStrategies,
+    },
+    tx_utils::meat::{meat_max_fee, validate_meats},
+    types::{
+        to_bundle_request, Action, BlockInfo, Event, RawIngredients, SandwichLeg, SandwichRecipe,
+        StratConfig,
     },
-    simulator::{huff_sandwich::create_recipe, lil_router::find_optimal_input},
-    types::{Action, BlockInfo, Event, RawIngredients, SandwichRecipe, StratConfig},
 };
 
+/// Most pending txs bundled ahead of a victim to fill its nonce gap (chain + victim must fit in
+/// a single sandwich)
+const MAX_NONCE_CHAIN: u64 = 4;
+
 pub struct SandwichBot<M> {
     /// Ethers client
     provider: Arc<M>,
@@ -26,25 +56,78 @@ pub struct SandwichBot<M> {
     pool_manager: PoolManager<M>,
     /// Block manager
     block_manager: BlockManager,
-    /// Keeps track of weth inventory & token dust
+    /// Fork backend shared by every analysis for the upcoming block (recreated every block)
+    fork_manager: ForkManager,
+    /// Victims waiting for base fee to drop below their max fee
+    pending_victim_manager: PendingVictimManager,
+    /// Pending approvals that victims' swaps depend on
+    pending_approval_manager: PendingApprovalManager,
+    /// Recently seen mempool txs, used to fill nonce gaps ahead of victims
+    pending_tx_manager: PendingTxManager,
+    /// Sandwichable victims per pool for the upcoming block (used to batch meats)
+    opportunity_book_manager: OpportunityBookManager,
+    /// Every sandwich found for the upcoming block (used to merge compatible sandwiches)
+    opportunity_scheduler_manager: OpportunitySchedulerManager,
+    /// Keeps track of weth inventory & token dust (synced every block)
     sandwich_state_manager: SandwichStateManager,
+    /// Decides how much revenue is paid to the builder
+    bribe_policy: Box<dyn BribePolicy>,
+    /// Bribing history fed back into the bribe policy
+    bribe_stats: BribeStats,
+    /// How bribes are paid to the builder
+    bribe_payment: BribePayment,
+    /// Hands out searcher nonces to bundles (resynced every block to detect landed bundles)
+    searcher_nonce_manager: SearcherNonceManager,
+    /// Profit & risk limits checked before sandwiches are sent
+    guardrails: Guardrails,
+    /// Longtail tokens held for inventory sandwiches (rebalanced every few blocks)
+    inventory: InventoryConfig,
+    /// How frontrun inputs are searched for each pool type
+    search_strategies: SearchStrategies,
+    /// Chain id, hardfork & block time simulations are run with
+    chain: ChainConfig,
 }
 
 impl<M: Middleware + 'static> SandwichBot<M> {
     /// Create a new instance
     pub fn new(client: Arc<M>, config: StratConfig) -> Self {
         Self {
-            pool_manager: PoolManager::new(client.clone()),
+            pool_manager: PoolManager::new(
+                client.clone(),
+                config.base_tokens.clone(),
+                config.inventory.clone(),
+            ),
             provider: client,
-            block_manager: BlockManager::new(),
+            block_manager: BlockManager::new(config.chain),
+            fork_manager: ForkManager::new(config.chain),
+            pending_victim_manager: PendingVictimManager::new(),
+            pending_approval_manager: PendingApprovalManager::new(),
+            pending_tx_manager: PendingTxManager::new(),
+            opportunity_book_manager: OpportunityBookManager::new(),
+            opportunity_scheduler_manager: OpportunitySchedulerManager::new(),
+            searcher_nonce_manager: SearcherNonceManager::new(config.searcher_signer.address()),
             sandwich_state_manager: SandwichStateManager::new(
                 config.sandwich_address,
                 config.searcher_signer,
                 config.sandwich_inception_block,
+                config.base_tokens,
+                config.inventory.clone(),
             ),
+            bribe_policy: config.bribe_policy.into_policy(),
+            bribe_stats: BribeStats::default(),
+            bribe_payment: config.bribe_payment,
+            guardrails: Guardrails::new(config.guardrails),
+            inventory: config.inventory,
+            search_strategies: SearchStrategies::new(config.search),
+            chain: config.chain,
         }
     }
 
+    /// Manually reset the daily loss kill switch
+    pub fn reset_kill_switch(&mut self) -> Result<()> {
+        self.guardrails.reset_kill_switch()
+    }
+
     /// Main logic for the strategy
     /// Checks if the passed `RawIngredients` is sandwichable
     pub async fn is_sandwichable(
@@ -52,31 +135,80 @@ impl<M: Middleware + 'static> SandwichBot<M> {
         ingredients: RawIngredients,
         target_block: BlockInfo,
     ) -> Result<SandwichRecipe> {
-        // setup shared backend
-        let shared_backend = SharedBackend::spawn_backend_thread(
-            self.provider.clone(),
-            BlockchainDb::new(
-                BlockchainDbMeta {
-                    cfg_env: Default::default(),
-                    block_env: Default::default(),
-                    hosts: BTreeSet::from(["".to_string()]),
-                },
-                None,
-            ), /* default because not accounting for this atm */
-            Some((target_block.number - 1).into()),
-        );
+        let shared_backend = self
+            .fork_manager
+            .get_backend(&target_block, self.provider.clone());
+
+        // meats that builders would reject can't be sandwiched
+        validate_meats(
+            ingredients.get_meats_ref(),
+            &target_block,
+            &self.chain,
+            &shared_backend,
+        )?;
 
-        let weth_inventory = if cfg!(feature = "debug") {
-            // spoof weth balance when the debug feature is active
+        // balances that failed to refresh may still be pre-reorg ones
+        if let Some(token) = ingredients
+            .get_legs()
+            .iter()
+            .flat_map(|leg| {
+                [leg.start_end_token.address, leg.intermediary_token]
+                    .into_iter()
+                    .chain(leg.inventory_token.map(|t| t.address))
+            })
+            .find(|token| !self.sandwich_state_manager.is_usable(*token))
+        {
+            return Err(anyhow!(
+                "[UnusableToken] balance of {:?} failed to refresh",
+                token
+            ));
+        }
+
+        let base_token = ingredients.get_start_end_token();
+        let inventory = if cfg!(feature = "debug") {
+            // spoof base token balance when the debug feature is active
             (*crate::constants::WETH_FUND_AMT).into()
         } else {
-            self.sandwich_state_manager.get_weth_inventory()
+            self.sandwich_state_manager
+                .get_inventory(base_token.address)
+        };
+
+        // never search past what the guardrails allow us to put into this sandwich
+        // (caps are in wei, non weth sandwiches are checked once their recipe is valued in eth)
+        let max_input = match ingredients.get_legs()[0].inventory_token {
+            // inventory legs sell the longtail token, search is bounded by how much of it we hold
+            Some(inventory_token) => match cfg!(feature = "debug") {
+                true => (*crate::constants::WETH_FUND_AMT).into(),
+                false => self
+                    .sandwich_state_manager
+                    .get_token_balance(inventory_token.address),
+            },
+            None => ingredients
+                .get_legs()
+                .iter()
+                .filter(|_| base_token.is_weth())
+                .filter_map(|leg| {
+                    self.guardrails
+                        .max_frontrun_in(target_block.number, leg.intermediary_token)
+                })
+                .fold(inventory, |max_input, max| max_input.min(max)),
         };
 
-        let optimal_input = find_optimal_input(
+        // contract injected in debug mode starts without any dust
+        let has_dust = !cfg!(feature = "debug")
+            && ingredients.get_legs().iter().all(|leg| {
+                // flash swap legs never hold their intermediary token and inventory legs always do
+                leg.flash_pool.is_some()
+                    || leg.inventory_token.is_some()
+                    || self.sandwich_state_manager.has_dust(leg.intermediary_token)
+            });
+
+        let optimal_inputs = find_optimal_inputs(
             &ingredients,
             &target_block,
-            weth_inventory,
+            max_input,
+            &self.search_strategies,
+            &self.chain,
             shared_backend.clone(),
         )
         .await?;
@@ -84,17 +216,32 @@ impl<M: Middleware + 'static> SandwichBot<M> {
         let recipe = create_recipe(
             &ingredients,
             &target_block,
-            optimal_input,
-            weth_inventory,
+            &optimal_inputs.inputs,
+            inventory,
+            has_dust,
             self.sandwich_state_manager.get_searcher_address(),
             self.sandwich_state_manager.get_sandwich_address(),
+            self.bribe_payment,
+            &self.chain,
+            self.pool_manager.get_v2_fee_checks(),
             shared_backend,
         )?;
 
         log_opportunity!(
             ingredients.print_meats(),
-            optimal_input.as_u128() as f64 / 1e18,
-            recipe.get_revenue().as_u128() as f64 / 1e18
+            recipe.get_frontrun_in().as_u128() as f64 / 1e18,
+            recipe.get_revenue().as_u128() as f64 / 1e18,
+            recipe.get_dust_overpay().as_u128() as f64 / 1e18,
+            recipe.get_encoding_loss().as_u128() as f64 / 1e18,
+            optimal_inputs
+                .slippage_bounds
+                .iter()
+                .map(|bound| match bound {
+                    Some(bound) => bound.to_string(),
+                    None => "unknown".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
         );
 
         Ok(recipe)
@@ -117,7 +264,25 @@ impl<M: Middleware + 'static> Strategy<Event, Action> for SandwichBot<M> {
     async fn process_event(&mut self, event: Event) -> Option<Action> {
         match event {
             Event::NewBlock(block) => match self.process_new_block(block).await {
-                Ok(_) => None,
+                Ok(ready_victims) => {
+                    // cached victims go through the normal sandwich path
+                    let mut sandwich_bundles = vec![];
+                    for victim_tx in ready_victims {
+                        if let Some(Action::SubmitToFlashbots(mut bundles)) =
+                            self.process_new_tx(victim_tx).await
+                        {
+                            sandwich_bundles.append(&mut bundles);
+                        }
+                    }
+
+                    sandwich_bundles.append(&mut self.rebalance_inventory().await);
+
+                    if sandwich_bundles.is_empty() {
+                        None
+                    } else {
+                        Some(Action::SubmitToFlashbots(sandwich_bundles))
+                    }
+                }
                 Err(e) => {
                     panic!("strategy is out of sync {}", e);
                 }
@@ -141,10 +306,84 @@ impl<M: Middleware + 'static> SandwichBot<M> {
     }
 
     /// Process new blocks as they come in
-    async fn process_new_block(&mut self, event: NewBlock) -> Result<()> {
+    /// Returns cached victims that can be included in the next block
+    async fn process_new_block(&mut self, event: NewBlock) -> Result<Vec<Transaction>> {
         log_new_block_info!(event);
+        let block_number = event.number;
         self.block_manager.update_block_info(event);
-        Ok(())
+        self.fork_manager
+            .on_new_block(block_number, self.provider.clone());
+        self.sandwich_state_manager
+            .update(block_number, self.provider.clone())
+            .await?;
+        self.guardrails
+            .on_new_block(&self.block_manager.get_latest_block());
+        let landed = self.update_landed_bundles(block_number).await?;
+
+        // track realized pnl for the daily loss kill switch, value moved by deposits and
+        // withdrawals isn't pnl
+        let external_flow = match (landed.txs > 0, landed.untracked_txs > 0) {
+            (true, false) => {
+                self.sandwich_state_manager
+                    .get_external_flow(block_number, self.provider.clone())
+                    .await?
+            }
+            _ => None,
+        };
+        if landed.sandwiches > 0 && external_flow.is_none() {
+            log_error!(
+                "can't measure pnl of block {}, searcher or sandwich contract moved funds",
+                block_number
+            );
+        }
+        let portfolio_value = self
+            .sandwich_state_manager
+            .get_portfolio_value(self.provider.clone(), block_number)
+            .await?;
+        self.guardrails
+            .record_portfolio_value(portfolio_value, external_flow);
+
+        let mined_nonces = self.get_mined_nonces(block_number).await?;
+        self.pending_approval_manager
+            .on_new_block(block_number, &mined_nonces);
+        self.pending_tx_manager
+            .on_new_block(block_number, &mined_nonces);
+        Ok(self.pending_victim_manager.on_new_block(
+            &self.block_manager.get_latest_block(),
+            &self.block_manager.get_next_block(),
+            &mined_nonces,
+        ))
+    }
+
+    /// Highest nonce mined by each sender in `block`, pending txs at or below it can't land anymore
+    async fn get_mined_nonces(&self, block: U64) -> Result<HashMap<Address, U256>> {
+        let block = self
+            .provider
+            .get_block_with_txs(block)
+            .await
+            .map_err(|e| anyhow!("Failed to get block {}: {:?}", block, e))?
+            .ok_or(anyhow!("Failed to get block {}", block))?;
+
+        let mut mined_nonces: HashMap<Address, U256> = HashMap::new();
+        for tx in block.transactions {
+            let nonce = mined_nonces.entry(tx.from).or_default();
+            *nonce = (*nonce).max(tx.nonce);
+        }
+
+        Ok(mined_nonces)
+    }
+
+    /// Feed landed bundles back into bribe stats & guardrails, returns what landed
+    async fn update_landed_bundles(&mut self, block_number: U64) -> Result<Landed> {
+        let landed = self
+            .searcher_nonce_manager
+            .on_new_block(block_number, self.provider.clone())
+            .await?;
+
+        self.bribe_stats.record_landed(landed.sandwiches);
+        self.guardrails.record_landed(&landed.exposure);
+
+        Ok(landed)
     }
 
     /// Process new txs as they come in
@@ -154,93 +393,507 @@ impl<M: Middleware + 'static> SandwichBot<M> {
         let next_block = self.block_manager.get_next_block();
         let latest_block = self.block_manager.get_latest_block();
 
-        // ignore txs that we can't include in next block
-        // enhancement: simulate all txs regardless, store result, and use result when tx can included
-        if victim_tx.max_fee_per_gas.unwrap_or_default() < next_block.base_fee_per_gas {
+        // kill switch is active, stop emitting actions until it is manually reset
+        if self.guardrails.is_halted() {
+            return None;
+        }
+
+        // a replacement makes any cached tx with the same sender & nonce unincludable
+        self.pending_victim_manager.remove_replaced(&victim_tx);
+
+        // approvals aren't swaps, keep them around for the swap that follows
+        if self
+            .pending_approval_manager
+            .insert_if_approval(&victim_tx, latest_block.number)
+        {
+            return None;
+        }
+        self.pending_tx_manager
+            .insert(&victim_tx, latest_block.number);
+
+        // txs that we can't include in next block are revisited once base fee drops
+        if meat_max_fee(&victim_tx) < next_block.base_fee_per_gas {
             log_info_cyan!("{:?} mf<nbf", victim_tx.hash);
+            self.pending_victim_manager
+                .insert(victim_tx, latest_block.number);
             return None;
         }
 
-        // check if tx is a swap
-        let touched_pools = self
-            .pool_manager
-            .get_touched_sandwichable_pools(
-                &victim_tx,
-                latest_block.number.into(),
-                self.provider.clone(),
-            )
-            .await
-            .map_err(|e| {
-                log_error!("Failed to get touched sandwichable pools: {}", e);
-                e
-            })
-            .ok()?;
+        // check if tx is a swap, it is traced on its own first since looking up its nonce chain
+        // costs an rpc call (only paid by swaps and by txs that may depend on a cached pending tx
+        // of their sender, e.g. a swap right after its approval)
+        let mut legs = self
+            .get_touched_legs(&victim_tx, &[], latest_block.number)
+            .await;
+        let may_have_prerequisites = !self
+            .pending_approval_manager
+            .get_approvals(&victim_tx)
+            .is_empty()
+            || (!victim_tx.nonce.is_zero()
+                && self
+                    .pending_tx_manager
+                    .get(victim_tx.from, victim_tx.nonce - 1)
+                    .is_some());
 
         // no touched pools = no sandwich opps
-        if touched_pools.is_empty() {
+        if legs.is_empty() && !may_have_prerequisites {
             info!("{:?}", victim_tx.hash);
             return None;
         }
 
+        // victim's pending txs with lower nonces (e.g. an approval) have to land first, they are
+        // bundled as meats ahead of it
+        let prerequisites = match self.get_nonce_chain(&victim_tx, latest_block.number).await {
+            Ok(chain) => chain,
+            Err(e) => {
+                log_not_sandwichable!("{:?} {:?}", victim_tx.hash, e);
+                return None;
+            }
+        };
+        if legs.is_empty() && !prerequisites.is_empty() {
+            legs = self
+                .get_touched_legs(&victim_tx, &prerequisites, latest_block.number)
+                .await;
+        }
+        if legs.is_empty() {
+            info!("{:?}", victim_tx.hash);
+            return None;
+        }
+
+        let mut meats = prerequisites.clone();
+        meats.push(victim_tx.clone());
+
+        if prerequisites
+            .iter()
+            .any(|tx| meat_max_fee(tx) < next_block.base_fee_per_gas)
+        {
+            log_info_cyan!("{:?} mf<nbf", victim_tx.hash);
+            self.pending_victim_manager
+                .insert(victim_tx, latest_block.number);
+            return None;
+        }
+
+        // busiest pools get prefetched into the next block's fork
+        self.fork_manager
+            .record_activity(legs.iter().flat_map(|leg| {
+                std::iter::once(leg.target_pool).chain(leg.flash_pool.map(Pool::UniswapV2))
+            }));
+
         let mut sandwich_bundles = vec![];
 
-        for pool in touched_pools {
-            let (token_a, token_b) = match pool {
-                UniswapV2(p) => (p.token_a, p.token_b),
-                UniswapV3(p) => (p.token_a, p.token_b),
-            };
+        // victim routes through several pools, sandwich all of them in one bundle
+        // (independent per pool bundles would all contain the victim and conflict)
+        // legs starting with different base tokens can't share a frontrun inventory (inventory
+        // legs sell a longtail token instead and are always sandwiched alone)
+        if legs.len() > 1
+            && legs.iter().all(|l| {
+                l.start_end_token == legs[0].start_end_token && l.inventory_token.is_none()
+            })
+        {
+            let ingredients = RawIngredients::new_multi_pool(meats.clone(), legs.clone());
 
-            if token_a != *WETH_ADDRESS && token_b != *WETH_ADDRESS {
-                // contract can only sandwich weth pools
-                continue;
+            match self.is_sandwichable(ingredients, next_block.clone()).await {
+                Ok(recipe) => match self
+                    .prepare_bundle(recipe, victim_tx.hash, &legs, &next_block)
+                    .await
+                {
+                    Ok(_bundle) => {
+                        #[cfg(not(feature = "debug"))]
+                        sandwich_bundles.push(_bundle);
+                        self.push_merged_bundle(&next_block, &mut sandwich_bundles)
+                            .await;
+                        return Some(Action::SubmitToFlashbots(sandwich_bundles));
+                    }
+                    Err(e) => log_not_sandwichable!("{:?} multi pool {:?}", victim_tx.hash, e),
+                },
+                Err(e) => log_not_sandwichable!("{:?} multi pool {:?}", victim_tx.hash, e),
             }
+        }
+
+        // fallback to sandwiching each pool on its own
+        for leg in legs {
+            let pool = leg.target_pool;
 
-            // token that we use as frontrun input and backrun output
-            let start_end_token = *WETH_ADDRESS;
+            let ingredients = RawIngredients::from_leg(meats.clone(), leg);
 
-            // token that we use as frontrun output and backrun input
-            let intermediary_token = if token_a == start_end_token {
-                token_b
-            } else {
-                token_a
+            let recipe = match self.is_sandwichable(ingredients, next_block.clone()).await {
+                Ok(s) => s,
+                Err(e) => {
+                    log_not_sandwichable!("{:?} {:?}", victim_tx.hash, e);
+                    continue;
+                }
             };
 
-            let ingredients = RawIngredients::new(
-                vec![victim_tx.clone()],
-                start_end_token,
-                intermediary_token,
-                pool,
+            // book victim so that it can be batched with other victims on the same pool
+            let candidates = self.opportunity_book_manager.add_victim(
+                next_block.number,
+                pool.address(),
+                meats.clone(),
+                recipe.get_revenue(),
             );
 
-            match self.is_sandwichable(ingredients, next_block.clone()).await {
-                Ok(s) => {
-                    let _bundle = match s
-                        .to_fb_bundle(
-                            self.sandwich_state_manager.get_sandwich_address(),
-                            self.sandwich_state_manager.get_searcher_signer(),
-                            false,
-                            self.provider.clone(),
-                        )
-                        .await
-                    {
-                        Ok(b) => b,
-                        Err(e) => {
-                            log_not_sandwichable!("{:?}", e);
-                            continue;
+            match self
+                .prepare_bundle(recipe, victim_tx.hash, &[leg], &next_block)
+                .await
+            {
+                Ok(_bundle) => {
+                    #[cfg(not(feature = "debug"))]
+                    sandwich_bundles.push(_bundle);
+                }
+                Err(e) => log_not_sandwichable!("{:?} {:?}", victim_tx.hash, e),
+            }
+
+            // find the most profitable batch of meats that includes this victim
+            let mut best_batch: Option<SandwichRecipe> = None;
+            for meats in candidates {
+                let ingredients = RawIngredients::from_leg(meats, leg);
+                match self.is_sandwichable(ingredients, next_block.clone()).await {
+                    Ok(r) => {
+                        if best_batch
+                            .as_ref()
+                            .map_or(true, |b| r.get_revenue() > b.get_revenue())
+                        {
+                            best_batch = Some(r);
                         }
-                    };
+                    }
+                    Err(e) => log_not_sandwichable!("{:?} batch {:?}", victim_tx.hash, e),
+                }
+            }
 
-                    #[cfg(not(feature = "debug"))]
+            // resubmit a larger bundle if batching beats the best known set of meats
+            if let Some(batch) = best_batch {
+                if self.opportunity_book_manager.record_result(
+                    next_block.number,
+                    pool.address(),
+                    batch.get_meats_ref().clone(),
+                    batch.get_revenue(),
+                ) {
+                    match self
+                        .prepare_bundle(batch, victim_tx.hash, &[leg], &next_block)
+                        .await
                     {
-                        sandwich_bundles.push(_bundle);
+                        Ok(_bundle) => {
+                            #[cfg(not(feature = "debug"))]
+                            sandwich_bundles.push(_bundle);
+                        }
+                        Err(e) => log_not_sandwichable!("{:?} batch {:?}", victim_tx.hash, e),
                     }
                 }
-                Err(e) => {
-                    log_not_sandwichable!("{:?} {:?}", victim_tx.hash, e)
+            }
+        }
+
+        self.push_merged_bundle(&next_block, &mut sandwich_bundles)
+            .await;
+        Some(Action::SubmitToFlashbots(sandwich_bundles))
+    }
+
+    /// Merge the most valuable set of compatible sandwiches found for `next_block` into one bundle
+    /// with sequential searcher nonces (pushed next to the single sandwich bundles, which stay as
+    /// fallbacks)
+    async fn push_merged_bundle(
+        &mut self,
+        next_block: &BlockInfo,
+        _sandwich_bundles: &mut Vec<BundleRequest>,
+    ) {
+        let scheduled = match self.opportunity_scheduler_manager.schedule(
+            next_block.number,
+            self.sandwich_state_manager
+                .get_inventory(BaseToken::weth().address),
+            self.sandwich_state_manager.get_eth_balance(),
+        ) {
+            Some(scheduled) => scheduled,
+            None => return,
+        };
+
+        match self.prepare_merged_bundle(scheduled, next_block).await {
+            Ok(_bundle) => {
+                #[cfg(not(feature = "debug"))]
+                _sandwich_bundles.push(_bundle);
+            }
+            Err(e) => log_error!("Failed to merge sandwiches {:?}", e),
+        }
+    }
+
+    /// Sign every scheduled sandwich one after the other into a single bundle
+    /// Its nonces start at the confirmed nonce like any other bundle for the block, the sandwiches'
+    /// nonces follow each other inside it
+    async fn prepare_merged_bundle(
+        &mut self,
+        scheduled: Vec<(SandwichRecipe, U256)>,
+        next_block: &BlockInfo,
+    ) -> Result<BundleRequest> {
+        let searcher = self.sandwich_state_manager.get_searcher_signer();
+
+        let sandwich_count = scheduled.len();
+        let tx_count = scheduled
+            .iter()
+            .map(|(recipe, _)| recipe.get_searcher_tx_count())
+            .sum();
+        let exposure = scheduled
+            .iter()
+            .flat_map(|(recipe, _)| recipe.get_frontrun_exposure().iter().copied())
+            .collect();
+        let mut nonce =
+            self.searcher_nonce_manager
+                .reserve(tx_count, sandwich_count as u64, exposure)?;
+
+        let mut bundled_transactions = vec![];
+        for (recipe, bribe_amount) in scheduled {
+            let (mut txs, next_nonce) = recipe
+                .to_signed_txs(
+                    self.sandwich_state_manager.get_sandwich_address(),
+                    searcher,
+                    bribe_amount,
+                    nonce,
+                )
+                .await?;
+            bundled_transactions.append(&mut txs);
+            nonce = next_nonce;
+        }
+
+        log_info_cyan!("merged {} sandwiches into one bundle", sandwich_count);
+
+        Ok(to_bundle_request(bundled_transactions, next_block))
+    }
+
+    /// Sandwich legs for the pools that `victim_tx` touches when executed after `prerequisites`
+    /// (none if it can't be traced)
+    async fn get_touched_legs(
+        &self,
+        victim_tx: &Transaction,
+        prerequisites: &[Transaction],
+        latest_block: U64,
+    ) -> Vec<SandwichLeg> {
+        self.pool_manager
+            .get_touched_sandwichable_pools(
+                victim_tx,
+                prerequisites,
+                latest_block.into(),
+                self.provider.clone(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                log_error!("Failed to get touched sandwichable pools: {}", e);
+                vec![]
+            })
+    }
+
+    /// Pending txs that fill the gap between the sender's account nonce and `victim_tx`'s nonce
+    /// Errors if any of them hasn't been seen (a bundle without them could never land)
+    async fn get_nonce_chain(
+        &self,
+        victim_tx: &Transaction,
+        latest_block: U64,
+    ) -> Result<Vec<Transaction>> {
+        let account_nonce = self
+            .provider
+            .get_transaction_count(victim_tx.from, Some(latest_block.into()))
+            .await
+            .map_err(|e| anyhow!("Failed to get nonce of {:?}: {:?}", victim_tx.from, e))?;
+
+        if victim_tx.nonce < account_nonce {
+            return Err(anyhow!(
+                "[NonceTooLow] nonce {} already used (account nonce {})",
+                victim_tx.nonce,
+                account_nonce
+            ));
+        }
+        if victim_tx.nonce - account_nonce > U256::from(MAX_NONCE_CHAIN) {
+            return Err(anyhow!(
+                "[NonceGap] {} pending txs ahead of victim (max {})",
+                victim_tx.nonce - account_nonce,
+                MAX_NONCE_CHAIN
+            ));
+        }
+
+        let approvals = self.pending_approval_manager.get_approvals(victim_tx);
+        let mut chain = vec![];
+        let mut nonce = account_nonce;
+
+        while nonce < victim_tx.nonce {
+            let tx = approvals
+                .iter()
+                .find(|a| a.nonce == nonce)
+                .cloned()
+                .or_else(|| self.pending_tx_manager.get(victim_tx.from, nonce))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "[NonceGap] missing pending tx with nonce {} (account nonce {})",
+                        nonce,
+                        account_nonce
+                    )
+                })?;
+            chain.push(tx);
+            nonce += U256::one();
+        }
+
+        Ok(chain)
+    }
+
+    /// Trade inventory tokens that drifted away from their target back to it
+    /// Runs every `rebalance_interval` blocks, each trade is sent as its own bundle
+    async fn rebalance_inventory(&mut self) -> Vec<BundleRequest> {
+        let next_block = self.block_manager.get_next_block();
+        let mut bundles = vec![];
+
+        if self.guardrails.is_halted()
+            || self.inventory.rebalance_interval == 0
+            || next_block.number.as_u64() % self.inventory.rebalance_interval != 0
+        {
+            return bundles;
+        }
+
+        for inventory_token in self.inventory.get_tokens().clone() {
+            let token = inventory_token.token.address;
+            if !self.sandwich_state_manager.is_usable(token) {
+                continue;
+            }
+            let balance = self.sandwich_state_manager.get_token_balance(token);
+
+            let rebalance =
+                match inventory_token.rebalance(balance, self.inventory.rebalance_band_bps) {
+                    Some(rebalance) => rebalance,
+                    None => continue,
+                };
+
+            let pool = match self.pool_manager.get_weth_pair(token) {
+                Some(pool) => pool,
+                None => {
+                    log_error!("Failed to rebalance {:?}: no uniswap v2 weth pair", token);
+                    continue;
                 }
             };
+
+            match self
+                .prepare_rebalance_bundle(pool, token, rebalance, &next_block)
+                .await
+            {
+                Ok(_bundle) => {
+                    info!("rebalancing {:?} {:?}", token, rebalance);
+                    #[cfg(not(feature = "debug"))]
+                    bundles.push(_bundle);
+                }
+                Err(e) => log_error!("Failed to rebalance {:?}: {:?}", token, e),
+            }
         }
 
-        Some(Action::SubmitToFlashbots(sandwich_bundles))
+        bundles
+    }
+
+    /// Simulate an inventory trade and turn it into a signed single tx bundle
+    async fn prepare_rebalance_bundle(
+        &mut self,
+        pool: UniswapV2Pool,
+        token: Address,
+        rebalance: Rebalance,
+        next_block: &BlockInfo,
+    ) -> Result<BundleRequest> {
+        let searcher = self.sandwich_state_manager.get_searcher_signer();
+        let sandwich_address = self.sandwich_state_manager.get_sandwich_address();
+
+        let (tx_env, gas_used) = create_rebalance_tx(
+            pool,
+            token,
+            rebalance,
+            next_block,
+            searcher.address(),
+            sandwich_address,
+            &self.chain,
+            self.fork_manager
+                .get_backend(next_block, self.provider.clone()),
+        )?;
+
+        let nonce = self.searcher_nonce_manager.reserve(1, 0, vec![])?;
+
+        let tx = Eip1559TransactionRequest {
+            to: Some(sandwich_address.into()),
+            gas: Some((U256::from(gas_used) * 10) / 7),
+            value: Some(tx_env.value.into()),
+            data: Some(tx_env.data.into()),
+            nonce: Some(nonce),
+            access_list: access_list_to_ethers(tx_env.access_list),
+            max_priority_fee_per_gas: Some(*REBALANCE_PRIORITY_FEE),
+            max_fee_per_gas: Some(next_block.base_fee_per_gas + *REBALANCE_PRIORITY_FEE),
+            ..Default::default()
+        };
+
+        let signed_tx = sign_eip1559(tx, searcher).await.map_err(|e| {
+            self.searcher_nonce_manager.release(nonce);
+            e
+        })?;
+
+        Ok(BundleRequest::new()
+            .push_transaction(signed_tx)
+            .set_block(next_block.number)
+            .set_simulation_block(next_block.number - 1)
+            .set_simulation_timestamp(next_block.timestamp.as_u64()))
+    }
+
+    /// Decide bribe, check guardrails and turn a recipe into a signed bundle
+    /// `victim` is the tx being processed, every bundle built for it is an alternative to the others
+    async fn prepare_bundle(
+        &mut self,
+        recipe: SandwichRecipe,
+        victim: TxHash,
+        legs: &[SandwichLeg],
+        next_block: &BlockInfo,
+    ) -> Result<BundleRequest> {
+        let bribe_amount =
+            self.bribe_policy
+                .calculate_bribe(&recipe, next_block, &self.bribe_stats)?;
+        let _bribeable_revenue = get_bribeable_revenue(&recipe, next_block).unwrap_or_default();
+
+        // coinbase bribes (dust overpay included) are paid out of the sandwich contract's eth
+        // balance
+        let coinbase_spend = match recipe.get_bribe_payment() {
+            BribePayment::CoinbaseTransfer => recipe.get_coinbase_bribe(bribe_amount)?,
+            BribePayment::PriorityFee => U256::zero(),
+        };
+        if coinbase_spend > self.sandwich_state_manager.get_eth_balance() {
+            return Err(anyhow!(
+                "sandwich contract eth balance can't cover coinbase bribe"
+            ));
+        }
+
+        // every leg is checked against the token it buys
+        self.guardrails.check(&recipe, victim, bribe_amount)?;
+        let exposure = recipe.get_frontrun_exposure().to_vec();
+
+        // candidate for the block's merged bundle
+        let footprint = Footprint::new(legs, &recipe, coinbase_spend);
+        self.opportunity_scheduler_manager.add_candidate(
+            next_block.number,
+            recipe.clone(),
+            bribe_amount,
+            footprint,
+        );
+
+        let nonce = self.searcher_nonce_manager.reserve(
+            recipe.get_searcher_tx_count(),
+            1,
+            exposure.clone(),
+        )?;
+        let bundle = recipe
+            .to_fb_bundle(
+                self.sandwich_state_manager.get_sandwich_address(),
+                self.sandwich_state_manager.get_searcher_signer(),
+                bribe_amount,
+                nonce,
+            )
+            .await
+            .map_err(|e| {
+                self.searcher_nonce_manager.release(nonce);
+                e
+            })?;
+
+        #[cfg(not(feature = "debug"))]
+        {
+            self.bribe_stats
+                .record_submission(bribe_amount, _bribeable_revenue);
+            self.guardrails
+                .record_sandwich(next_block.number, victim, &exposure);
+        }
+
+        Ok(bundle)
     }
 }
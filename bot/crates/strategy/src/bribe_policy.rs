@@ -0,0 +1,504 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use anyhow::{anyhow, ensure, Result};
+use ethers::types::U256;
+
+use crate::types::{BlockInfo, SandwichRecipe};
+
+/// All percentages used by bribe policies are expressed in basis points
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// How many recent bribes are kept around when calculating averages
+const BRIBE_HISTORY_LEN: usize = 100;
+
+/// Decides how much of a sandwich's revenue is paid to the builder
+pub trait BribePolicy: Send + Sync {
+    /// Returns the total amount (in wei) spent on the backrun to get the bundle included.
+    /// Frontrun gas is paid separately and is already deducted before the policy is consulted.
+    fn calculate_bribe(
+        &self,
+        recipe: &SandwichRecipe,
+        target_block: &BlockInfo,
+        stats: &BribeStats,
+    ) -> Result<U256>;
+}
+
+/// Historical bribing data that policies can use to adapt
+#[derive(Debug, Default, Clone)]
+pub struct BribeStats {
+    bundles_submitted: u64,
+    bundles_landed: u64,
+    recent_bribe_bps: VecDeque<u64>,
+}
+
+impl BribeStats {
+    /// Record a bundle that was sent to the relay along with the share of revenue it bribed
+    pub fn record_submission(&mut self, bribe_amount: U256, bribeable_revenue: U256) {
+        self.bundles_submitted += 1;
+
+        if bribeable_revenue.is_zero() {
+            return;
+        }
+
+        let bps = (bribe_amount * BPS_DENOMINATOR / bribeable_revenue)
+            .min(U256::from(BPS_DENOMINATOR))
+            .as_u64();
+
+        if self.recent_bribe_bps.len() == BRIBE_HISTORY_LEN {
+            self.recent_bribe_bps.pop_front();
+        }
+        self.recent_bribe_bps.push_back(bps);
+    }
+
+    /// Record bundles that made it onchain
+    pub fn record_landed(&mut self, count: u64) {
+        self.bundles_landed += count;
+    }
+
+    pub fn get_bundles_submitted(&self) -> u64 {
+        self.bundles_submitted
+    }
+
+    pub fn get_bundles_landed(&self) -> u64 {
+        self.bundles_landed
+    }
+
+    /// Share of submitted bundles that landed (in bps), `None` if nothing was submitted yet
+    pub fn inclusion_rate_bps(&self) -> Option<u64> {
+        if self.bundles_submitted == 0 {
+            return None;
+        }
+        Some((self.bundles_landed * BPS_DENOMINATOR / self.bundles_submitted).min(BPS_DENOMINATOR))
+    }
+
+    /// Average share of revenue bribed over the recent history (in bps)
+    pub fn average_bribe_bps(&self) -> Option<u64> {
+        if self.recent_bribe_bps.is_empty() {
+            return None;
+        }
+        Some(self.recent_bribe_bps.iter().sum::<u64>() / self.recent_bribe_bps.len() as u64)
+    }
+}
+
+/// How the bribe reaches the builder
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BribePayment {
+    /// Bribe is paid through the backrun's `max_priority_fee_per_gas`
+    #[default]
+    PriorityFee,
+    /// Bribe is paid by the sandwich contract transferring eth to `block.coinbase` during the
+    /// backrun, backrun only pays basefee
+    CoinbaseTransfer,
+}
+
+impl FromStr for BribePayment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "priority-fee" => Ok(BribePayment::PriorityFee),
+            "coinbase" => Ok(BribePayment::CoinbaseTransfer),
+            _ => Err(anyhow!("Unknown bribe payment \"{}\"", s)),
+        }
+    }
+}
+
+/// Revenue left to bribe with after paying for the frontrun's basefee
+pub fn get_bribeable_revenue(recipe: &SandwichRecipe, target_block: &BlockInfo) -> Result<U256> {
+    recipe
+        .get_revenue()
+        .checked_sub(U256::from(recipe.get_frontrun_gas_used()) * target_block.base_fee_per_gas)
+        .ok_or_else(|| anyhow!("[FAILED TO CREATE BUNDLE] revenue doesn't cover frontrun basefee"))
+}
+
+fn apply_bps(amount: U256, bps: u64) -> U256 {
+    amount * bps / BPS_DENOMINATOR
+}
+
+/// Bribe a fixed share of revenue
+pub struct FixedPercentage {
+    pub bps: u64,
+}
+
+impl BribePolicy for FixedPercentage {
+    fn calculate_bribe(
+        &self,
+        recipe: &SandwichRecipe,
+        target_block: &BlockInfo,
+        _stats: &BribeStats,
+    ) -> Result<U256> {
+        let bribeable = get_bribeable_revenue(recipe, target_block)?;
+        Ok(apply_bps(bribeable, self.bps))
+    }
+}
+
+/// Bribe up to `max_bps` of revenue while always keeping at least `min_profit`
+pub struct MinProfitFloor {
+    pub min_profit: U256,
+    pub max_bps: u64,
+}
+
+impl BribePolicy for MinProfitFloor {
+    fn calculate_bribe(
+        &self,
+        recipe: &SandwichRecipe,
+        target_block: &BlockInfo,
+        _stats: &BribeStats,
+    ) -> Result<U256> {
+        let bribeable = get_bribeable_revenue(recipe, target_block)?;
+
+        let max_bribe = bribeable.checked_sub(self.min_profit).ok_or_else(|| {
+            anyhow!("[FAILED TO CREATE BUNDLE] revenue doesn't cover minimum profit floor")
+        })?;
+
+        Ok(apply_bps(bribeable, self.max_bps).min(max_bribe))
+    }
+}
+
+/// Bribe share is interpolated from a curve keyed on revenue
+/// (bigger opportunities tend to attract more competition)
+pub struct RevenueCurve {
+    /// (revenue, bps) points sorted by revenue
+    pub points: Vec<(U256, u64)>,
+}
+
+impl RevenueCurve {
+    fn bps_at(&self, revenue: U256) -> u64 {
+        let first = match self.points.first() {
+            Some(p) => p,
+            None => return 0,
+        };
+        if revenue <= first.0 {
+            return first.1;
+        }
+
+        for window in self.points.windows(2) {
+            let (lower, upper) = (window[0], window[1]);
+            if revenue > upper.0 {
+                continue;
+            }
+
+            // linear interpolation between the two surrounding points
+            let range = upper.0 - lower.0;
+            if range.is_zero() {
+                return upper.1;
+            }
+            let offset = revenue - lower.0;
+            return if upper.1 >= lower.1 {
+                lower.1 + (U256::from(upper.1 - lower.1) * offset / range).as_u64()
+            } else {
+                lower.1 - (U256::from(lower.1 - upper.1) * offset / range).as_u64()
+            };
+        }
+
+        // revenue is past the last point
+        self.points[self.points.len() - 1].1
+    }
+}
+
+impl BribePolicy for RevenueCurve {
+    fn calculate_bribe(
+        &self,
+        recipe: &SandwichRecipe,
+        target_block: &BlockInfo,
+        _stats: &BribeStats,
+    ) -> Result<U256> {
+        let bribeable = get_bribeable_revenue(recipe, target_block)?;
+        Ok(apply_bps(bribeable, self.bps_at(bribeable)))
+    }
+}
+
+/// Adapts the bribe share to how often our bundles land
+/// (bribe more when losing auctions, claw back margin when winning)
+pub struct Competitive {
+    pub min_bps: u64,
+    pub max_bps: u64,
+    pub target_inclusion_bps: u64,
+}
+
+impl Competitive {
+    fn current_bps(&self, stats: &BribeStats) -> u64 {
+        let (average_bps, inclusion_rate_bps) =
+            match (stats.average_bribe_bps(), stats.inclusion_rate_bps()) {
+                (Some(a), Some(i)) => (a, i),
+                // no history yet, start aggressive to learn where the market is
+                _ => return self.max_bps,
+            };
+
+        let bps = if inclusion_rate_bps < self.target_inclusion_bps {
+            average_bps + self.max_bps.saturating_sub(average_bps) / 2
+        } else {
+            average_bps - average_bps.saturating_sub(self.min_bps) / 4
+        };
+
+        bps.clamp(self.min_bps, self.max_bps)
+    }
+}
+
+impl BribePolicy for Competitive {
+    fn calculate_bribe(
+        &self,
+        recipe: &SandwichRecipe,
+        target_block: &BlockInfo,
+        stats: &BribeStats,
+    ) -> Result<U256> {
+        let bribeable = get_bribeable_revenue(recipe, target_block)?;
+        Ok(apply_bps(bribeable, self.current_bps(stats)))
+    }
+}
+
+/// Config used to select and parametrize a bribe policy
+///
+/// Parsed from strings of the form:
+/// * `fixed:<bps>`
+/// * `min-profit:<min_profit_eth>:<max_bps>`
+/// * `curve:<revenue_eth>=<bps>,<revenue_eth>=<bps>,...`
+/// * `competitive:<min_bps>:<max_bps>:<target_inclusion_bps>`
+#[derive(Debug, Clone)]
+pub enum BribePolicyConfig {
+    FixedPercentage {
+        bps: u64,
+    },
+    MinProfitFloor {
+        min_profit: U256,
+        max_bps: u64,
+    },
+    RevenueCurve {
+        points: Vec<(U256, u64)>,
+    },
+    Competitive {
+        min_bps: u64,
+        max_bps: u64,
+        target_inclusion_bps: u64,
+    },
+}
+
+impl Default for BribePolicyConfig {
+    fn default() -> Self {
+        BribePolicyConfig::FixedPercentage { bps: 9_000 }
+    }
+}
+
+impl BribePolicyConfig {
+    /// Create the policy described by this config
+    pub fn into_policy(self) -> Box<dyn BribePolicy> {
+        match self {
+            BribePolicyConfig::FixedPercentage { bps } => Box::new(FixedPercentage { bps }),
+            BribePolicyConfig::MinProfitFloor {
+                min_profit,
+                max_bps,
+            } => Box::new(MinProfitFloor {
+                min_profit,
+                max_bps,
+            }),
+            BribePolicyConfig::RevenueCurve { points } => Box::new(RevenueCurve { points }),
+            BribePolicyConfig::Competitive {
+                min_bps,
+                max_bps,
+                target_inclusion_bps,
+            } => Box::new(Competitive {
+                min_bps,
+                max_bps,
+                target_inclusion_bps,
+            }),
+        }
+    }
+}
+
+fn parse_bps(value: &str) -> Result<u64> {
+    let bps = value
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| anyhow!("Failed to parse \"{}\" as bps", value))?;
+    ensure!(
+        bps <= BPS_DENOMINATOR,
+        "bps value {} is larger than {}",
+        bps,
+        BPS_DENOMINATOR
+    );
+    Ok(bps)
+}
+
+fn parse_eth(value: &str) -> Result<U256> {
+    ethers::utils::parse_ether(value.trim())
+        .map_err(|_| anyhow!("Failed to parse \"{}\" as an ether amount", value))
+}
+
+impl FromStr for BribePolicyConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.trim().splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts
+            .next()
+            .map(|a| a.split(':').collect())
+            .unwrap_or_default();
+
+        match (name, args.as_slice()) {
+            ("fixed", [bps]) => Ok(BribePolicyConfig::FixedPercentage {
+                bps: parse_bps(bps)?,
+            }),
+            ("min-profit", [min_profit, max_bps]) => Ok(BribePolicyConfig::MinProfitFloor {
+                min_profit: parse_eth(min_profit)?,
+                max_bps: parse_bps(max_bps)?,
+            }),
+            ("curve", [points]) => {
+                let mut points = points
+                    .split(',')
+                    .map(|point| {
+                        let (revenue, bps) = point
+                            .split_once('=')
+                            .ok_or_else(|| anyhow!("Malformed curve point \"{}\"", point))?;
+                        Ok((parse_eth(revenue)?, parse_bps(bps)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                points.sort_by(|a, b| a.0.cmp(&b.0));
+                Ok(BribePolicyConfig::RevenueCurve { points })
+            }
+            ("competitive", [min_bps, max_bps, target_inclusion_bps]) => {
+                let (min_bps, max_bps) = (parse_bps(min_bps)?, parse_bps(max_bps)?);
+                ensure!(
+                    min_bps <= max_bps,
+                    "competitive min_bps larger than max_bps"
+                );
+                Ok(BribePolicyConfig::Competitive {
+                    min_bps,
+                    max_bps,
+                    target_inclusion_bps: parse_bps(target_inclusion_bps)?,
+                })
+            }
+            _ => Err(anyhow!("Unknown bribe policy \"{}\"", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::utils::parse_ether;
+    use foundry_evm::executor::TxEnv;
+
+    use super::*;
+
+    /// Recipe whose frontrun is free, so all of `revenue` is bribeable
+    fn recipe_with_revenue(revenue: U256) -> SandwichRecipe {
+        SandwichRecipe::new(
+            vec![(TxEnv::default(), 0)],
+            vec![],
+            vec![],
+            vec![(TxEnv::default(), 100_000)],
+            revenue,
+            BlockInfo::default(),
+            BribePayment::default(),
+            true,
+            U256::zero(),
+        )
+    }
+
+    fn bribe_for(policy: &str, revenue: &str) -> U256 {
+        let recipe = recipe_with_revenue(parse_ether(revenue).unwrap());
+        policy
+            .parse::<BribePolicyConfig>()
+            .unwrap()
+            .into_policy()
+            .calculate_bribe(&recipe, &BlockInfo::default(), &BribeStats::default())
+            .unwrap()
+    }
+
+    /// revenue curve bribes the share of its closest points, interpolated linearly in between
+    #[test]
+    fn revenue_curve_interpolates_bribe_share() {
+        let curve = "curve:1=5000,10=9000";
+
+        // flat before the first and after the last point
+        assert_eq!(bribe_for(curve, "0.5"), parse_ether("0.25").unwrap());
+        assert_eq!(bribe_for(curve, "1"), parse_ether("0.5").unwrap());
+        assert_eq!(bribe_for(curve, "10"), parse_ether("9").unwrap());
+        assert_eq!(bribe_for(curve, "20"), parse_ether("18").unwrap());
+
+        // halfway between the points bribes halfway between their shares (7000 bps)
+        assert_eq!(bribe_for(curve, "5.5"), parse_ether("3.85").unwrap());
+
+        // points are sorted when parsed
+        assert_eq!(
+            bribe_for("curve:10=9000,1=5000", "5.5"),
+            parse_ether("3.85").unwrap()
+        );
+
+        // decreasing curves interpolate downwards
+        assert_eq!(
+            bribe_for("curve:1=9000,3=5000", "2"),
+            parse_ether("1.4").unwrap()
+        );
+    }
+
+    /// every policy parses from its config string, malformed configs are rejected
+    #[test]
+    fn bribe_policy_config_parses() {
+        assert!(matches!(
+            "fixed:9000".parse::<BribePolicyConfig>().unwrap(),
+            BribePolicyConfig::FixedPercentage { bps: 9000 }
+        ));
+        assert_eq!(bribe_for("fixed:9000", "1"), parse_ether("0.9").unwrap());
+
+        match "min-profit:0.1:9500".parse::<BribePolicyConfig>().unwrap() {
+            BribePolicyConfig::MinProfitFloor {
+                min_profit,
+                max_bps,
+            } => {
+                assert_eq!(min_profit, parse_ether("0.1").unwrap());
+                assert_eq!(max_bps, 9500);
+            }
+            config => panic!("parsed {:?}", config),
+        }
+        // floor binds before the max share does
+        assert_eq!(
+            bribe_for("min-profit:0.1:9500", "1"),
+            parse_ether("0.9").unwrap()
+        );
+
+        assert!(matches!(
+            "competitive:1000:9000:5000"
+                .parse::<BribePolicyConfig>()
+                .unwrap(),
+            BribePolicyConfig::Competitive {
+                min_bps: 1000,
+                max_bps: 9000,
+                target_inclusion_bps: 5000,
+            }
+        ));
+        // no history yet, competitive starts at its max share
+        assert_eq!(
+            bribe_for("competitive:1000:9000:5000", "1"),
+            parse_ether("0.9").unwrap()
+        );
+
+        for malformed in [
+            "",
+            "fixed",
+            "fixed:10001",
+            "fixed:ninety",
+            "min-profit:0.1",
+            "curve:1:5000",
+            "curve:one=5000",
+            "competitive:9000:1000:5000",
+            "unknown:1",
+        ] {
+            assert!(
+                malformed.parse::<BribePolicyConfig>().is_err(),
+                "{:?} parsed",
+                malformed
+            );
+        }
+
+        assert_eq!(
+            "coinbase".parse::<BribePayment>().unwrap(),
+            BribePayment::CoinbaseTransfer
+        );
+        assert_eq!(
+            "priority-fee".parse::<BribePayment>().unwrap(),
+            BribePayment::PriorityFee
+        );
+        assert!("miner".parse::<BribePayment>().is_err());
+    }
+}
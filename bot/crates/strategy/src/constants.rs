@@ -58,3 +58,34 @@ pub static COINBASE: Lazy<rAddress> = Lazy::new(|| {
 });
 
 pub static DUST_OVERPAY: Lazy<U256> = Lazy::new(|| ethers::utils::parse_ether("0.00015").unwrap());
+
+// inventory rebalances aren't competitive, they only tip enough to get included
+pub static REBALANCE_PRIORITY_FEE: Lazy<U256> = Lazy::new(|| U256::from(1_000_000_000u64));
+
+// routers whose swaps are decoded from a victim's calldata (uniswap v2 router02, sushiswap router,
+// swap router, swap router02 and both universal router deployments)
+pub static KNOWN_SWAP_ROUTERS: Lazy<[Address; 6]> = Lazy::new(|| {
+    [
+        "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D",
+        "0xd9e1cE17f2641f24aE83637ab66a2cca9C378B9F",
+        "0xE592427A0AEce92De3Edee1F18E0157C05861564",
+        "0x68b3465833fb72A70ecDF485E0e4C7bD8665Fc45",
+        "0xEf1c6E67703c7BD7107eed8303Fbe6EC2554BF6B",
+        "0x3fC91A3afd70395Cd496C647d5a6CC9D4B2b7FAD",
+    ]
+    .map(|router| router.parse().unwrap())
+});
+
+// canonical uniswap v2 weth pair of a token is derived from these (create2 address), flash swap
+// callbacks are checked against the pair the sandwich contract stored, not against this factory
+pub static UNISWAP_V2_FACTORY: Lazy<Address> = Lazy::new(|| {
+    "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f"
+        .parse()
+        .unwrap()
+});
+
+pub static UNISWAP_V2_PAIR_INIT_CODE_HASH: Lazy<H256> = Lazy::new(|| {
+    "0x96e8ac4277198ff8b6f785478aa9a39f403cb768dd02cbee326c3e7da348845f"
+        .parse()
+        .unwrap()
+});
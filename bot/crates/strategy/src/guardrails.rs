@@ -0,0 +1,534 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, ensure, Result};
+use ethers::types::{Address, TxHash, I256, U256, U64};
+use log::error;
+
+use crate::{
+    bribe_policy::BPS_DENOMINATOR,
+    types::{BlockInfo, SandwichRecipe},
+};
+
+/// File written when the kill switch trips, delete it to resume sandwiching
+pub const KILL_SWITCH_FILE: &str = ".sandwich-kill-switch";
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Limits every sandwich has to respect before it is sent to the relay
+/// All amounts are in wei, `None` means the limit is disabled
+#[derive(Debug, Clone, Default)]
+pub struct GuardrailConfig {
+    /// Minimum profit left after frontrun gas, bribe and dust overpay
+    pub min_profit_wei: U256,
+    /// Minimum profit as a share of the frontrun input (in bps)
+    pub min_profit_bps: u64,
+    /// Largest frontrun input allowed for a single sandwich
+    pub max_frontrun_per_sandwich: Option<U256>,
+    /// Largest sum of frontrun inputs allowed across all sandwiches targetting the same block
+    pub max_frontrun_per_block: Option<U256>,
+    /// Largest sum of frontrun inputs allowed into a single token over a day (landed sandwiches
+    /// plus bundles still pending)
+    pub max_token_exposure: Option<U256>,
+    /// Realized loss over a day at which the bot stops emitting actions
+    pub max_daily_loss: Option<U256>,
+}
+
+/// Enforces `GuardrailConfig` and keeps track of the state needed to do so
+pub struct Guardrails {
+    config: GuardrailConfig,
+    /// Kill switch state survives restarts through this file
+    kill_switch_path: PathBuf,
+    halted: bool,
+    /// Day (unix timestamp / 86400) that daily limits are accounted against
+    current_day: u64,
+    realized_pnl_today: I256,
+    /// Frontrun inputs of sandwiches that landed today, per intermediary token
+    token_exposure_today: HashMap<Address, U256>,
+    /// (intermediary token, frontrun input) of bundles sent to the relay, per target block and
+    /// victim (released once the target block is mined, what landed moves to
+    /// `token_exposure_today`)
+    pending_frontrun: BTreeMap<U64, HashMap<TxHash, Vec<(Address, U256)>>>,
+    /// Value held by searcher + sandwich contract at the last block our bundles landed
+    last_portfolio_value: Option<U256>,
+}
+
+impl Guardrails {
+    pub fn new(config: GuardrailConfig) -> Self {
+        Self::with_kill_switch_path(config, PathBuf::from(KILL_SWITCH_FILE))
+    }
+
+    /// Same as `new` with the kill switch kept in `kill_switch_path`
+    pub fn with_kill_switch_path(config: GuardrailConfig, kill_switch_path: PathBuf) -> Self {
+        let halted = kill_switch_path.exists();
+        if halted {
+            error!(
+                "kill switch is active, delete {:?} to resume sandwiching",
+                kill_switch_path
+            );
+        }
+
+        Self {
+            config,
+            kill_switch_path,
+            halted,
+            current_day: 0,
+            realized_pnl_today: I256::zero(),
+            token_exposure_today: HashMap::new(),
+            pending_frontrun: BTreeMap::new(),
+            last_portfolio_value: None,
+        }
+    }
+
+    /// True while the kill switch is active, no actions should be emitted
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Manually reset the kill switch and today's realized pnl
+    pub fn reset_kill_switch(&mut self) -> Result<()> {
+        if self.kill_switch_path.exists() {
+            std::fs::remove_file(&self.kill_switch_path)
+                .map_err(|e| anyhow!("Failed to remove kill switch file: {}", e))?;
+        }
+        self.halted = false;
+        self.realized_pnl_today = I256::zero();
+        Ok(())
+    }
+
+    /// Roll daily counters, release bundles that targetted `block` (or older) and pick up manual
+    /// kill switch resets
+    pub fn on_new_block(&mut self, block: &BlockInfo) {
+        self.pending_frontrun
+            .retain(|target_block, _| *target_block > block.number);
+
+        let day = block.timestamp.as_u64() / SECONDS_PER_DAY;
+        if day != self.current_day {
+            self.current_day = day;
+            self.realized_pnl_today = I256::zero();
+            self.token_exposure_today.clear();
+        }
+
+        // operator deleted the kill switch file
+        if self.halted && !self.kill_switch_path.exists() {
+            self.halted = false;
+            self.realized_pnl_today = I256::zero();
+        }
+    }
+
+    /// Feed the latest portfolio value (searcher + sandwich contract holdings)
+    /// `external_flow` is what deposits & withdrawals added to the value since the last one, it is
+    /// `Some` only for blocks where the value changed because of our sandwiches
+    pub fn record_portfolio_value(&mut self, value: U256, external_flow: Option<I256>) {
+        if let (Some(external_flow), Some(last_value)) = (external_flow, self.last_portfolio_value)
+        {
+            let pnl = I256::from_raw(value) - I256::from_raw(last_value) - external_flow;
+            self.realized_pnl_today += pnl;
+
+            if let Some(max_loss) = self.config.max_daily_loss {
+                if !self.halted && self.realized_pnl_today < -I256::from_raw(max_loss) {
+                    self.trip_kill_switch();
+                }
+            }
+        }
+        self.last_portfolio_value = Some(value);
+    }
+
+    fn trip_kill_switch(&mut self) {
+        self.halted = true;
+        let reason = format!(
+            "daily realized loss {} wei exceeded limit {:?} wei\n",
+            self.realized_pnl_today, self.config.max_daily_loss
+        );
+        if let Err(e) = std::fs::write(&self.kill_switch_path, &reason) {
+            error!("Failed to write kill switch file: {}", e);
+        }
+        error!(
+            "KILL SWITCH ACTIVE: {}delete {:?} to resume sandwiching",
+            reason, self.kill_switch_path
+        );
+    }
+
+    /// Largest frontrun input still allowed for a sandwich on `token` in `target_block`
+    pub fn max_frontrun_in(&self, target_block: U64, token: Address) -> Option<U256> {
+        [
+            self.remaining_block_frontrun_in(target_block, None),
+            self.remaining_token_exposure(token, None),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Frontrun inputs booked by pending bundles, leaving out what is booked for `victim` (bundles
+    /// for the same victim are alternatives, the one being checked replaces it)
+    fn pending_exposure(
+        victims: &HashMap<TxHash, Vec<(Address, U256)>>,
+        victim: Option<TxHash>,
+    ) -> impl Iterator<Item = (Address, U256)> + '_ {
+        victims
+            .iter()
+            .filter(move |(hash, _)| Some(**hash) != victim)
+            .flat_map(|(_, exposure)| exposure.iter().copied())
+    }
+
+    /// Largest frontrun input (all legs) still allowed for a sandwich in `target_block`
+    fn remaining_block_frontrun_in(
+        &self,
+        target_block: U64,
+        victim: Option<TxHash>,
+    ) -> Option<U256> {
+        let block_used = self
+            .pending_frontrun
+            .get(&target_block)
+            .map(|victims| {
+                Self::pending_exposure(victims, victim)
+                    .fold(U256::zero(), |acc, (_, amount)| acc + amount)
+            })
+            .unwrap_or_default();
+
+        [
+            self.config.max_frontrun_per_sandwich,
+            self.config
+                .max_frontrun_per_block
+                .map(|max| max.saturating_sub(block_used)),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Largest frontrun input still allowed into `token` today
+    fn remaining_token_exposure(&self, token: Address, victim: Option<TxHash>) -> Option<U256> {
+        // bundles still pending may land too
+        let token_used = self
+            .pending_frontrun
+            .values()
+            .flat_map(|victims| Self::pending_exposure(victims, victim))
+            .filter(|(t, _)| *t == token)
+            .fold(
+                self.token_exposure_today
+                    .get(&token)
+                    .copied()
+                    .unwrap_or_default(),
+                |acc, (_, amount)| acc + amount,
+            );
+
+        self.config
+            .max_token_exposure
+            .map(|max| max.saturating_sub(token_used))
+    }
+
+    /// Check if a sandwich passes all guardrails
+    /// `bribe_amount` is the amount decided by the `BribePolicy` (before dust overpay)
+    /// `victim` is the tx the sandwich was built for
+    pub fn check(&self, recipe: &SandwichRecipe, victim: TxHash, bribe_amount: U256) -> Result<()> {
+        ensure!(!self.halted, "[GUARDRAIL] kill switch is active");
+
+        let frontrun_in = recipe.get_frontrun_in();
+        if let Some(max) =
+            self.remaining_block_frontrun_in(recipe.get_target_block().number, Some(victim))
+        {
+            ensure!(
+                frontrun_in <= max,
+                "[GUARDRAIL] frontrun input {} exceeds remaining limit {}",
+                frontrun_in,
+                max
+            );
+        }
+
+        // every leg counts towards the exposure of the token it buys
+        let mut token_in: HashMap<Address, U256> = HashMap::new();
+        for (token, leg_in) in recipe.get_frontrun_exposure() {
+            *token_in.entry(*token).or_default() += leg_in;
+        }
+        for (token, token_in) in token_in {
+            if let Some(max) = self.remaining_token_exposure(token, Some(victim)) {
+                ensure!(
+                    token_in <= max,
+                    "[GUARDRAIL] frontrun input {} into {:?} exceeds remaining exposure {}",
+                    token_in,
+                    token,
+                    max
+                );
+            }
+        }
+
+        let frontrun_fee =
+            U256::from(recipe.get_frontrun_gas_used()) * recipe.get_target_block().base_fee_per_gas;
+        let profit = recipe
+            .get_revenue()
+            .checked_sub(frontrun_fee + bribe_amount + recipe.get_dust_overpay())
+            .ok_or_else(|| anyhow!("[GUARDRAIL] sandwich has negative net profit"))?;
+
+        ensure!(
+            profit >= self.config.min_profit_wei,
+            "[GUARDRAIL] net profit {} below minimum {}",
+            profit,
+            self.config.min_profit_wei
+        );
+
+        if self.config.min_profit_bps > 0 {
+            ensure!(
+                !frontrun_in.is_zero()
+                    && profit * BPS_DENOMINATOR / frontrun_in
+                        >= U256::from(self.config.min_profit_bps),
+                "[GUARDRAIL] net profit {} below {} bps of frontrun input {}",
+                profit,
+                self.config.min_profit_bps,
+                frontrun_in
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Account for a sandwich on `victim` that is being sent to the relay (until `target_block` is
+    /// mined), `exposure` is the (intermediary token, frontrun input) of each of its legs
+    /// Only one of the bundles sent for a victim can land, the largest one stays booked
+    pub fn record_sandwich(
+        &mut self,
+        target_block: U64,
+        victim: TxHash,
+        exposure: &[(Address, U256)],
+    ) {
+        let total = |exposure: &[(Address, U256)]| {
+            exposure
+                .iter()
+                .fold(U256::zero(), |acc, (_, amount)| acc + amount)
+        };
+
+        let booked = self
+            .pending_frontrun
+            .entry(target_block)
+            .or_default()
+            .entry(victim)
+            .or_default();
+        if booked.is_empty() || total(exposure) > total(booked) {
+            *booked = exposure.to_vec();
+        }
+    }
+
+    /// Account for sandwiches that landed, their frontrun inputs count towards today's exposure
+    pub fn record_landed(&mut self, exposure: &[(Address, U256)]) {
+        for (token, frontrun_in) in exposure {
+            *self.token_exposure_today.entry(*token).or_default() += frontrun_in;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::utils::parse_ether;
+    use foundry_evm::executor::TxEnv;
+
+    use super::*;
+    use crate::bribe_policy::BribePayment;
+
+    /// Recipe whose frontrun is free, so all of `revenue` is bribeable
+    fn recipe_with_revenue(revenue: U256) -> SandwichRecipe {
+        recipe_with_exposure(revenue, vec![])
+    }
+
+    /// Gas free recipe (with dust) putting `frontrun_exposure` into the sandwich
+    fn recipe_with_exposure(
+        revenue: U256,
+        frontrun_exposure: Vec<(Address, U256)>,
+    ) -> SandwichRecipe {
+        SandwichRecipe::new(
+            vec![(TxEnv::default(), 0)],
+            frontrun_exposure,
+            vec![],
+            vec![(TxEnv::default(), 100_000)],
+            revenue,
+            BlockInfo::default(),
+            BribePayment::default(),
+            true,
+            U256::zero(),
+        )
+    }
+
+    /// Guardrails keeping their kill switch in a file of their own (tests run in parallel)
+    fn guardrails_with(config: GuardrailConfig, name: &str) -> (Guardrails, PathBuf) {
+        let kill_switch_path = std::env::temp_dir().join(format!(
+            "{}-{}-{}",
+            KILL_SWITCH_FILE,
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&kill_switch_path);
+        (
+            Guardrails::with_kill_switch_path(config, kill_switch_path.clone()),
+            kill_switch_path,
+        )
+    }
+
+    /// frontrun caps count pending bundles until their target block is mined, and only what landed
+    /// towards the day's token exposure
+    #[test]
+    fn guardrail_caps_release_pending_bundles() {
+        let token = Address::from_low_u64_be(1);
+        let other_token = Address::from_low_u64_be(2);
+        let victim = TxHash::from_low_u64_be(1);
+        let other_victim = TxHash::from_low_u64_be(2);
+        let (mut guardrails, _) = guardrails_with(
+            GuardrailConfig {
+                max_frontrun_per_sandwich: Some(U256::from(10)),
+                max_frontrun_per_block: Some(U256::from(8)),
+                max_token_exposure: Some(U256::from(12)),
+                ..Default::default()
+            },
+            "caps",
+        );
+        let block = |number: u64, timestamp: u64| BlockInfo {
+            number: number.into(),
+            timestamp: timestamp.into(),
+            ..Default::default()
+        };
+        let revenue = parse_ether("1").unwrap();
+
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::zero(), token),
+            Some(U256::from(8))
+        );
+        assert!(guardrails
+            .check(
+                &recipe_with_exposure(revenue, vec![(token, U256::from(8))]),
+                victim,
+                U256::zero()
+            )
+            .is_ok());
+        assert!(guardrails
+            .check(
+                &recipe_with_exposure(revenue, vec![(token, U256::from(9))]),
+                victim,
+                U256::zero()
+            )
+            .is_err());
+
+        // pending bundle uses up the block's cap and the token's exposure
+        guardrails.record_sandwich(U64::zero(), victim, &[(token, U256::from(5))]);
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::zero(), token),
+            Some(U256::from(3))
+        );
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::zero(), other_token),
+            Some(U256::from(3))
+        );
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::one(), token),
+            Some(U256::from(7))
+        );
+        assert!(guardrails
+            .check(
+                &recipe_with_exposure(revenue, vec![(token, U256::from(4))]),
+                other_victim,
+                U256::zero()
+            )
+            .is_err());
+
+        // bundles for the same victim are alternatives, only the largest one stays booked
+        assert!(guardrails
+            .check(
+                &recipe_with_exposure(revenue, vec![(token, U256::from(8))]),
+                victim,
+                U256::zero()
+            )
+            .is_ok());
+        guardrails.record_sandwich(U64::zero(), victim, &[(token, U256::from(7))]);
+        guardrails.record_sandwich(U64::zero(), victim, &[(token, U256::from(2))]);
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::zero(), token),
+            Some(U256::from(1))
+        );
+
+        // target block was mined without the bundle, caps are released
+        guardrails.on_new_block(&block(0, 0));
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::one(), token),
+            Some(U256::from(8))
+        );
+
+        // landed sandwiches count towards the day's exposure until the day rolls
+        guardrails.record_landed(&[(token, U256::from(6))]);
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::one(), token),
+            Some(U256::from(6))
+        );
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::one(), other_token),
+            Some(U256::from(8))
+        );
+        guardrails.on_new_block(&block(7200, 86_400));
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::from(7201), token),
+            Some(U256::from(8))
+        );
+
+        // every leg of a multi leg sandwich counts against the token it buys
+        guardrails.record_landed(&[(other_token, U256::from(9))]);
+        let legs = vec![(token, U256::from(3)), (other_token, U256::from(4))];
+        assert!(guardrails
+            .check(&recipe_with_exposure(revenue, legs), victim, U256::zero())
+            .is_err());
+        guardrails.record_sandwich(
+            U64::from(7201),
+            victim,
+            &[(token, U256::from(3)), (other_token, U256::from(3))],
+        );
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::from(7201), token),
+            Some(U256::from(2))
+        );
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::from(7202), token),
+            Some(U256::from(8))
+        );
+        assert_eq!(
+            guardrails.max_frontrun_in(U64::from(7202), other_token),
+            Some(U256::zero())
+        );
+    }
+
+    /// kill switch trips on the day's realized loss (deposits & withdrawals left out), survives
+    /// restarts and resumes once its file is deleted
+    #[test]
+    fn guardrail_kill_switch_trips_on_daily_loss() {
+        let config = GuardrailConfig {
+            max_daily_loss: Some(U256::from(10)),
+            ..Default::default()
+        };
+        let (mut guardrails, kill_switch_path) = guardrails_with(config.clone(), "kill-switch");
+        let recipe = recipe_with_revenue(parse_ether("1").unwrap());
+
+        guardrails.record_portfolio_value(U256::from(100), None);
+        // value changes in blocks without our bundles aren't pnl
+        guardrails.record_portfolio_value(U256::from(50), None);
+        guardrails.record_portfolio_value(U256::from(100), None);
+        // a 60 wei deposit landed next to a sandwich that lost 10 wei
+        guardrails.record_portfolio_value(U256::from(150), Some(I256::from(60)));
+        assert!(!guardrails.is_halted());
+        // a 50 wei withdrawal landed next to a sandwich that lost 5 wei
+        guardrails.record_portfolio_value(U256::from(95), Some(I256::from(-50)));
+        assert!(guardrails.is_halted());
+        assert!(kill_switch_path.exists());
+        assert!(guardrails
+            .check(&recipe, TxHash::zero(), U256::zero())
+            .is_err());
+
+        // restarting doesn't resume sandwiching
+        let mut guardrails = Guardrails::with_kill_switch_path(config, kill_switch_path.clone());
+        assert!(guardrails.is_halted());
+
+        // operator deletes the file
+        std::fs::remove_file(&kill_switch_path).unwrap();
+        guardrails.on_new_block(&BlockInfo::default());
+        assert!(!guardrails.is_halted());
+        assert!(guardrails
+            .check(&recipe, TxHash::zero(), U256::zero())
+            .is_ok());
+    }
+}
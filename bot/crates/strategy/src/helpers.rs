@@ -6,7 +6,7 @@ use ethers::{
             eip2718::TypedTransaction,
             eip2930::{AccessList, AccessListItem},
         },
-        BigEndianHash, Bytes, Eip1559TransactionRequest, H256,
+        Address, BigEndianHash, Bytes, Eip1559TransactionRequest, H256,
     },
 };
 use foundry_evm::{
@@ -14,6 +14,8 @@ use foundry_evm::{
     utils::{b160_to_h160, h160_to_b160, ru256_to_u256, u256_to_ru256},
 };
 
+use crate::constants::{UNISWAP_V2_FACTORY, UNISWAP_V2_PAIR_INIT_CODE_HASH, WETH_ADDRESS};
+
 /// Sign eip1559 transactions
 pub async fn sign_eip1559(
     tx: Eip1559TransactionRequest,
@@ -28,6 +30,22 @@ pub async fn sign_eip1559(
     Ok(tx_typed.rlp_signed(&signed_frontrun_tx_sig))
 }
 
+/// Address of the uniswap v2 factory's weth pair for `token`
+pub fn uniswap_v2_weth_pair(token: Address) -> Address {
+    let (token0, token1) = if token < *WETH_ADDRESS {
+        (token, *WETH_ADDRESS)
+    } else {
+        (*WETH_ADDRESS, token)
+    };
+    let salt = ethers::utils::keccak256([token0.as_bytes(), token1.as_bytes()].concat());
+
+    ethers::utils::get_create2_address_from_hash(
+        *UNISWAP_V2_FACTORY,
+        salt,
+        *UNISWAP_V2_PAIR_INIT_CODE_HASH,
+    )
+}
+
 /// convert revm access list to ethers access list
 pub fn access_list_to_ethers(access_list: Vec<(B160, Vec<rU256>)>) -> AccessList {
     AccessList::from(
@@ -80,7 +98,7 @@ macro_rules! log_not_sandwichable {
 
 #[macro_export]
 macro_rules! log_opportunity {
-    ($meats:expr, $optimal_input:expr, $revenue:expr) => {{
+    ($meats:expr, $optimal_input:expr, $revenue:expr, $dust_overpay:expr, $encoding_loss:expr, $slippage:expr) => {{
         info!("\n{}", "[OPPORTUNITY DETECTED]".green().on_black().bold());
         info!(
             "{}",
@@ -102,6 +120,30 @@ macro_rules! log_opportunity {
             )
             .bold()
         );
+        info!(
+            "{}",
+            format!(
+                "dust_overpay : {} wETH",
+                $dust_overpay.to_string().green().on_black()
+            )
+            .bold()
+        );
+        info!(
+            "{}",
+            format!(
+                "encoding_loss: {} wETH",
+                $encoding_loss.to_string().green().on_black()
+            )
+            .bold()
+        );
+        info!(
+            "{}",
+            format!(
+                "slippage     : {}",
+                $slippage.to_string().green().on_black()
+            )
+            .bold()
+        );
     }};
 }
 
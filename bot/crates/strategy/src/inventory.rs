@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, U256};
+
+use crate::base_token::BaseToken;
+
+/// Longtail token held on the sandwich contract to sandwich `token->weth` victims without a flash
+/// swap (frontrun sells inventory, backrun buys the same amount back)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InventoryToken {
+    /// Token address & `balanceOf` mapping slot (used to fund simulations)
+    pub token: BaseToken,
+    /// How much of the token the bot aims to hold
+    pub target: U256,
+}
+
+/// Trade that brings an inventory token back to its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rebalance {
+    /// Buy this amount of token with weth
+    Buy(U256),
+    /// Sell this amount of token for weth
+    Sell(U256),
+}
+
+impl InventoryToken {
+    /// Returns the trade needed when `balance` drifts more than `band_bps` away from target
+    pub fn rebalance(&self, balance: U256, band_bps: u64) -> Option<Rebalance> {
+        let band = self.target * band_bps / 10_000;
+
+        if balance + band < self.target {
+            Some(Rebalance::Buy(self.target - balance))
+        } else if balance > self.target + band {
+            Some(Rebalance::Sell(balance - self.target))
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses `<address>:<balance slot>:<target amount>`
+impl FromStr for InventoryToken {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (token, target) = s
+            .trim()
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow!("Malformed inventory token \"{}\"", s))?;
+
+        Ok(Self {
+            token: token.parse()?,
+            target: U256::from_dec_str(target)
+                .map_err(|e| anyhow!("Failed to parse inventory target \"{}\": {}", target, e))?,
+        })
+    }
+}
+
+/// Basket of longtail tokens the bot keeps on the sandwich contract
+#[derive(Debug, Clone)]
+pub struct InventoryConfig {
+    tokens: Vec<InventoryToken>,
+    /// Blocks between rebalances
+    pub rebalance_interval: u64,
+    /// How far (in bps of target) a balance can drift before it is rebalanced
+    pub rebalance_band_bps: u64,
+}
+
+impl InventoryConfig {
+    pub fn new(tokens: Vec<InventoryToken>) -> Self {
+        Self {
+            tokens: tokens.into_iter().filter(|t| !t.token.is_weth()).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn get_tokens(&self) -> &Vec<InventoryToken> {
+        &self.tokens
+    }
+
+    pub fn get(&self, address: Address) -> Option<InventoryToken> {
+        self.tokens
+            .iter()
+            .find(|t| t.token.address == address)
+            .copied()
+    }
+}
+
+/// Empty basket (inventory sandwiches are disabled)
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        Self {
+            tokens: vec![],
+            rebalance_interval: 50,
+            rebalance_band_bps: 2000,
+        }
+    }
+}
+
+/// Parses a comma separated list of `<address>:<balance slot>:<target amount>`
+impl FromStr for InventoryConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = s
+            .split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(InventoryToken::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(tokens))
+    }
+}
@@ -3,12 +3,30 @@ mod constants;
 mod helpers;
 mod simulator;
 
+/// Module contains the searches for a sandwich's optimal frontrun input
+pub use simulator::search_strategy;
+
 /// Module contains logic to manage info on onchain pools
 mod managers;
 
 /// Module contains logic related to transaction building
 mod tx_utils;
 
+/// Module contains policies deciding how much revenue is bribed to builders
+pub mod bribe_policy;
+
+/// Module contains profit and risk limits checked before sandwiches are sent
+pub mod guardrails;
+
+/// Module contains the tokens that sandwiches start and end with
+pub mod base_token;
+
+/// Module contains the chain config and block environment simulations run with
+pub mod block_env;
+
+/// Module contains the longtail token basket held for inventory sandwiches
+pub mod inventory;
+
 /// Module contains core strategy implementation
 pub mod bot;
 
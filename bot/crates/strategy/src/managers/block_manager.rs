@@ -5,18 +5,20 @@ use std::sync::Arc;
 
 use colored::Colorize;
 
-use crate::{startup_info_log, types::BlockInfo};
+use crate::{block_env::ChainConfig, startup_info_log, types::BlockInfo};
 
 pub struct BlockManager {
     latest_block: BlockInfo,
     next_block: BlockInfo,
+    chain: ChainConfig,
 }
 
 impl BlockManager {
-    pub fn new() -> Self {
+    pub fn new(chain: ChainConfig) -> Self {
         Self {
             latest_block: BlockInfo::default(),
             next_block: BlockInfo::default(),
+            chain,
         }
     }
 
@@ -48,6 +50,6 @@ impl BlockManager {
     pub fn update_block_info<T: Into<BlockInfo>>(&mut self, latest_block: T) {
         let latest_block: BlockInfo = latest_block.into();
         self.latest_block = latest_block;
-        self.next_block = latest_block.get_next_block();
+        self.next_block = self.chain.next_block(&latest_block);
     }
 }
@@ -0,0 +1,137 @@
+use cfmms::pool::Pool;
+use ethers::{
+    providers::Middleware,
+    types::{Address, U64},
+};
+use foundry_evm::{
+    executor::fork::{BlockchainDb, BlockchainDbMeta, SharedBackend},
+    revm::{
+        db::DatabaseRef,
+        primitives::{BlockEnv, U256 as rU256},
+    },
+    utils::h160_to_b160,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use crate::{base_token::BaseToken, block_env::ChainConfig, types::BlockInfo};
+
+/// How many of the most active pools are prefetched into each new fork
+const MAX_PREFETCH_POOLS: usize = 50;
+
+/// `UniswapV2Pair` slot holding `reserve0`, `reserve1` & `blockTimestampLast`
+const V2_RESERVES_SLOT: u64 = 8;
+
+/// `UniswapV3Pool` slots holding `slot0` & `liquidity`
+const V3_SLOT0_SLOT: u64 = 0;
+const V3_LIQUIDITY_SLOT: u64 = 4;
+
+/// Keeps a single fork backend per block so that every analysis for the upcoming block shares
+/// the state already fetched by the others, the previous block's fork is dropped on new blocks
+pub struct ForkManager {
+    /// (fork block, backend forked from it)
+    fork: Option<(U64, SharedBackend)>,
+    /// How often each pool was touched by victims, halved every block
+    activity: HashMap<Address, (Pool, u64)>,
+    chain: ChainConfig,
+}
+
+impl ForkManager {
+    pub fn new(chain: ChainConfig) -> Self {
+        Self {
+            fork: None,
+            activity: HashMap::new(),
+            chain,
+        }
+    }
+
+    /// Fork from the new block and prefetch the state of the most active pools into it
+    pub fn on_new_block<M: Middleware + 'static>(&mut self, latest_block: U64, provider: Arc<M>) {
+        let backend = spawn_backend(provider, latest_block, &self.chain);
+
+        for (pool, _) in self.most_active_pools() {
+            let backend = backend.clone();
+            tokio::task::spawn_blocking(move || prefetch_pool(&backend, pool));
+        }
+
+        self.fork = Some((latest_block, backend));
+
+        // older activity matters less
+        self.activity.retain(|_, (_, score)| {
+            *score /= 2;
+            *score > 0
+        });
+    }
+
+    /// Backend forked from the block before `target_block`, shares the block's fork if it has
+    /// one (a fresh backend is spawned for any other block)
+    pub fn get_backend<M: Middleware + 'static>(
+        &self,
+        target_block: &BlockInfo,
+        provider: Arc<M>,
+    ) -> SharedBackend {
+        let fork_block = target_block.number - 1;
+        match &self.fork {
+            Some((block, backend)) if *block == fork_block => backend.clone(),
+            _ => spawn_backend(provider, fork_block, &self.chain),
+        }
+    }
+
+    /// Record pools touched by a victim
+    pub fn record_activity(&mut self, pools: impl IntoIterator<Item = Pool>) {
+        for pool in pools {
+            self.activity.entry(pool.address()).or_insert((pool, 0)).1 += 1;
+        }
+    }
+
+    fn most_active_pools(&self) -> Vec<(Pool, u64)> {
+        let mut pools: Vec<(Pool, u64)> = self.activity.values().cloned().collect();
+        pools.sort_by(|a, b| b.1.cmp(&a.1));
+        pools.truncate(MAX_PREFETCH_POOLS);
+        pools
+    }
+}
+
+/// Setup shared backend forked from `fork_block`
+fn spawn_backend<M: Middleware + 'static>(
+    provider: Arc<M>,
+    fork_block: U64,
+    chain: &ChainConfig,
+) -> SharedBackend {
+    SharedBackend::spawn_backend_thread(
+        provider,
+        BlockchainDb::new(
+            BlockchainDbMeta {
+                cfg_env: chain.cfg_env(),
+                block_env: BlockEnv {
+                    number: rU256::from(fork_block.as_u64()),
+                    ..Default::default()
+                },
+                hosts: BTreeSet::from(["".to_string()]),
+            },
+            None,
+        ),
+        Some(fork_block.into()),
+    )
+}
+
+/// Load a pool's account, reserves/slot0 and weth balance into the backend's cache
+/// (best effort, forks with a different storage layout just prefetch unused slots)
+fn prefetch_pool(backend: &SharedBackend, pool: Pool) {
+    let address = h160_to_b160(pool.address());
+    let slots: &[u64] = match pool {
+        Pool::UniswapV2(_) => &[V2_RESERVES_SLOT],
+        Pool::UniswapV3(_) => &[V3_SLOT0_SLOT, V3_LIQUIDITY_SLOT],
+    };
+
+    let _ = backend.basic(address);
+    for slot in slots {
+        let _ = backend.storage(address, rU256::from(*slot));
+    }
+
+    let weth = BaseToken::weth();
+    let weth_balance_slot = rU256::from_be_bytes(weth.balance_storage_key(pool.address()));
+    let _ = backend.storage(h160_to_b160(weth.address), weth_balance_slot);
+}
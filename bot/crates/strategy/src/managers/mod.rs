@@ -1,3 +1,10 @@
 pub(crate) mod block_manager;
+pub(crate) mod fork_manager;
+pub(crate) mod opportunity_book_manager;
+pub(crate) mod opportunity_scheduler_manager;
+pub(crate) mod pending_approval_manager;
+pub(crate) mod pending_tx_manager;
+pub(crate) mod pending_victim_manager;
 pub(crate) mod pool_manager;
 pub(crate) mod sandwich_state_manager;
+pub(crate) mod searcher_nonce_manager;
@@ -0,0 +1,113 @@
+use ethers::types::{Address, Transaction, U256, U64};
+use std::collections::HashMap;
+
+/// Most meats wrapped by a single frontrun/backrun pair
+const MAX_MEATS_PER_SANDWICH: usize = 5;
+
+/// Sandwichable victims seen on a pool for the upcoming block
+#[derive(Default)]
+struct PoolBook {
+    /// Best known ordered set of meats and the revenue it produces
+    best_meats: Vec<Transaction>,
+    best_revenue: U256,
+}
+
+/// Collects sandwichable victims per pool for the upcoming block so that several of them
+/// can be wrapped in one frontrun/backrun pair
+pub struct OpportunityBookManager {
+    target_block: U64,
+    books: HashMap<Address, PoolBook>,
+}
+
+impl OpportunityBookManager {
+    pub fn new() -> Self {
+        Self {
+            target_block: U64::zero(),
+            books: HashMap::new(),
+        }
+    }
+
+    /// Books only hold victims for a single block, reset when the target block changes
+    fn get_book(&mut self, target_block: U64, pool: Address) -> &mut PoolBook {
+        if target_block != self.target_block {
+            self.target_block = target_block;
+            self.books.clear();
+        }
+        self.books.entry(pool).or_default()
+    }
+
+    /// Add a victim that was found sandwichable on its own with `revenue`
+    /// `victim` is the swap preceded by the pending txs it depends on (kept together in batches)
+    /// Returns the meat orderings worth simulating that batch the victim with the pool's best set
+    pub fn add_victim(
+        &mut self,
+        target_block: U64,
+        pool: Address,
+        victim: Vec<Transaction>,
+        revenue: U256,
+    ) -> Vec<Vec<Transaction>> {
+        let book = self.get_book(target_block, pool);
+
+        if book
+            .best_meats
+            .iter()
+            .any(|m| victim.iter().any(|v| v.hash == m.hash))
+        {
+            return vec![];
+        }
+
+        let mut candidates = vec![];
+        if !book.best_meats.is_empty()
+            && book.best_meats.len() + victim.len() <= MAX_MEATS_PER_SANDWICH
+        {
+            // try the new victim at every position of the current best ordering
+            for position in 0..=book.best_meats.len() {
+                let mut meats = book.best_meats.clone();
+                meats.splice(position..position, victim.iter().cloned());
+                if is_nonce_ordered(&meats) {
+                    candidates.push(meats);
+                }
+            }
+        }
+
+        if revenue > book.best_revenue {
+            book.best_meats = victim;
+            book.best_revenue = revenue;
+        }
+
+        candidates
+    }
+
+    /// Record the revenue of a simulated set of meats
+    /// Returns true if it beats the best known set for the pool (and should be submitted)
+    pub fn record_result(
+        &mut self,
+        target_block: U64,
+        pool: Address,
+        meats: Vec<Transaction>,
+        revenue: U256,
+    ) -> bool {
+        let book = self.get_book(target_block, pool);
+
+        if revenue <= book.best_revenue {
+            return false;
+        }
+
+        book.best_meats = meats;
+        book.best_revenue = revenue;
+        true
+    }
+}
+
+/// Txs from the same sender have to appear in nonce order
+fn is_nonce_ordered(meats: &[Transaction]) -> bool {
+    let mut last_nonces: HashMap<Address, U256> = HashMap::new();
+    for meat in meats {
+        if let Some(last_nonce) = last_nonces.insert(meat.from, meat.nonce) {
+            if meat.nonce <= last_nonce {
+                return false;
+            }
+        }
+    }
+    true
+}
@@ -0,0 +1,391 @@
+use ethers::types::{Address, H256, U256, U64};
+use std::collections::HashSet;
+
+use crate::types::{SandwichLeg, SandwichRecipe};
+
+/// Most candidates kept per block (finding the best compatible set is exponential in this)
+const MAX_CANDIDATES: usize = 16;
+
+/// Onchain state a sandwich depends on, two sandwiches that share any of it can't land together
+#[derive(Clone, Default)]
+pub struct Footprint {
+    /// Pools whose reserves the sandwich moves (target & flash pools)
+    pools: HashSet<Address>,
+    /// Tokens whose sandwich contract balance the sandwich moves (intermediary & non weth base
+    /// tokens, weth is shared and budgeted instead)
+    tokens: HashSet<Address>,
+    /// Senders of the meats (a tx can only land once and nonces have to line up)
+    senders: HashSet<Address>,
+    /// Weth the frontrun/s take out of the sandwich contract
+    weth_spend: U256,
+    /// Eth paid to the builder out of the sandwich contract
+    coinbase_spend: U256,
+}
+
+impl Footprint {
+    pub fn new(legs: &[SandwichLeg], recipe: &SandwichRecipe, coinbase_spend: U256) -> Self {
+        let mut footprint = Self {
+            coinbase_spend,
+            ..Default::default()
+        };
+
+        for leg in legs {
+            footprint.pools.insert(leg.target_pool.address());
+            if let Some(flash_pool) = leg.flash_pool {
+                footprint.pools.insert(flash_pool.address);
+            }
+
+            footprint.tokens.insert(leg.intermediary_token);
+            if !leg.start_end_token.is_weth() {
+                footprint.tokens.insert(leg.start_end_token.address);
+            }
+        }
+
+        // inventory legs receive weth in their frontrun instead of spending it
+        if legs
+            .iter()
+            .all(|leg| leg.start_end_token.is_weth() && leg.inventory_token.is_none())
+        {
+            footprint.weth_spend = recipe.get_frontrun_in();
+        }
+
+        footprint.senders = recipe.get_meats_ref().iter().map(|m| m.from).collect();
+
+        footprint
+    }
+
+    fn conflicts_with(&self, other: &Footprint) -> bool {
+        !self.pools.is_disjoint(&other.pools)
+            || !self.tokens.is_disjoint(&other.tokens)
+            || !self.senders.is_disjoint(&other.senders)
+    }
+}
+
+/// A priced sandwich that could be part of the block's merged bundle
+struct Candidate {
+    recipe: SandwichRecipe,
+    bribe_amount: U256,
+    footprint: Footprint,
+    /// Revenue left after paying the bribe
+    value: U256,
+}
+
+/// Collects every sandwich found for the upcoming block and picks the most valuable set of them
+/// that can land together, so that they can be merged into one bundle with sequential nonces
+/// (separate bundles all use the same searcher nonces and at most one of them can land)
+pub struct OpportunitySchedulerManager {
+    target_block: U64,
+    candidates: Vec<Candidate>,
+    /// Meats of the last scheduled set (avoids resubmitting the same merged bundle)
+    last_scheduled: Vec<H256>,
+}
+
+impl OpportunitySchedulerManager {
+    pub fn new() -> Self {
+        Self {
+            target_block: U64::zero(),
+            candidates: vec![],
+            last_scheduled: vec![],
+        }
+    }
+
+    /// Candidates only live for a single block, reset when the target block changes
+    fn reset_if_stale(&mut self, target_block: U64) {
+        if target_block != self.target_block {
+            self.target_block = target_block;
+            self.candidates.clear();
+            self.last_scheduled.clear();
+        }
+    }
+
+    /// Add a sandwich that passed guardrails with its bribe
+    pub fn add_candidate(
+        &mut self,
+        target_block: U64,
+        recipe: SandwichRecipe,
+        bribe_amount: U256,
+        footprint: Footprint,
+    ) {
+        self.reset_if_stale(target_block);
+
+        let value = recipe.get_revenue().saturating_sub(bribe_amount);
+        self.candidates.push(Candidate {
+            recipe,
+            bribe_amount,
+            footprint,
+            value,
+        });
+
+        // keep the most valuable candidates
+        self.candidates.sort_by(|a, b| b.value.cmp(&a.value));
+        self.candidates.truncate(MAX_CANDIDATES);
+    }
+
+    /// Most valuable set of candidates that can land together within the sandwich contract's weth
+    /// inventory & eth balance
+    /// Returns (recipe, bribe) pairs when the set merges several sandwiches and changed since last
+    /// call
+    pub fn schedule(
+        &mut self,
+        target_block: U64,
+        weth_inventory: U256,
+        eth_balance: U256,
+    ) -> Option<Vec<(SandwichRecipe, U256)>> {
+        self.reset_if_stale(target_block);
+
+        let mut best = Selection::default();
+        self.search(
+            0,
+            &mut Selection::default(),
+            &mut best,
+            weth_inventory,
+            eth_balance,
+        );
+
+        if best.indices.len() < 2 {
+            return None;
+        }
+
+        let scheduled: Vec<H256> = best
+            .indices
+            .iter()
+            .flat_map(|&i| {
+                self.candidates[i]
+                    .recipe
+                    .get_meats_ref()
+                    .iter()
+                    .map(|m| m.hash)
+            })
+            .collect();
+        if scheduled == self.last_scheduled {
+            return None;
+        }
+        self.last_scheduled = scheduled;
+
+        Some(
+            best.indices
+                .iter()
+                .map(|&i| {
+                    let candidate = &self.candidates[i];
+                    (candidate.recipe.clone(), candidate.bribe_amount)
+                })
+                .collect(),
+        )
+    }
+
+    /// Branch and bound over candidates (sorted by value, so the remaining sum is a tight bound)
+    fn search(
+        &self,
+        next: usize,
+        current: &mut Selection,
+        best: &mut Selection,
+        weth_inventory: U256,
+        eth_balance: U256,
+    ) {
+        if current.value > best.value {
+            *best = current.clone();
+        }
+
+        let remaining = self.candidates[next..]
+            .iter()
+            .fold(U256::zero(), |sum, c| sum + c.value);
+        if current.value + remaining <= best.value {
+            return;
+        }
+
+        for i in next..self.candidates.len() {
+            let candidate = &self.candidates[i];
+
+            let fits = current.weth_spend + candidate.footprint.weth_spend <= weth_inventory
+                && current.coinbase_spend + candidate.footprint.coinbase_spend <= eth_balance
+                && current.indices.iter().all(|&j| {
+                    !self.candidates[j]
+                        .footprint
+                        .conflicts_with(&candidate.footprint)
+                });
+            if !fits {
+                continue;
+            }
+
+            current.indices.push(i);
+            current.value += candidate.value;
+            current.weth_spend += candidate.footprint.weth_spend;
+            current.coinbase_spend += candidate.footprint.coinbase_spend;
+
+            self.search(i + 1, current, best, weth_inventory, eth_balance);
+
+            current.indices.pop();
+            current.value -= candidate.value;
+            current.weth_spend -= candidate.footprint.weth_spend;
+            current.coinbase_spend -= candidate.footprint.coinbase_spend;
+        }
+    }
+}
+
+/// Set of compatible candidates explored during search
+#[derive(Clone, Default)]
+struct Selection {
+    indices: Vec<usize>,
+    value: U256,
+    weth_spend: U256,
+    coinbase_spend: U256,
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::Transaction;
+    use foundry_evm::executor::TxEnv;
+
+    use super::*;
+    use crate::{bribe_policy::BribePayment, types::BlockInfo};
+
+    fn address(id: u64) -> Address {
+        Address::from_low_u64_be(id)
+    }
+
+    /// Footprint moving a single pool and spending `weth_spend`
+    fn footprint(pool: u64, weth_spend: u64) -> Footprint {
+        Footprint {
+            pools: HashSet::from([address(pool)]),
+            weth_spend: U256::from(weth_spend),
+            ..Default::default()
+        }
+    }
+
+    /// Add a sandwich of meat `id` worth `value` (no bribe)
+    fn add(scheduler: &mut OpportunitySchedulerManager, id: u64, value: u64, footprint: Footprint) {
+        let meat = Transaction {
+            hash: H256::from_low_u64_be(id),
+            from: address(id),
+            ..Default::default()
+        };
+        let recipe = SandwichRecipe::new(
+            vec![(TxEnv::default(), 0)],
+            vec![],
+            vec![meat],
+            vec![(TxEnv::default(), 0)],
+            U256::from(value),
+            BlockInfo::default(),
+            BribePayment::default(),
+            true,
+            U256::zero(),
+        );
+        scheduler.add_candidate(U64::one(), recipe, U256::zero(), footprint);
+    }
+
+    fn scheduled_meats(scheduled: Vec<(SandwichRecipe, U256)>) -> Vec<H256> {
+        let mut meats: Vec<H256> = scheduled
+            .iter()
+            .map(|(recipe, _)| recipe.get_meats_ref()[0].hash)
+            .collect();
+        meats.sort();
+        meats
+    }
+
+    #[test]
+    fn footprints_conflict_on_shared_pools_tokens_or_senders() {
+        let with = |pools: &[u64], tokens: &[u64], senders: &[u64]| Footprint {
+            pools: pools.iter().map(|&id| address(id)).collect(),
+            tokens: tokens.iter().map(|&id| address(id)).collect(),
+            senders: senders.iter().map(|&id| address(id)).collect(),
+            ..Default::default()
+        };
+        let footprint = with(&[1], &[2], &[3]);
+
+        assert!(footprint.conflicts_with(&with(&[1], &[], &[])));
+        assert!(footprint.conflicts_with(&with(&[], &[2], &[])));
+        assert!(footprint.conflicts_with(&with(&[], &[], &[3])));
+        assert!(!footprint.conflicts_with(&with(&[2, 3], &[1, 3], &[1, 2])));
+    }
+
+    /// the most valuable set that doesn't conflict wins over the single most valuable sandwich
+    #[test]
+    fn conflicting_candidates_are_not_scheduled_together() {
+        let mut scheduler = OpportunitySchedulerManager::new();
+        add(&mut scheduler, 1, 10, footprint(1, 0));
+        add(&mut scheduler, 2, 7, footprint(1, 0));
+        add(&mut scheduler, 3, 4, footprint(2, 0));
+
+        let scheduled = scheduler
+            .schedule(U64::one(), U256::zero(), U256::zero())
+            .unwrap();
+        assert_eq!(
+            scheduled_meats(scheduled),
+            vec![H256::from_low_u64_be(1), H256::from_low_u64_be(3)]
+        );
+    }
+
+    #[test]
+    fn weth_spend_stays_within_inventory() {
+        let mut scheduler = OpportunitySchedulerManager::new();
+        add(&mut scheduler, 1, 10, footprint(1, 60));
+        add(&mut scheduler, 2, 7, footprint(2, 60));
+        add(&mut scheduler, 3, 4, footprint(3, 40));
+
+        let scheduled = scheduler
+            .schedule(U64::one(), U256::from(100), U256::zero())
+            .unwrap();
+        assert_eq!(
+            scheduled_meats(scheduled),
+            vec![H256::from_low_u64_be(1), H256::from_low_u64_be(3)]
+        );
+    }
+
+    #[test]
+    fn coinbase_spend_stays_within_eth_balance() {
+        let mut scheduler = OpportunitySchedulerManager::new();
+        for id in 1..=2 {
+            let footprint = Footprint {
+                coinbase_spend: U256::from(60),
+                ..footprint(id, 0)
+            };
+            add(&mut scheduler, id, 10, footprint);
+        }
+
+        assert!(scheduler
+            .schedule(U64::one(), U256::zero(), U256::from(100))
+            .is_none());
+        assert!(scheduler
+            .schedule(U64::one(), U256::zero(), U256::from(120))
+            .is_some());
+    }
+
+    #[test]
+    fn unchanged_set_is_scheduled_once() {
+        let mut scheduler = OpportunitySchedulerManager::new();
+        add(&mut scheduler, 1, 10, footprint(1, 0));
+        add(&mut scheduler, 2, 7, footprint(2, 0));
+
+        assert!(scheduler
+            .schedule(U64::one(), U256::zero(), U256::zero())
+            .is_some());
+        assert!(scheduler
+            .schedule(U64::one(), U256::zero(), U256::zero())
+            .is_none());
+
+        add(&mut scheduler, 3, 4, footprint(3, 0));
+        assert_eq!(
+            scheduler
+                .schedule(U64::one(), U256::zero(), U256::zero())
+                .map(|scheduled| scheduled.len()),
+            Some(3)
+        );
+    }
+
+    /// candidates and the last scheduled set are dropped once the target block moves on
+    #[test]
+    fn new_target_block_resets_candidates() {
+        let mut scheduler = OpportunitySchedulerManager::new();
+        add(&mut scheduler, 1, 10, footprint(1, 0));
+        add(&mut scheduler, 2, 7, footprint(2, 0));
+        assert!(scheduler
+            .schedule(U64::one(), U256::zero(), U256::zero())
+            .is_some());
+
+        assert!(scheduler
+            .schedule(U64::from(2), U256::zero(), U256::zero())
+            .is_none());
+        assert!(scheduler.candidates.is_empty());
+        assert!(scheduler.last_scheduled.is_empty());
+    }
+}
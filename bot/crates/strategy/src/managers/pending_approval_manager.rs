@@ -0,0 +1,79 @@
+use ethers::types::{Address, Transaction, U256, U64};
+use std::collections::HashMap;
+
+/// How many blocks an approval is kept around before it is considered expired
+const MAX_APPROVAL_AGE: u64 = 25;
+
+/// Upper bound on tracked approvals so a flood of approvals can't grow the cache forever
+const MAX_PENDING_APPROVALS: usize = 10_000;
+
+/// approve(address spender, uint256 amount)
+const APPROVE_SELECTOR: [u8; 4] = [0x09, 0x5e, 0xa7, 0xb3];
+
+struct PendingApproval {
+    tx: Transaction,
+    /// Block that was latest when the tx was first seen
+    seen_at: U64,
+}
+
+/// Keeps track of pending erc20 approvals so that a victim's swap can be simulated and bundled on
+/// top of the approval it depends on (the swap reverts on its own until the approval is mined)
+pub struct PendingApprovalManager {
+    /// (sender, token) -> latest pending approval
+    approvals: HashMap<(Address, Address), PendingApproval>,
+}
+
+impl PendingApprovalManager {
+    pub fn new() -> Self {
+        Self {
+            approvals: HashMap::new(),
+        }
+    }
+
+    /// Track `tx` if it is an erc20 approval, returns true if it was one
+    pub fn insert_if_approval(&mut self, tx: &Transaction, latest_block: U64) -> bool {
+        let token = match tx.to {
+            Some(token) if tx.input.0.starts_with(&APPROVE_SELECTOR) => token,
+            _ => return false,
+        };
+
+        if self.approvals.len() < MAX_PENDING_APPROVALS
+            || self.approvals.contains_key(&(tx.from, token))
+        {
+            // a newer approval (or replacement) supersedes the previous one
+            self.approvals.insert(
+                (tx.from, token),
+                PendingApproval {
+                    tx: tx.clone(),
+                    seen_at: latest_block,
+                },
+            );
+        }
+
+        true
+    }
+
+    /// Pending approvals sent by `tx`'s sender ahead of it, in nonce order
+    pub fn get_approvals(&self, tx: &Transaction) -> Vec<Transaction> {
+        let mut approvals: Vec<Transaction> = self
+            .approvals
+            .iter()
+            .filter(|((sender, _), a)| *sender == tx.from && a.tx.nonce < tx.nonce)
+            .map(|(_, a)| a.tx.clone())
+            .collect();
+        approvals.sort_by_key(|a| a.nonce);
+        approvals
+    }
+
+    /// Evict approvals that were mined, replaced by a mined tx, or expired
+    /// `mined_nonces` holds the highest nonce mined by each sender in `latest_block`
+    pub fn on_new_block(&mut self, latest_block: U64, mined_nonces: &HashMap<Address, U256>) {
+        self.approvals.retain(|(sender, _), a| {
+            let mined = mined_nonces
+                .get(sender)
+                .map_or(false, |nonce| a.tx.nonce <= *nonce);
+            let expired = latest_block > a.seen_at + MAX_APPROVAL_AGE;
+            !mined && !expired
+        });
+    }
+}
@@ -0,0 +1,59 @@
+use ethers::types::{Address, Transaction, U256, U64};
+use std::collections::HashMap;
+
+/// How many blocks a pending tx is kept around before it is considered expired
+const MAX_PENDING_AGE: u64 = 25;
+
+/// Upper bound on cached txs so mempool floods can't grow the cache forever
+const MAX_PENDING_TXS: usize = 50_000;
+
+struct PendingTx {
+    tx: Transaction,
+    /// Block that was latest when the tx was first seen
+    seen_at: U64,
+}
+
+/// Keeps track of recently seen mempool txs by sender & nonce so that a victim whose nonce is
+/// ahead of its account can be bundled together with the pending txs that fill the gap
+pub struct PendingTxManager {
+    txs: HashMap<(Address, U256), PendingTx>,
+}
+
+impl PendingTxManager {
+    pub fn new() -> Self {
+        Self {
+            txs: HashMap::new(),
+        }
+    }
+
+    /// Cache a mempool tx (replaces any tx with the same sender & nonce)
+    pub fn insert(&mut self, tx: &Transaction, latest_block: U64) {
+        if self.txs.len() >= MAX_PENDING_TXS && !self.txs.contains_key(&(tx.from, tx.nonce)) {
+            return;
+        }
+
+        self.txs.insert(
+            (tx.from, tx.nonce),
+            PendingTx {
+                tx: tx.clone(),
+                seen_at: latest_block,
+            },
+        );
+    }
+
+    pub fn get(&self, sender: Address, nonce: U256) -> Option<Transaction> {
+        self.txs.get(&(sender, nonce)).map(|p| p.tx.clone())
+    }
+
+    /// Evict txs that were mined, replaced by a mined tx, or expired
+    /// `mined_nonces` holds the highest nonce mined by each sender in `latest_block`
+    pub fn on_new_block(&mut self, latest_block: U64, mined_nonces: &HashMap<Address, U256>) {
+        self.txs.retain(|(sender, nonce), p| {
+            let mined = mined_nonces
+                .get(sender)
+                .map_or(false, |mined_nonce| nonce <= mined_nonce);
+            let expired = latest_block > p.seen_at + MAX_PENDING_AGE;
+            !mined && !expired
+        });
+    }
+}
@@ -0,0 +1,99 @@
+use ethers::types::{Address, Transaction, H256, U256, U64};
+use std::collections::HashMap;
+
+use crate::{tx_utils::meat::meat_max_fee, types::BlockInfo};
+
+/// How many blocks a victim is kept around before it is considered expired
+const MAX_PENDING_AGE: u64 = 25;
+
+/// Upper bound on cached victims so a flood of underpriced txs can't grow the cache forever
+const MAX_PENDING_VICTIMS: usize = 10_000;
+
+struct PendingVictim {
+    tx: Transaction,
+    /// Block that was latest when the tx was first seen
+    seen_at: U64,
+}
+
+/// Keeps track of txs that could not be included in the next block because their max fee
+/// was below the next block's base fee, so that they can be revisited once base fee drops
+pub struct PendingVictimManager {
+    victims: HashMap<H256, PendingVictim>,
+    /// (sender, nonce) -> hash, used to detect replacements
+    by_sender_nonce: HashMap<(Address, U256), H256>,
+}
+
+impl PendingVictimManager {
+    pub fn new() -> Self {
+        Self {
+            victims: HashMap::new(),
+            by_sender_nonce: HashMap::new(),
+        }
+    }
+
+    /// Cache a victim that can't be included in the next block
+    pub fn insert(&mut self, tx: Transaction, latest_block: U64) {
+        if self.victims.contains_key(&tx.hash) || self.victims.len() >= MAX_PENDING_VICTIMS {
+            return;
+        }
+
+        self.remove_replaced(&tx);
+        self.by_sender_nonce.insert((tx.from, tx.nonce), tx.hash);
+        self.victims.insert(
+            tx.hash,
+            PendingVictim {
+                tx,
+                seen_at: latest_block,
+            },
+        );
+    }
+
+    /// Evict a cached victim that shares sender & nonce with `tx` but has a different hash
+    pub fn remove_replaced(&mut self, tx: &Transaction) {
+        if let Some(hash) = self.by_sender_nonce.get(&(tx.from, tx.nonce)).copied() {
+            if hash != tx.hash {
+                self.remove(&hash);
+            }
+        }
+    }
+
+    fn remove(&mut self, hash: &H256) {
+        if let Some(victim) = self.victims.remove(hash) {
+            self.by_sender_nonce
+                .remove(&(victim.tx.from, victim.tx.nonce));
+        }
+    }
+
+    /// Evict victims that were mined, replaced by a mined tx, or expired
+    /// `mined_nonces` holds the highest nonce mined by each sender in `latest_block`
+    /// Returns the victims that can now be included in `next_block` (they stay cached until evicted)
+    pub fn on_new_block(
+        &mut self,
+        latest_block: &BlockInfo,
+        next_block: &BlockInfo,
+        mined_nonces: &HashMap<Address, U256>,
+    ) -> Vec<Transaction> {
+        let stale: Vec<H256> = self
+            .victims
+            .iter()
+            .filter(|(_, v)| {
+                let mined = mined_nonces
+                    .get(&v.tx.from)
+                    .map_or(false, |nonce| v.tx.nonce <= *nonce);
+                let expired = latest_block.number > v.seen_at + MAX_PENDING_AGE;
+                mined || expired
+            })
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        for hash in stale {
+            self.remove(&hash);
+        }
+
+        self.victims
+            .values()
+            .filter(|v| meat_max_fee(&v.tx) >= next_block.base_fee_per_gas)
+            .map(|v| v.tx.clone())
+            .collect()
+    }
+}
@@ -2,26 +2,44 @@ use anyhow::{anyhow, Result};
 use cfmms::{
     checkpoint::sync_pools_from_checkpoint,
     dex::{Dex, DexVariant},
-    pool::Pool,
+    pool::{Pool, UniswapV2Pool},
     sync::sync_pairs,
 };
 use colored::Colorize;
 use dashmap::DashMap;
 use ethers::{
-    abi,
     providers::Middleware,
     types::{Address, BlockNumber, Diff, TraceType, Transaction, H160, H256, U256},
 };
 use log::info;
-use std::{path::Path, str::FromStr, sync::Arc};
+use std::{collections::HashMap, path::Path, str::FromStr, sync::Arc};
 
-use crate::{constants::WETH_ADDRESS, startup_info_log};
+use crate::{
+    base_token::{BaseToken, BaseTokenConfig},
+    constants::WETH_ADDRESS,
+    helpers::uniswap_v2_weth_pair,
+    inventory::InventoryConfig,
+    startup_info_log,
+    types::SandwichLeg,
+};
 
 pub(crate) struct PoolManager<M> {
     /// Provider
     provider: Arc<M>,
     /// Sandwichable pools
     pools: DashMap<Address, Pool>,
+    /// Canonical uniswap v2 weth pair of each token, used to value non weth base tokens in eth and
+    /// to rebalance inventory
+    weth_pairs: DashMap<Address, UniswapV2Pool>,
+    /// Weth pairs of each token on every monitored v2 fork (deepest first at sync), flash swaps
+    /// borrow tokens for `token->weth` sandwiches from them
+    flash_pairs: DashMap<Address, Vec<UniswapV2Pool>>,
+    /// V2 pools whose output was checked against their configured fee, true if it matched
+    v2_fee_checks: DashMap<Address, bool>,
+    /// Tokens that sandwiches can start and end with
+    base_tokens: BaseTokenConfig,
+    /// Longtail tokens held on the sandwich contract
+    inventory: InventoryConfig,
     /// Which dexes to monitor
     dexes: Vec<Dex>,
 }
@@ -33,153 +51,310 @@ impl<M: Middleware + 'static> PoolManager<M> {
 
         let checkpoint_exists = Path::new(checkpoint_path).exists();
 
-        let pools = if checkpoint_exists {
-            let (_, pools) =
+        let checkpoint = if checkpoint_exists {
+            let (dexes, pools) =
                 sync_pools_from_checkpoint(checkpoint_path, 100000, self.provider.clone()).await?;
-            pools
+            // pools keep the fee of the dex they were synced with, a checkpoint written with other
+            // fees than `dexes` would price their pairs wrong
+            match self.has_dex_fees_of(&dexes) {
+                true => Some(pools),
+                false => {
+                    startup_info_log!("checkpoint dex fees are outdated, resyncing pools");
+                    None
+                }
+            }
         } else {
-            sync_pairs(
-                self.dexes.clone(),
-                self.provider.clone(),
-                Some(checkpoint_path),
-            )
-            .await?
+            None
+        };
+
+        let pools = match checkpoint {
+            Some(pools) => pools,
+            None => {
+                sync_pairs(
+                    self.dexes.clone(),
+                    self.provider.clone(),
+                    Some(checkpoint_path),
+                )
+                .await?
+            }
         };
 
         for pool in pools {
+            if let Pool::UniswapV2(p) = pool {
+                if let Some(token) = weth_pair_token(&p) {
+                    if uniswap_v2_weth_pair(token) == p.address {
+                        self.weth_pairs.insert(token, p);
+                    }
+                    self.flash_pairs.entry(token).or_default().push(p);
+                }
+            }
             self.pools.insert(pool.address(), pool);
         }
+        for mut pairs in self.flash_pairs.iter_mut() {
+            pairs.sort_by_key(|p| std::cmp::Reverse(weth_reserve(p)));
+        }
 
         startup_info_log!("pools synced: {}", self.pools.len());
+        startup_info_log!("weth pairs found: {}", self.weth_pairs.len());
+        startup_info_log!("tokens with flash pairs: {}", self.flash_pairs.len());
 
         Ok(())
     }
 
-    /// Return a tx's touched pools
-    // enhancement: record stable coin pairs to sandwich as well here
+    /// True if every v2 dex in `dexes` (e.g. loaded from a checkpoint) charges the fee configured
+    /// for its factory
+    fn has_dex_fees_of(&self, dexes: &[Dex]) -> bool {
+        let fees: HashMap<H160, _> = self
+            .dexes
+            .iter()
+            .filter_map(|dex| match dex {
+                Dex::UniswapV2(dex) => Some((dex.factory_address, dex.fee)),
+                _ => None,
+            })
+            .collect();
+
+        dexes.iter().all(|dex| match dex {
+            Dex::UniswapV2(dex) => fees.get(&dex.factory_address) == Some(&dex.fee),
+            _ => true,
+        })
+    }
+
+    /// V2 pools checked to charge their configured fee (filled in as sandwiches are simulated)
+    pub fn get_v2_fee_checks(&self) -> &DashMap<Address, bool> {
+        &self.v2_fee_checks
+    }
+
+    /// Canonical uniswap v2 weth pair of `token`
+    pub fn get_weth_pair(&self, token: Address) -> Option<UniswapV2Pool> {
+        self.weth_pairs.get(&token).map(|p| *p.value())
+    }
+
+    /// Return a sandwich leg for each of a tx's touched pools
+    /// `prerequisites` are the victim's pending txs that it depends on (e.g. an approval), the
+    /// victim is traced on top of them
     pub async fn get_touched_sandwichable_pools(
         &self,
         victim_tx: &Transaction,
+        prerequisites: &[Transaction],
         latest_block: BlockNumber,
         provider: Arc<M>,
-    ) -> Result<Vec<Pool>> {
+    ) -> Result<Vec<SandwichLeg>> {
         // get victim tx state diffs
-        let state_diffs = provider
-            .trace_call(victim_tx, vec![TraceType::StateDiff], Some(latest_block))
-            .await?
+        let trace = if prerequisites.is_empty() {
+            provider
+                .trace_call(victim_tx, vec![TraceType::StateDiff], Some(latest_block))
+                .await?
+        } else {
+            provider
+                .trace_call_many(
+                    prerequisites
+                        .iter()
+                        .chain(std::iter::once(victim_tx))
+                        .map(|tx| (tx, vec![TraceType::StateDiff]))
+                        .collect(),
+                    Some(latest_block),
+                )
+                .await?
+                .pop()
+                .ok_or(anyhow!("not sandwichable, no traces produced"))?
+        };
+        let state_diffs = trace
             .state_diff
             .ok_or(anyhow!("not sandwichable, no state diffs produced"))?
             .0;
 
-        // capture all addresses that have a state change and are also a `WETH` pool
-        let touched_pools: Vec<Pool> = state_diffs
+        // capture all addresses that have a state change and are also paired with a base token
+        // (pools pairing two base tokens start with whichever base token is configured first)
+        let touched_pools: Vec<(Pool, BaseToken)> = state_diffs
             .keys()
             .filter_map(|e| self.pools.get(e).map(|p| (*p.value()).clone()))
-            .filter(|e| match e {
-                Pool::UniswapV2(p) => vec![p.token_a, p.token_b].contains(&WETH_ADDRESS),
-                Pool::UniswapV3(p) => vec![p.token_a, p.token_b].contains(&WETH_ADDRESS),
+            .filter_map(|e| {
+                let (token_a, token_b) = match e {
+                    Pool::UniswapV2(p) => (p.token_a, p.token_b),
+                    Pool::UniswapV3(p) => (p.token_a, p.token_b),
+                };
+                self.base_tokens
+                    .get_tokens()
+                    .iter()
+                    .find(|t| t.address == token_a || t.address == token_b)
+                    .map(|t| (e, *t))
             })
             .collect();
 
-        // nothing to sandwich
-        if touched_pools.is_empty() {
-            return Ok(vec![]);
-        }
-
-        // find trade direction
-        let weth_state_diff = &state_diffs
-            .get(&WETH_ADDRESS)
-            .ok_or(anyhow!("Missing WETH state diffs"))?
-            .storage;
-
-        let mut sandwichable_pools = vec![];
+        let mut sandwichable_legs = vec![];
 
-        for pool in touched_pools {
-            // find pool mapping location on WETH contract
-            let storage_key = H256::from(ethers::utils::keccak256(abi::encode(&[
-                abi::Token::Address(pool.address()),
-                abi::Token::Uint(U256::from(3)), // WETH balanceOf mapping is at index 3
-            ])));
+        for (pool, base_token) in touched_pools {
+            // find trade direction from the pool's balance on the base token contract
+            let base_token_state_diff = match state_diffs.get(&base_token.address) {
+                Some(d) => &d.storage,
+                None => continue,
+            };
+            let storage_key = H256::from(base_token.balance_storage_key(pool.address()));
 
-            // in reality we also want to check stable coin pools
-            if let Some(Diff::Changed(c)) = weth_state_diff.get(&storage_key) {
+            if let Some(Diff::Changed(c)) = base_token_state_diff.get(&storage_key) {
                 let from = U256::from(c.from.to_fixed_bytes());
                 let to = U256::from(c.to.to_fixed_bytes());
 
-                // right now bot can only sandwich `weth->token` trades
-                // enhancement: add support for `token->weth` trades (using longtail or flashswaps sandwichs)
+                let (token_a, token_b) = match pool {
+                    Pool::UniswapV2(p) => (p.token_a, p.token_b),
+                    Pool::UniswapV3(p) => (p.token_a, p.token_b),
+                };
+
+                // token that we use as frontrun output and backrun input
+                let intermediary_token = if token_a == base_token.address {
+                    token_b
+                } else {
+                    token_a
+                };
+
+                // revenue of non weth sandwiches is valued through the base token's weth pair
+                let quote_pool = if base_token.is_weth() {
+                    None
+                } else {
+                    match self.weth_pairs.get(&base_token.address) {
+                        Some(p) => Some(*p.value()),
+                        None => continue,
+                    }
+                };
+
+                let mut leg = SandwichLeg {
+                    start_end_token: base_token,
+                    intermediary_token,
+                    target_pool: pool,
+                    flash_pool: None,
+                    quote_pool,
+                    inventory_token: None,
+                };
+
                 if to > from {
-                    sandwichable_pools.push(pool);
+                    // `base->token` trade
+                    sandwichable_legs.push(leg);
+                } else if to < from && base_token.is_weth() {
+                    // `token->weth` trade, sell the token from inventory when we hold it
+                    // enhancement: sandwich v3 pools from inventory
+                    if let (Some(inventory_token), Pool::UniswapV2(_)) =
+                        (self.inventory.get(intermediary_token), pool)
+                    {
+                        leg.inventory_token = Some(inventory_token.token);
+                        sandwichable_legs.push(leg);
+                        continue;
+                    }
+
+                    // otherwise borrow the token from its deepest other weth pair through a
+                    // flash swap
+                    // enhancement: sandwich v3 pools and tokens with a single v2 weth pair
+                    let flash_pool = self.flash_pairs.get(&intermediary_token).and_then(|pairs| {
+                        pairs.iter().find(|p| p.address != pool.address()).copied()
+                    });
+
+                    if let (Some(flash_pool), Pool::UniswapV2(_)) = (flash_pool, pool) {
+                        leg.flash_pool = Some(flash_pool);
+                        sandwichable_legs.push(leg);
+                    }
                 }
             }
         }
 
-        Ok(sandwichable_pools)
+        Ok(sandwichable_legs)
     }
 
-    pub fn new(provider: Arc<M>) -> Self {
+    pub fn new(provider: Arc<M>, base_tokens: BaseTokenConfig, inventory: InventoryConfig) -> Self {
+        // (factory, variant, creation block, v2 pair fee in units of 1e-5)
+        // v2 amount math uses each pool's fee, pairs charging another fee aren't sandwiched
         let dexes_data = [
             (
                 // Uniswap v2
                 "0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f",
                 DexVariant::UniswapV2,
                 10000835u64,
+                300,
             ),
             (
                 // Sushiswap
                 "0xC0AEe478e3658e2610c5F7A4A2E1777cE9e4f2Ac",
                 DexVariant::UniswapV2,
                 10794229u64,
+                300,
             ),
             (
                 // Crypto.com swap
                 "0x9DEB29c9a4c7A88a3C0257393b7f3335338D9A9D",
                 DexVariant::UniswapV2,
                 10828414u64,
+                300,
             ),
             (
                 // Convergence swap
                 "0x4eef5746ED22A2fD368629C1852365bf5dcb79f1",
                 DexVariant::UniswapV2,
                 12385067u64,
+                300,
             ),
             (
                 // Pancakeswap
                 "0x1097053Fd2ea711dad45caCcc45EfF7548fCB362",
                 DexVariant::UniswapV2,
                 15614590u64,
+                250,
             ),
             (
                 // ShibaSwap
                 "0x115934131916C8b277DD010Ee02de363c09d037c",
                 DexVariant::UniswapV2,
                 12771526u64,
+                300,
             ),
             (
                 // Saitaswap
                 "0x35113a300ca0D7621374890ABFEAC30E88f214b1",
                 DexVariant::UniswapV2,
                 15210780u64,
+                300,
             ),
             (
                 // Uniswap v3
                 "0x1F98431c8aD98523631AE4a59f267346ea31F984",
                 DexVariant::UniswapV3,
                 12369621u64,
+                300,
             ),
         ];
 
         let dexes = dexes_data
             .into_iter()
-            .map(|(address, variant, number)| {
-                Dex::new(H160::from_str(address).unwrap(), variant, number, Some(300))
+            .map(|(address, variant, number, fee)| {
+                Dex::new(H160::from_str(address).unwrap(), variant, number, Some(fee))
             })
             .collect();
 
         Self {
             pools: DashMap::new(),
+            weth_pairs: DashMap::new(),
+            flash_pairs: DashMap::new(),
+            v2_fee_checks: DashMap::new(),
+            base_tokens,
+            inventory,
             provider,
             dexes,
         }
     }
 }
+
+/// Returns the non weth token of `pool` if it pairs a token with weth
+fn weth_pair_token(pool: &UniswapV2Pool) -> Option<Address> {
+    if pool.token_a == *WETH_ADDRESS {
+        Some(pool.token_b)
+    } else if pool.token_b == *WETH_ADDRESS {
+        Some(pool.token_a)
+    } else {
+        None
+    }
+}
+
+/// Weth reserve of a weth pair when it was synced
+fn weth_reserve(pool: &UniswapV2Pool) -> u128 {
+    match pool.token_a == *WETH_ADDRESS {
+        true => pool.reserve_0,
+        false => pool.reserve_1,
+    }
+}
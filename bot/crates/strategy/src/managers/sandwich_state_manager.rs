@@ -3,23 +3,55 @@ use colored::Colorize;
 use ethers::{
     providers::Middleware,
     signers::{LocalWallet, Signer},
-    types::{Address, BlockNumber, Filter, U256, U64},
+    types::{Address, BlockNumber, Filter, H256, I256, U256, U64},
+};
+use log::{error, info};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
 };
-use log::info;
-use std::sync::Arc;
 
 use crate::{
-    abi::Erc20,
+    abi::{Erc20, UniswapV2Pair},
+    base_token::BaseTokenConfig,
     constants::{ERC20_TRANSFER_EVENT_SIG, WETH_ADDRESS},
-    startup_info_log,
+    helpers::uniswap_v2_weth_pair,
+    inventory::InventoryConfig,
+    log_error, startup_info_log,
 };
 
+/// How many blocks of balance changes are kept around to unwind reorgs
+const REORG_DEPTH: usize = 64;
+
+/// Balance changes applied when syncing up to a block
+struct BalanceJournalEntry {
+    number: U64,
+    hash: H256,
+    /// Token balances before this entry was applied (`None` = token wasn't tracked)
+    previous_balances: HashMap<Address, Option<U256>>,
+    previous_eth_balance: U256,
+    previous_cost_basis: HashMap<Address, U256>,
+    previous_unusable_tokens: HashSet<Address>,
+}
+
 pub struct SandwichStateManager {
     sandwich_contract: Address,
     sandwich_inception_block: U64,
     searcher_signer: LocalWallet,
-    weth_inventory: U256,
-    token_dust: Vec<Address>,
+    /// Tokens that sandwiches start and end with, their balances are the bot's inventory
+    base_tokens: BaseTokenConfig,
+    eth_balance: U256,
+    /// Balances of every token the sandwich contract holds or has held (base tokens included)
+    token_balances: HashMap<Address, U256>,
+    /// Longtail tokens held for inventory sandwiches
+    inventory: InventoryConfig,
+    /// Weth paid for the inventory currently held (average cost, increases valued at spot price)
+    cost_basis: HashMap<Address, U256>,
+    /// Tokens whose latest balance refresh failed (tracked balance may be stale, e.g. pre-reorg),
+    /// retried every block until a refresh succeeds
+    unusable_tokens: HashSet<Address>,
+    /// Most recent entry is the block the balances are synced to
+    journal: VecDeque<BalanceJournalEntry>,
 }
 
 impl SandwichStateManager {
@@ -27,65 +59,353 @@ impl SandwichStateManager {
         sandwich_contract: Address,
         searcher_signer: LocalWallet,
         sandwich_inception_block: U64,
+        base_tokens: BaseTokenConfig,
+        inventory: InventoryConfig,
     ) -> Self {
         Self {
             sandwich_contract,
             sandwich_inception_block,
             searcher_signer,
-            weth_inventory: Default::default(),
-            token_dust: Default::default(),
+            base_tokens,
+            eth_balance: Default::default(),
+            token_balances: Default::default(),
+            inventory,
+            cost_basis: Default::default(),
+            unusable_tokens: Default::default(),
+            journal: Default::default(),
         }
     }
 
     pub async fn setup<M: Middleware + 'static>(&mut self, provider: Arc<M>) -> Result<()> {
-        // find weth inventory
-        let weth = Erc20::new(*WETH_ADDRESS, provider.clone());
-        let weth_balance = weth.balance_of(self.sandwich_contract).call().await?;
-        startup_info_log!("weth inventory   : {}", weth_balance);
-        self.weth_inventory = weth_balance;
-
-        // find weth dust
-        let step = 10000;
-
         let latest_block = provider
             .get_block(BlockNumber::Latest)
             .await
             .map_err(|_| anyhow!("Failed to get latest block"))?
-            .ok_or(anyhow!("Failed to get latest block"))?
+            .ok_or(anyhow!("Failed to get latest block"))?;
+        let latest_number = latest_block
             .number
-            .ok_or(anyhow!("Field block number does not exist on latest block"))?
-            .as_u64();
-
-        let mut token_dust = vec![];
-
-        let start_block = self.sandwich_inception_block.as_u64();
-
-        // for each block within the range, get all transfer events asynchronously
-        for from_block in (start_block..=latest_block).step_by(step) {
-            let to_block = from_block + step as u64;
-
-            // check for all incoming and outgoing txs within step range
-            let transfer_logs = provider
-                .get_logs(
-                    &Filter::new()
-                        .topic0(*ERC20_TRANSFER_EVENT_SIG)
-                        .topic1(self.sandwich_contract)
-                        .from_block(BlockNumber::Number(U64([from_block])))
-                        .to_block(BlockNumber::Number(U64([to_block]))),
-                )
-                .await?;
-
-            for log in transfer_logs {
-                token_dust.push(log.address);
+            .ok_or(anyhow!("Field block number does not exist on latest block"))?;
+        let latest_hash = latest_block
+            .hash
+            .ok_or(anyhow!("Field block hash does not exist on latest block"))?;
+
+        // find all tokens that ever moved in or out of the sandwich contract
+        let mut tokens = self
+            .get_transferred_tokens(
+                self.sandwich_inception_block,
+                latest_number,
+                provider.clone(),
+            )
+            .await?;
+        tokens.extend(self.base_tokens.get_tokens().iter().map(|t| t.address));
+        tokens.extend(self.inventory.get_tokens().iter().map(|t| t.token.address));
+
+        self.token_balances.clear();
+        self.cost_basis.clear();
+        self.unusable_tokens.clear();
+        self.journal.clear();
+        let previous_eth_balance = self.eth_balance;
+        let previous_cost_basis = self.cost_basis.clone();
+        let previous_unusable_tokens = self.unusable_tokens.clone();
+        let previous_balances = self
+            .refresh_balances(tokens, latest_number, provider.clone())
+            .await?;
+        self.update_cost_basis(&previous_balances, latest_number, provider.clone())
+            .await?;
+        self.push_journal_entry(BalanceJournalEntry {
+            number: latest_number,
+            hash: latest_hash,
+            previous_balances,
+            previous_eth_balance,
+            previous_cost_basis,
+            previous_unusable_tokens,
+        });
+
+        for base_token in self.base_tokens.get_tokens() {
+            startup_info_log!(
+                "inventory        : {} of {:?}",
+                self.get_token_balance(base_token.address),
+                base_token.address
+            );
+        }
+        for inventory_token in self.inventory.get_tokens() {
+            let token = inventory_token.token.address;
+            startup_info_log!(
+                "longtail         : {} of {:?} (cost basis {} wei)",
+                self.get_token_balance(token),
+                token,
+                self.get_cost_basis(token)
+            );
+        }
+        startup_info_log!("eth balance      : {}", self.eth_balance);
+        startup_info_log!(
+            "token dust found : {}",
+            self.token_balances
+                .iter()
+                .filter(|(token, balance)| {
+                    self.base_tokens.get(**token).is_none()
+                        && self.inventory.get(**token).is_none()
+                        && !balance.is_zero()
+                })
+                .count()
+        );
+
+        Ok(())
+    }
+
+    /// Sync balances up to `block_number` using the sandwich contract's transfer logs
+    /// Balance changes from blocks that were reorged out are unwound first
+    pub async fn update<M: Middleware + 'static>(
+        &mut self,
+        block_number: U64,
+        provider: Arc<M>,
+    ) -> Result<()> {
+        let block = provider
+            .get_block(block_number)
+            .await
+            .map_err(|e| anyhow!("Failed to get block {}: {:?}", block_number, e))?
+            .ok_or(anyhow!("Failed to get block {}", block_number))?;
+        let hash = block
+            .hash
+            .ok_or(anyhow!("Field block hash does not exist on block"))?;
+
+        // unwind entries that are no longer part of the canonical chain
+        while let Some(entry) = self.journal.back() {
+            if entry.number < block_number {
+                let canonical_hash = if entry.number + 1 == block_number {
+                    Some(block.parent_hash)
+                } else {
+                    provider
+                        .get_block(entry.number)
+                        .await
+                        .map_err(|e| anyhow!("Failed to get block {}: {:?}", entry.number, e))?
+                        .and_then(|b| b.hash)
+                };
+
+                if canonical_hash == Some(entry.hash) {
+                    break;
+                }
             }
+
+            let entry = self.journal.pop_back().unwrap();
+            self.unwind(entry);
         }
 
-        startup_info_log!("token dust found : {}", token_dust.len());
-        self.token_dust = token_dust;
+        let previous_eth_balance = self.eth_balance;
+        let previous_cost_basis = self.cost_basis.clone();
+        let previous_unusable_tokens = self.unusable_tokens.clone();
+        let previous_balances = match self.journal.back() {
+            Some(entry) => {
+                let from_block = entry.number + 1;
+                let mut tokens = self
+                    .get_transferred_tokens(from_block, block_number, provider.clone())
+                    .await?;
+                // base tokens are always refreshed, weth deposits & withdrawals don't emit
+                // transfers
+                tokens.extend(self.base_tokens.get_tokens().iter().map(|t| t.address));
+                tokens.extend(self.inventory.get_tokens().iter().map(|t| t.token.address));
+                tokens.extend(self.unusable_tokens.iter().copied());
+                self.refresh_balances(tokens, block_number, provider.clone())
+                    .await?
+            }
+            None => {
+                // reorg is deeper than what we keep track of, refresh everything
+                log_error!(
+                    "reorg deeper than {} blocks, refreshing balances",
+                    REORG_DEPTH
+                );
+                let mut tokens = self
+                    .get_transferred_tokens(
+                        block_number.saturating_sub(U64::from(REORG_DEPTH)),
+                        block_number,
+                        provider.clone(),
+                    )
+                    .await?;
+                tokens.extend(self.token_balances.keys().copied());
+                tokens.extend(self.unusable_tokens.iter().copied());
+                self.refresh_balances(tokens, block_number, provider.clone())
+                    .await?
+            }
+        };
+        self.update_cost_basis(&previous_balances, block_number, provider.clone())
+            .await?;
+
+        self.push_journal_entry(BalanceJournalEntry {
+            number: block_number,
+            hash,
+            previous_balances,
+            previous_eth_balance,
+            previous_cost_basis,
+            previous_unusable_tokens,
+        });
 
         Ok(())
     }
 
+    /// Record the changes applied when syncing up to a block, forgetting blocks past reorg depth
+    fn push_journal_entry(&mut self, entry: BalanceJournalEntry) {
+        self.journal.push_back(entry);
+        if self.journal.len() > REORG_DEPTH {
+            self.journal.pop_front();
+        }
+    }
+
+    /// Restore balances to what they were before `entry` was applied
+    fn unwind(&mut self, entry: BalanceJournalEntry) {
+        for (token, balance) in entry.previous_balances {
+            match balance {
+                Some(balance) => self.token_balances.insert(token, balance),
+                None => self.token_balances.remove(&token),
+            };
+        }
+        self.eth_balance = entry.previous_eth_balance;
+        self.cost_basis = entry.previous_cost_basis;
+        self.unusable_tokens = entry.previous_unusable_tokens;
+    }
+
+    /// Move cost basis of inventory tokens whose balance changed
+    /// Increases are valued at the spot price of the token's uniswap v2 weth pair at `block`,
+    /// decreases remove cost basis proportionally (average cost)
+    async fn update_cost_basis<M: Middleware + 'static>(
+        &mut self,
+        previous_balances: &HashMap<Address, Option<U256>>,
+        block: U64,
+        provider: Arc<M>,
+    ) -> Result<()> {
+        for (token, previous) in previous_balances {
+            if self.inventory.get(*token).is_none() {
+                continue;
+            }
+
+            let previous = previous.unwrap_or_default();
+            let balance = self.get_token_balance(*token);
+            let cost_basis = self.get_cost_basis(*token);
+
+            let cost_basis = if balance > previous {
+                let pair = UniswapV2Pair::new(uniswap_v2_weth_pair(*token), provider.clone());
+                let (reserve0, reserve1, _) =
+                    pair.get_reserves().block(block).call().await.map_err(|e| {
+                        anyhow!("Failed to get weth pair reserves of {:?}: {:?}", token, e)
+                    })?;
+                let (reserve_token, reserve_weth) = if *token < *WETH_ADDRESS {
+                    (U256::from(reserve0), U256::from(reserve1))
+                } else {
+                    (U256::from(reserve1), U256::from(reserve0))
+                };
+
+                cost_basis
+                    + ((balance - previous) * reserve_weth)
+                        .checked_div(reserve_token)
+                        .unwrap_or_default()
+            } else if previous.is_zero() {
+                U256::zero()
+            } else {
+                cost_basis * balance / previous
+            };
+
+            self.cost_basis.insert(*token, cost_basis);
+        }
+
+        Ok(())
+    }
+
+    /// Find all tokens transferred in or out of the sandwich contract within a block range
+    async fn get_transferred_tokens<M: Middleware + 'static>(
+        &self,
+        from_block: U64,
+        to_block: U64,
+        provider: Arc<M>,
+    ) -> Result<HashSet<Address>> {
+        let step = 10000;
+        let mut tokens = HashSet::new();
+
+        for start in (from_block.as_u64()..=to_block.as_u64()).step_by(step) {
+            let end = (start + step as u64 - 1).min(to_block.as_u64());
+            let filter = Filter::new()
+                .topic0(*ERC20_TRANSFER_EVENT_SIG)
+                .from_block(BlockNumber::Number(U64([start])))
+                .to_block(BlockNumber::Number(U64([end])));
+
+            // check for all outgoing (topic1) and incoming (topic2) transfers within step range
+            for filter in [
+                filter.clone().topic1(self.sandwich_contract),
+                filter.topic2(self.sandwich_contract),
+            ] {
+                let transfer_logs = provider
+                    .get_logs(&filter)
+                    .await
+                    .map_err(|e| anyhow!("Failed to get transfer logs: {:?}", e))?;
+                tokens.extend(transfer_logs.into_iter().map(|log| log.address));
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Read balances of `tokens` (and eth) at `block`, returns the balances they replaced
+    async fn refresh_balances<M: Middleware + 'static>(
+        &mut self,
+        tokens: HashSet<Address>,
+        block: U64,
+        provider: Arc<M>,
+    ) -> Result<HashMap<Address, Option<U256>>> {
+        let mut balances = vec![];
+
+        for token in tokens {
+            // balance is read instead of summing transfer amounts to account for taxed tokens
+            let balance = match Erc20::new(token, provider.clone())
+                .balance_of(self.sandwich_contract)
+                .block(block)
+                .call()
+                .await
+            {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    log_error!(
+                        "Failed to get balance of token {:?}, unusable until refreshed: {:?}",
+                        token,
+                        e
+                    );
+                    None
+                }
+            };
+            balances.push((token, balance));
+        }
+
+        // eth balance is used to pay coinbase bribes
+        let eth_balance = provider
+            .get_balance(self.sandwich_contract, Some(block.into()))
+            .await
+            .map_err(|_| anyhow!("Failed to get sandwich contract eth balance"))?;
+
+        Ok(self.apply_balances(balances, eth_balance))
+    }
+
+    /// Store refreshed balances (`None` if the refresh failed, the token stays unusable until one
+    /// succeeds), returns the balances they replaced
+    fn apply_balances(
+        &mut self,
+        balances: Vec<(Address, Option<U256>)>,
+        eth_balance: U256,
+    ) -> HashMap<Address, Option<U256>> {
+        let mut previous_balances = HashMap::new();
+
+        for (token, balance) in balances {
+            match balance {
+                Some(balance) => {
+                    self.unusable_tokens.remove(&token);
+                    previous_balances.insert(token, self.token_balances.insert(token, balance));
+                }
+                None => {
+                    self.unusable_tokens.insert(token);
+                }
+            }
+        }
+        self.eth_balance = eth_balance;
+
+        previous_balances
+    }
+
     pub fn get_sandwich_address(&self) -> Address {
         self.sandwich_contract
     }
@@ -98,7 +418,245 @@ impl SandwichStateManager {
         &self.searcher_signer
     }
 
-    pub fn get_weth_inventory(&self) -> U256 {
-        self.weth_inventory
+    /// Sandwich contract's inventory of a base token (what frontruns can spend)
+    pub fn get_inventory(&self, base_token: Address) -> U256 {
+        self.get_token_balance(base_token)
+    }
+
+    pub fn get_eth_balance(&self) -> U256 {
+        self.eth_balance
+    }
+
+    /// Sandwich contract's balance of `token` at the latest synced block
+    pub fn get_token_balance(&self, token: Address) -> U256 {
+        self.token_balances.get(&token).copied().unwrap_or_default()
+    }
+
+    /// Weth paid for the sandwich contract's current inventory of `token`
+    pub fn get_cost_basis(&self, token: Address) -> U256 {
+        self.cost_basis.get(&token).copied().unwrap_or_default()
+    }
+
+    /// False while the balance of `token` can't be trusted (its latest refresh failed)
+    pub fn is_usable(&self, token: Address) -> bool {
+        !self.unusable_tokens.contains(&token)
+    }
+
+    /// True if the sandwich contract holds dust of `token`
+    pub fn has_dust(&self, token: Address) -> bool {
+        !self.get_token_balance(token).is_zero()
+    }
+
+    /// Net value that txs not sent by the searcher moved into the searcher & sandwich contract in
+    /// `block` (deposits count positive, withdrawals negative), so that it can be left out of pnl
+    /// Returns `None` if one of them moved something other than eth or weth
+    pub async fn get_external_flow<M: Middleware + 'static>(
+        &self,
+        block: U64,
+        provider: Arc<M>,
+    ) -> Result<Option<I256>> {
+        let searcher = self.get_searcher_address();
+        let owners = [self.sandwich_contract, searcher];
+
+        let txs = provider
+            .get_block_with_txs(block)
+            .await
+            .map_err(|e| anyhow!("Failed to get block {}: {:?}", block, e))?
+            .ok_or(anyhow!("Failed to get block {}", block))?
+            .transactions;
+        let foreign_txs: HashSet<H256> = txs
+            .iter()
+            .filter(|tx| tx.from != searcher)
+            .map(|tx| tx.hash)
+            .collect();
+
+        // plain eth transfers
+        let mut flow = txs
+            .iter()
+            .filter(|tx| foreign_txs.contains(&tx.hash))
+            .filter(|tx| tx.to.map_or(false, |to| owners.contains(&to)))
+            .fold(I256::zero(), |acc, tx| acc + I256::from_raw(tx.value));
+
+        // token transfers, a transfer between both owners shows up in two queries
+        let filter = Filter::new()
+            .topic0(*ERC20_TRANSFER_EVENT_SIG)
+            .from_block(block)
+            .to_block(block);
+        let mut transfer_logs = HashMap::new();
+        for owner in owners {
+            for filter in [filter.clone().topic1(owner), filter.clone().topic2(owner)] {
+                let logs = provider
+                    .get_logs(&filter)
+                    .await
+                    .map_err(|e| anyhow!("Failed to get transfer logs: {:?}", e))?;
+                transfer_logs.extend(
+                    logs.into_iter()
+                        .map(|log| ((log.transaction_hash, log.log_index), log)),
+                );
+            }
+        }
+
+        for log in transfer_logs.into_values() {
+            if !log
+                .transaction_hash
+                .map_or(false, |hash| foreign_txs.contains(&hash))
+            {
+                continue;
+            }
+            if log.address != *WETH_ADDRESS || log.topics.len() != 3 {
+                return Ok(None);
+            }
+
+            let amount = I256::from_raw(U256::from_big_endian(&log.data));
+            if owners.contains(&Address::from(log.topics[2])) {
+                flow += amount;
+            }
+            if owners.contains(&Address::from(log.topics[1])) {
+                flow -= amount;
+            }
+        }
+
+        Ok(Some(flow))
+    }
+
+    /// Total eth + weth held by the searcher and the sandwich contract at `block` plus the cost
+    /// basis of inventory (used to measure realized pnl, buying inventory isn't a loss)
+    pub async fn get_portfolio_value<M: Middleware + 'static>(
+        &self,
+        provider: Arc<M>,
+        block: U64,
+    ) -> Result<U256> {
+        let weth = Erc20::new(*WETH_ADDRESS, provider.clone());
+        let mut value = U256::zero();
+
+        for owner in [self.sandwich_contract, self.get_searcher_address()] {
+            value += provider
+                .get_balance(owner, Some(block.into()))
+                .await
+                .map_err(|e| anyhow!("Failed to get eth balance of {:?}: {:?}", owner, e))?;
+            value += weth
+                .balance_of(owner)
+                .block(block)
+                .call()
+                .await
+                .map_err(|e| anyhow!("Failed to get weth balance of {:?}: {:?}", owner, e))?;
+        }
+        value += self
+            .cost_basis
+            .values()
+            .fold(U256::zero(), |acc, c| acc + c);
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_manager() -> SandwichStateManager {
+        SandwichStateManager::new(
+            Address::from_low_u64_be(0x5a),
+            "0000000000000000000000000000000000000000000000000000000000000001"
+                .parse()
+                .unwrap(),
+            U64::zero(),
+            BaseTokenConfig::default(),
+            InventoryConfig::default(),
+        )
+    }
+
+    /// Apply a block's refreshed balances the way `update` does
+    fn sync(
+        manager: &mut SandwichStateManager,
+        number: u64,
+        balances: Vec<(Address, Option<U256>)>,
+        eth_balance: u64,
+    ) {
+        let previous_eth_balance = manager.eth_balance;
+        let previous_cost_basis = manager.cost_basis.clone();
+        let previous_unusable_tokens = manager.unusable_tokens.clone();
+        let previous_balances = manager.apply_balances(balances, U256::from(eth_balance));
+        manager.push_journal_entry(BalanceJournalEntry {
+            number: number.into(),
+            hash: H256::from_low_u64_be(number),
+            previous_balances,
+            previous_eth_balance,
+            previous_cost_basis,
+            previous_unusable_tokens,
+        });
+    }
+
+    /// a failed refresh keeps the token unusable (not its stale balance) until a refresh succeeds
+    #[test]
+    fn failed_refresh_marks_token_unusable() {
+        let token = Address::from_low_u64_be(1);
+        let mut manager = state_manager();
+
+        sync(&mut manager, 1, vec![(token, Some(U256::from(5)))], 0);
+        assert!(manager.is_usable(token));
+
+        sync(&mut manager, 2, vec![(token, None)], 0);
+        assert!(!manager.is_usable(token));
+
+        sync(&mut manager, 3, vec![(token, Some(U256::from(7)))], 0);
+        assert!(manager.is_usable(token));
+        assert_eq!(manager.get_token_balance(token), U256::from(7));
+    }
+
+    /// unwinding a reorged block restores balances, untracked tokens & unusable tokens
+    #[test]
+    fn unwind_restores_state_before_block() {
+        let (kept, failed, new) = (
+            Address::from_low_u64_be(1),
+            Address::from_low_u64_be(2),
+            Address::from_low_u64_be(3),
+        );
+        let mut manager = state_manager();
+
+        sync(
+            &mut manager,
+            1,
+            vec![(kept, Some(U256::from(5))), (failed, Some(U256::from(6)))],
+            10,
+        );
+        sync(
+            &mut manager,
+            2,
+            vec![
+                (kept, Some(U256::from(9))),
+                (failed, None),
+                (new, Some(U256::from(3))),
+            ],
+            20,
+        );
+        assert!(!manager.is_usable(failed));
+        assert!(manager.has_dust(new));
+
+        let entry = manager.journal.pop_back().unwrap();
+        manager.unwind(entry);
+
+        assert_eq!(manager.get_token_balance(kept), U256::from(5));
+        assert_eq!(manager.get_token_balance(failed), U256::from(6));
+        assert!(manager.is_usable(failed));
+        assert!(!manager.token_balances.contains_key(&new));
+        assert_eq!(manager.get_eth_balance(), U256::from(10));
+        assert_eq!(manager.journal.back().unwrap().number, U64::from(1));
+    }
+
+    /// journal only keeps as many blocks as a reorg can unwind
+    #[test]
+    fn journal_is_bounded_by_reorg_depth() {
+        let mut manager = state_manager();
+
+        for number in 0..2 * REORG_DEPTH as u64 {
+            sync(&mut manager, number, vec![], 0);
+        }
+
+        assert_eq!(manager.journal.len(), REORG_DEPTH);
+        assert_eq!(
+            manager.journal.front().unwrap().number,
+            U64::from(REORG_DEPTH)
+        );
     }
 }
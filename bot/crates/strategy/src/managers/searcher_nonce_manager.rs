@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use ethers::{
+    providers::Middleware,
+    types::{Address, U256, U64},
+};
+use std::sync::Arc;
+
+/// Nonces handed out to one bundle
+struct Reservation {
+    start: U256,
+    count: u64,
+    /// Sandwiches in the bundle (zero for rebalances)
+    sandwiches: u64,
+    /// (intermediary token, frontrun input in eth) of the bundle's sandwich legs
+    exposure: Vec<(Address, U256)>,
+}
+
+impl Reservation {
+    fn end(&self) -> U256 {
+        self.start + self.count
+    }
+}
+
+/// Searcher txs & sandwiches that landed in the latest block
+#[derive(Default, Clone)]
+pub struct Landed {
+    pub txs: u64,
+    /// Txs whose nonce wasn't handed out by the bot (e.g. manual withdrawals)
+    pub untracked_txs: u64,
+    pub sandwiches: u64,
+    /// Frontrun exposure of the sandwiches that landed
+    pub exposure: Vec<(Address, U256)>,
+}
+
+/// Keeps track of the searcher's confirmed nonce and of the nonce ranges handed out to bundles
+/// targetting the upcoming block, without an rpc round trip per bundle
+/// Bundles for the same block are alternatives (only one of them can land since they all spend
+/// the confirmed nonce), so every range starts at the confirmed nonce. Several sandwiches only
+/// chain their nonces inside a single merged bundle
+pub struct SearcherNonceManager {
+    searcher: Address,
+    /// Searcher nonce at latest block (`None` until first synced)
+    confirmed: Option<U256>,
+    /// Ranges handed out for the upcoming block, in the order they were handed out
+    reservations: Vec<Reservation>,
+}
+
+impl SearcherNonceManager {
+    pub fn new(searcher: Address) -> Self {
+        Self {
+            searcher,
+            confirmed: None,
+            reservations: vec![],
+        }
+    }
+
+    /// Hand out `count` nonces starting at the confirmed nonce for a bundle targetting the
+    /// upcoming block, returns the first nonce of the range
+    pub fn reserve(
+        &mut self,
+        count: u64,
+        sandwiches: u64,
+        exposure: Vec<(Address, U256)>,
+    ) -> Result<U256> {
+        let start = self
+            .confirmed
+            .ok_or_else(|| anyhow!("searcher nonce not synced yet"))?;
+
+        self.reservations.push(Reservation {
+            start,
+            count,
+            sandwiches,
+            exposure,
+        });
+
+        Ok(start)
+    }
+
+    /// Give back the latest range starting at `start`, its bundle could not be built
+    pub fn release(&mut self, start: U256) {
+        if let Some(position) = self.reservations.iter().rposition(|r| r.start == start) {
+            self.reservations.remove(position);
+        }
+    }
+
+    /// Resync the confirmed nonce from chain
+    /// Returns what landed since the previous sync, ranges for the old target block are dropped
+    /// (bundles only target a single block)
+    pub async fn on_new_block<M: Middleware + 'static>(
+        &mut self,
+        latest_block: U64,
+        provider: Arc<M>,
+    ) -> Result<Landed> {
+        let nonce = provider
+            .get_transaction_count(self.searcher, Some(latest_block.into()))
+            .await
+            .map_err(|e| anyhow!("Failed to get searcher nonce {:?}", e))?;
+
+        let mut landed = Landed::default();
+        if let Some(previous_nonce) = self.confirmed {
+            if nonce > previous_nonce {
+                landed = self.find_landed(previous_nonce, nonce);
+            }
+        }
+
+        self.confirmed = Some(nonce);
+        self.reservations.clear();
+
+        Ok(landed)
+    }
+
+    /// Walk the consumed nonces from `from` to `to`, at each step taking the longest range that
+    /// starts there and was fully consumed
+    fn find_landed(&self, from: U256, to: U256) -> Landed {
+        let mut landed = Landed {
+            txs: (to - from).as_u64(),
+            ..Default::default()
+        };
+        let mut position = from;
+
+        while position < to {
+            let consumed = self
+                .reservations
+                .iter()
+                .filter(|r| r.start == position && r.count > 0 && r.end() <= to)
+                .max_by_key(|r| r.count);
+
+            match consumed {
+                Some(reservation) => {
+                    landed.sandwiches += reservation.sandwiches;
+                    landed.exposure.extend(reservation.exposure.iter().copied());
+                    position = reservation.end();
+                }
+                // nonce used outside of the bot (or by an untracked bundle)
+                None => {
+                    landed.untracked_txs += 1;
+                    position += U256::one();
+                }
+            }
+        }
+
+        landed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Manager synced at nonce 10
+    fn synced() -> SearcherNonceManager {
+        let mut manager = SearcherNonceManager::new(Address::zero());
+        manager.confirmed = Some(U256::from(10));
+        manager
+    }
+
+    fn exposure(token: u64) -> Vec<(Address, U256)> {
+        vec![(Address::from_low_u64_be(token), U256::exp10(18))]
+    }
+
+    #[test]
+    fn reserve_needs_a_synced_nonce() {
+        let mut manager = SearcherNonceManager::new(Address::zero());
+        assert!(manager.reserve(2, 1, vec![]).is_err());
+        assert_eq!(synced().reserve(2, 1, vec![]).unwrap(), U256::from(10));
+    }
+
+    /// a merged bundle and the single sandwich bundles it was built from share their start nonce
+    #[test]
+    fn longest_fully_consumed_range_landed() {
+        let mut manager = synced();
+        manager.reserve(2, 1, exposure(1)).unwrap();
+        manager.reserve(4, 2, exposure(2)).unwrap();
+
+        let landed = manager.find_landed(U256::from(10), U256::from(14));
+        assert_eq!(landed.txs, 4);
+        assert_eq!(landed.untracked_txs, 0);
+        assert_eq!(landed.sandwiches, 2);
+        assert_eq!(landed.exposure, exposure(2));
+
+        // the merged bundle's range wasn't fully consumed, only the single sandwich landed
+        let landed = manager.find_landed(U256::from(10), U256::from(12));
+        assert_eq!(landed.sandwiches, 1);
+        assert_eq!(landed.exposure, exposure(1));
+    }
+
+    #[test]
+    fn nonces_outside_reservations_are_untracked() {
+        let mut manager = synced();
+        manager.reserve(2, 1, exposure(1)).unwrap();
+
+        let landed = manager.find_landed(U256::from(10), U256::from(13));
+        assert_eq!(landed.txs, 3);
+        assert_eq!(landed.untracked_txs, 1);
+        assert_eq!(landed.sandwiches, 1);
+
+        let landed = synced().find_landed(U256::from(10), U256::from(12));
+        assert_eq!(landed.untracked_txs, 2);
+        assert_eq!(landed.sandwiches, 0);
+    }
+
+    #[test]
+    fn release_removes_latest_range_with_start() {
+        let mut manager = synced();
+        manager.reserve(2, 1, exposure(1)).unwrap();
+        manager.reserve(2, 1, exposure(2)).unwrap();
+
+        manager.release(U256::from(10));
+        assert_eq!(manager.reservations.len(), 1);
+        assert_eq!(manager.reservations[0].exposure, exposure(1));
+
+        // nothing starts there
+        manager.release(U256::from(11));
+        assert_eq!(manager.reservations.len(), 1);
+    }
+}
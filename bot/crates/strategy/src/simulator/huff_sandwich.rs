@@ -2,175 +2,315 @@ use anvil::eth::util::get_precompiles_for;
 use anyhow::{anyhow, Result};
 use cfmms::pool::Pool::{UniswapV2, UniswapV3};
 use cfmms::pool::UniswapV2Pool;
+use dashmap::DashMap;
 use ethers::abi::{self, parse_abi, Address, ParamType};
 use ethers::prelude::BaseContract;
 use ethers::types::{Bytes, U256};
 use foundry_evm::executor::TxEnv;
 use foundry_evm::executor::{
-    fork::SharedBackend, inspector::AccessListTracer, ExecutionResult, Output, TransactTo,
+    fork::SharedBackend, inspector::AccessListTracer, Bytecode, ExecutionResult, Output, TransactTo,
 };
 use foundry_evm::revm::{
     db::CacheDB,
-    primitives::{Address as rAddress, U256 as rU256},
+    primitives::{AccountInfo, U256 as rU256},
     EVM,
 };
 
-use crate::constants::{GET_RESERVES_SIG, SUGAR_DADDY, WETH_ADDRESS};
+use crate::base_token::BaseToken;
+use crate::block_env::ChainConfig;
+use crate::bribe_policy::BribePayment;
+use crate::constants::{COINBASE, GET_RESERVES_SIG, SUGAR_DADDY, WETH_ADDRESS};
 use crate::helpers::access_list_to_revm;
+use crate::inventory::Rebalance;
 use crate::simulator::setup_block_state;
+use crate::simulator::uniswap_v3::v3_get_amount_out;
 use crate::tx_utils::huff_sandwich_interface::common::five_byte_encoder::FiveByteMetaData;
 use crate::tx_utils::huff_sandwich_interface::{
-    common::weth_encoder::WethEncoder,
-    v2::{v2_create_backrun_payload, v2_create_frontrun_payload},
+    common::{encode_coinbase_bribe, weth_encoder::WethEncoder},
+    v2::{v2_create_backrun_payload, v2_create_flash_payload, v2_create_frontrun_payload},
     v3::{v3_create_backrun_payload, v3_create_frontrun_payload},
 };
-use crate::types::{BlockInfo, RawIngredients, SandwichRecipe};
+use crate::tx_utils::meat::meat_to_tx_env;
+use crate::types::{BlockInfo, RawIngredients, SandwichLeg, SandwichRecipe};
 
 use super::salmonella_inspector::{IsSandwichSafu, SalmonellaInspectoooor};
 
+/// Calldata gas of a zero and of a nonzero byte (EIP-2028)
+const ZERO_CALLDATA_BYTE_GAS: u64 = 4;
+const NONZERO_CALLDATA_BYTE_GAS: u64 = 16;
+
 /// finds if sandwich is profitable + salmonella free
 pub fn create_recipe(
     ingredients: &RawIngredients,
     next_block: &BlockInfo,
-    optimal_in: U256,
+    optimal_ins: &[U256],
     sandwich_start_bal: U256,
+    has_dust: bool,
     searcher: Address,
     sandwich_address: Address,
+    bribe_payment: BribePayment,
+    chain: &ChainConfig,
+    v2_fee_checks: &DashMap<Address, bool>,
     shared_backend: SharedBackend,
 ) -> Result<SandwichRecipe> {
-    #[allow(unused_mut)]
     let mut fork_db = CacheDB::new(shared_backend);
 
+    // builder coinbase accounts already exist onchain, so coinbase transfers in our
+    // simulations should not be charged new account gas
+    fork_db.insert_account_info(
+        *COINBASE,
+        AccountInfo::new(rU256::from(1), 0, Bytecode::default()),
+    );
+
     #[cfg(feature = "debug")]
     {
         inject_huff_sandwich(
             &mut fork_db,
             sandwich_address.0.into(),
             searcher.0.into(),
+            ingredients.get_start_end_token(),
             sandwich_start_bal,
         );
+
+        // fund injected contract with the inventory that legs sell
+        for token in ingredients
+            .get_legs()
+            .iter()
+            .filter_map(|l| l.inventory_token)
+        {
+            let slot = token.balance_storage_key(sandwich_address);
+            fork_db
+                .insert_account_storage(
+                    token.address.into(),
+                    rU256::from_be_bytes(slot),
+                    *crate::constants::WETH_FUND_AMT,
+                )
+                .unwrap();
+        }
     }
     let mut evm = EVM::new();
     evm.database(fork_db);
-    setup_block_state(&mut evm, &next_block);
+    setup_block_state(&mut evm, next_block, chain);
 
-    // *´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
-    // *                    FRONTRUN TRANSACTION                    */
-    // *.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
-    // encode frontrun_in before passing to sandwich contract
-    let frontrun_in = WethEncoder::decode(WethEncoder::encode(optimal_in));
-
-    // caluclate frontrun_out using encoded frontrun_in
-    let frontrun_out = match ingredients.get_target_pool() {
-        UniswapV2(p) => {
-            evm.env.tx.gas_price = next_block.base_fee_per_gas.into();
-            evm.env.tx.gas_limit = 700000;
-            evm.env.tx.value = rU256::ZERO;
-            v2_get_amount_out(frontrun_in, p, true, &mut evm)?
+    // legs without input are left out of multi pool sandwiches
+    let legs: Vec<(SandwichLeg, U256)> = ingredients
+        .get_legs()
+        .iter()
+        .copied()
+        .zip(optimal_ins.iter().copied())
+        .filter(|(_, optimal_in)| ingredients.get_legs().len() == 1 || !optimal_in.is_zero())
+        .collect();
+
+    if legs.is_empty() {
+        return Err(anyhow!(
+            "[huffsandwich: NoInput] no leg has a frontrun input"
+        ));
+    }
+
+    // revenue is measured in a single token
+    if legs
+        .iter()
+        .any(|(l, _)| l.start_end_token != legs[0].0.start_end_token)
+    {
+        return Err(anyhow!(
+            "[huffsandwich: MixedBaseTokens] all legs must start and end with the same token"
+        ));
+    }
+
+    // v2 payloads are priced with each pool's configured fee, make sure the pairs charge it
+    for (leg, _) in &legs {
+        for pool in leg.flash_pool.into_iter().chain(match leg.target_pool {
+            UniswapV2(p) => Some(p),
+            UniswapV3(_) => None,
+        }) {
+            check_v2_fee(
+                pool,
+                leg.start_end_token,
+                next_block,
+                v2_fee_checks,
+                &mut evm,
+            )?;
         }
-        UniswapV3(_) => U256::zero(),
-    };
+    }
 
-    // create tx.data and tx.value for frontrun_in
-    let (frontrun_data, frontrun_value) = match ingredients.get_target_pool() {
-        UniswapV2(p) => v2_create_frontrun_payload(
-            p,
-            ingredients.get_intermediary_token(),
-            frontrun_in,
-            frontrun_out,
-        ),
-        UniswapV3(p) => v3_create_frontrun_payload(
-            p,
-            ingredients.get_intermediary_token(),
-            frontrun_in.as_u128().into(),
-        ),
+    // when several legs hold the same token, each backrun only sells what its frontrun bought
+    // (flash swap & inventory legs never sell more than their frontrun moved)
+    let is_token_shared = |token: Address| {
+        legs.iter()
+            .filter(|(l, _)| {
+                l.intermediary_token == token
+                    && l.flash_pool.is_none()
+                    && l.inventory_token.is_none()
+            })
+            .count()
+            > 1
     };
 
-    // setup evm for frontrun transaction
-    let mut frontrun_tx_env = TxEnv {
-        caller: searcher.0.into(),
-        gas_limit: 700000,
-        gas_price: next_block.base_fee_per_gas.into(),
-        gas_priority_fee: None,
-        transact_to: TransactTo::Call(sandwich_address.0.into()),
-        value: frontrun_value.into(),
-        data: frontrun_data.clone().into(),
-        chain_id: None,
-        nonce: None,
-        access_list: Default::default(),
-    };
-    evm.env.tx = frontrun_tx_env.clone();
+    // *´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
+    // *                   FRONTRUN TRANSACTION/s                   */
+    // *.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
+    let mut frontruns = vec![];
+    // (intermediary token, base token put into it) per leg
+    let mut frontrun_exposure = vec![];
+    let mut bought_amounts = vec![];
+    // base token lost to lossy calldata encodings
+    let mut encoding_loss = U256::zero();
 
-    // get access list
-    let mut access_list_inspector = AccessListTracer::new(
-        Default::default(),
-        searcher,
-        sandwich_address,
-        get_precompiles_for(evm.env.cfg.spec_id),
-    );
-    evm.inspect_ref(&mut access_list_inspector)
-        .map_err(|e| anyhow!("[EVM ERROR] frontrun: {:?}", (e)))?;
-    let frontrun_access_list = access_list_inspector.access_list();
+    for (leg, optimal_in) in &legs {
+        let base_token = leg.start_end_token.address;
+        // encode frontrun_in before passing to sandwich contract (no-op for searched inputs)
+        let frontrun_in = WethEncoder::truncate(*optimal_in);
 
-    frontrun_tx_env.access_list = access_list_to_revm(frontrun_access_list);
-    evm.env.tx = frontrun_tx_env.clone();
+        evm.env.tx.gas_price = next_block.base_fee_per_gas.into();
+        evm.env.tx.gas_limit = 700000;
+        evm.env.tx.value = rU256::ZERO;
 
-    // run again but now with access list (so that we get accurate gas used)
-    // run with a salmonella inspector to flag `suspicious` opcodes
-    let mut salmonella_inspector = SalmonellaInspectoooor::new();
-    let frontrun_result = match evm.inspect_commit(&mut salmonella_inspector) {
-        Ok(result) => result,
-        Err(e) => return Err(anyhow!("[huffsandwich: EVM ERROR] frontrun: {:?}", e)),
-    };
-    match frontrun_result {
-        ExecutionResult::Success { .. } => { /* continue operation */ }
-        ExecutionResult::Revert { output, .. } => {
-            return Err(anyhow!("[huffsandwich: REVERT] frontrun: {:?}", output));
-        }
-        ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow!("[huffsandwich: HALT] frontrun: {:?}", reason));
-        }
-    };
-    match salmonella_inspector.is_sandwich_safu() {
-        IsSandwichSafu::Safu => { /* continue operation */ }
-        IsSandwichSafu::NotSafu(not_safu_opcodes) => {
-            return Err(anyhow!(
-                "[huffsandwich: FrontrunNotSafu] {:?}",
-                not_safu_opcodes
-            ))
+        // exact amount bought on v3 legs (v2 legs encode it in their payload)
+        let mut v3_frontrun_out = U256::zero();
+
+        // create tx.data and tx.value for frontrun_in
+        let (frontrun_data, frontrun_value) = match (leg.flash_pool, leg.target_pool) {
+            // `token->weth` victim, borrow intermediary token from flash pool and sell it into
+            // target pool (weth repaid to flash pool is what we put into the sandwich)
+            (Some(flash_pool), UniswapV2(p)) => {
+                let borrow_amount =
+                    v2_get_amount_out(frontrun_in, flash_pool, base_token, true, &mut evm)?;
+                let borrow_amount = FiveByteMetaData::encode(borrow_amount, 0).decode();
+                let weth_repay =
+                    v2_get_amount_in(borrow_amount, flash_pool, base_token, true, &mut evm)?;
+                let weth_out = v2_get_amount_out(borrow_amount, p, base_token, false, &mut evm)?;
+
+                let (frontrun_data, frontrun_value) = v2_create_flash_payload(
+                    flash_pool,
+                    p,
+                    leg.intermediary_token,
+                    borrow_amount,
+                    weth_repay,
+                    weth_out,
+                    None,
+                );
+                encoding_loss += flash_encoding_loss(weth_repay, weth_out);
+                frontrun_exposure
+                    .push((leg.intermediary_token, WethEncoder::decode(frontrun_value)));
+                // backrun borrows the same amount back from the target pool
+                bought_amounts.push(borrow_amount);
+                (frontrun_data, frontrun_value)
+            }
+            (Some(_), UniswapV3(_)) => {
+                return Err(anyhow!(
+                    "[huffsandwich: UNSUPPORTED] flash swap legs can only target v2 pools"
+                ))
+            }
+            // `token->weth` victim, sell inventory into target pool (weth received is what we put
+            // into the sandwich)
+            (None, UniswapV2(p)) if leg.inventory_token.is_some() => {
+                let weth_out = v2_get_amount_out(frontrun_in, p, base_token, false, &mut evm)?;
+                frontrun_exposure.push((leg.intermediary_token, weth_out));
+                // backrun buys the same amount back
+                bought_amounts.push(frontrun_in);
+                v2_create_frontrun_payload(
+                    p,
+                    leg.intermediary_token,
+                    base_token,
+                    frontrun_in,
+                    weth_out,
+                )
+            }
+            (None, UniswapV3(_)) if leg.inventory_token.is_some() => {
+                return Err(anyhow!(
+                    "[huffsandwich: UNSUPPORTED] inventory legs can only target v2 pools"
+                ))
+            }
+            (None, UniswapV2(p)) => {
+                frontrun_exposure.push((leg.intermediary_token, frontrun_in));
+                encoding_loss += *optimal_in - frontrun_in;
+                // caluclate frontrun_out using encoded frontrun_in
+                let frontrun_out = v2_get_amount_out(frontrun_in, p, base_token, true, &mut evm)?;
+                v2_create_frontrun_payload(
+                    p,
+                    base_token,
+                    leg.intermediary_token,
+                    frontrun_in,
+                    frontrun_out,
+                )
+            }
+            (None, UniswapV3(p)) => {
+                frontrun_exposure.push((leg.intermediary_token, frontrun_in));
+                encoding_loss += *optimal_in - frontrun_in;
+                v3_frontrun_out = v3_get_amount_out(frontrun_in, p, base_token, true, &mut evm)?;
+                if v3_frontrun_out.is_zero() {
+                    return Err(anyhow!("[huffsandwich: V3ZeroOut] frontrun buys nothing"));
+                }
+                v3_create_frontrun_payload(
+                    p,
+                    base_token,
+                    leg.intermediary_token,
+                    frontrun_in.as_u128().into(),
+                )
+            }
+        };
+
+        // setup evm for frontrun transaction
+        let frontrun_tx_env = TxEnv {
+            caller: searcher.0.into(),
+            gas_limit: 700000,
+            gas_price: next_block.base_fee_per_gas.into(),
+            gas_priority_fee: None,
+            transact_to: TransactTo::Call(sandwich_address.0.into()),
+            value: frontrun_value.into(),
+            data: frontrun_data.clone().into(),
+            chain_id: None,
+            nonce: None,
+            access_list: Default::default(),
+        };
+
+        if leg.flash_pool.is_some() || leg.inventory_token.is_some() {
+            frontruns.push(simulate_sandwich_tx(
+                &mut evm,
+                frontrun_tx_env,
+                searcher,
+                sandwich_address,
+                "frontrun",
+            )?);
+            continue;
         }
-    }
 
-    let frontrun_gas_used = frontrun_result.gas_used();
+        let balance_before = match is_token_shared(leg.intermediary_token) {
+            true => Some(get_erc20_balance(
+                leg.intermediary_token,
+                sandwich_address,
+                next_block,
+                &mut evm,
+            )?),
+            false => None,
+        };
+        frontruns.push(simulate_sandwich_tx(
+            &mut evm,
+            frontrun_tx_env,
+            searcher,
+            sandwich_address,
+            "frontrun",
+        )?);
+        let bought_amount = match balance_before {
+            Some(before) => get_erc20_balance(
+                leg.intermediary_token,
+                sandwich_address,
+                next_block,
+                &mut evm,
+            )?
+            .saturating_sub(before),
+            // not needed for v2, backrun sells the whole balance
+            None => v3_frontrun_out,
+        };
+        bought_amounts.push(bought_amount);
+    }
 
     // *´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
     // *                     MEAT TRANSACTION/s                     */
     // *.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
     let mut is_meat_good = Vec::new();
     for meat in ingredients.get_meats_ref().iter() {
-        evm.env.tx.caller = rAddress::from_slice(&meat.from.0);
-        evm.env.tx.transact_to =
-            TransactTo::Call(rAddress::from_slice(&meat.to.unwrap_or_default().0));
-        evm.env.tx.data = meat.input.0.clone();
-        evm.env.tx.value = meat.value.into();
-        evm.env.tx.chain_id = meat.chain_id.map(|id| id.as_u64());
-        //evm.env.tx.nonce = Some(meat.nonce.as_u64());
-        evm.env.tx.gas_limit = meat.gas.as_u64();
-        match meat.transaction_type {
-            Some(ethers::types::U64([0])) => {
-                // legacy tx
-                evm.env.tx.gas_price = meat.gas_price.unwrap_or_default().into();
-            }
-            Some(_) => {
-                // type 2 tx
-                evm.env.tx.gas_priority_fee = meat.max_priority_fee_per_gas.map(|mpf| mpf.into());
-                evm.env.tx.gas_price = meat.max_fee_per_gas.unwrap_or_default().into();
-            }
-            None => {
-                // legacy tx
-                evm.env.tx.gas_price = meat.gas_price.unwrap().into();
-            }
-        }
+        evm.env.tx = meat_to_tx_env(meat)?;
 
         // keep track of which meat transactions are successful to filter reverted meats at end
         // remove reverted meats because mempool tx/s gas costs are accounted for by fb
@@ -185,60 +325,278 @@ pub fn create_recipe(
     }
 
     // *´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
-    // *                    BACKRUN TRANSACTION                     */
+    // *                    BACKRUN TRANSACTION/s                   */
     // *.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
-    // encode backrun_in before passing to sandwich contract
-    let backrun_token_in = ingredients.get_intermediary_token();
-    let backrun_token_out = ingredients.get_start_end_token();
-
-    // keep some dust
-    let backrun_in = get_erc20_balance(backrun_token_in, sandwich_address, next_block, &mut evm)?;
-    let backrun_in = match ingredients.get_target_pool() {
-        UniswapV2(_) => {
-            let mut backrun_in_encoded = FiveByteMetaData::encode(backrun_in, 1);
-            backrun_in_encoded.decrement_four_bytes();
-            backrun_in_encoded.decode()
-        }
-        UniswapV3(_) => {
-            let backrun_in_encoded = FiveByteMetaData::encode(backrun_in, 1);
-            backrun_in_encoded.decode()
-        }
-    };
+    let backrun_token_out = ingredients.get_start_end_token().address;
+    let mut backruns = vec![];
 
-    // caluclate backrun_out using encoded backrun_in
-    let backrun_out = match ingredients.get_target_pool() {
-        UniswapV2(p) => {
-            let out = v2_get_amount_out(backrun_in, p, false, &mut evm)?;
-            out
+    for (i, ((leg, _), bought_amount)) in legs.iter().zip(bought_amounts).enumerate() {
+        // encode backrun_in before passing to sandwich contract
+        let base_token = leg.start_end_token.address;
+        let backrun_token_in = leg.intermediary_token;
+
+        // when paying through coinbase, simulate with a placeholder bribe so that gas used accounts
+        // for the transfer (real amount is filled in when the bundle is created)
+        // only the last backrun pays the bribe
+        let coinbase_bribe = match bribe_payment {
+            BribePayment::CoinbaseTransfer if i == legs.len() - 1 => Some(U256::one()),
+            _ => None,
+        };
+
+        // create tx.data and tx.value for backrun_in
+        let (backrun_data, backrun_value) = match (leg.flash_pool, leg.target_pool) {
+            // borrow back what was sold in the frontrun from target pool and sell it into flash pool
+            (Some(flash_pool), UniswapV2(p)) => {
+                let weth_repay = v2_get_amount_in(bought_amount, p, base_token, true, &mut evm)?;
+                let weth_out =
+                    v2_get_amount_out(bought_amount, flash_pool, base_token, false, &mut evm)?;
+                encoding_loss += flash_encoding_loss(weth_repay, weth_out);
+                v2_create_flash_payload(
+                    p,
+                    flash_pool,
+                    backrun_token_in,
+                    bought_amount,
+                    weth_repay,
+                    weth_out,
+                    coinbase_bribe,
+                )
+            }
+            (Some(_), UniswapV3(_)) => {
+                return Err(anyhow!(
+                    "[huffsandwich: UNSUPPORTED] flash swap legs can only target v2 pools"
+                ))
+            }
+            // buy back what was sold from inventory, leftover weth is the revenue
+            (None, UniswapV2(p)) if leg.inventory_token.is_some() => {
+                let weth_in = v2_get_amount_in(bought_amount, p, base_token, true, &mut evm)?;
+                let weth_in = FiveByteMetaData::encode(weth_in, 1).decode();
+                let token_out = v2_get_amount_out(weth_in, p, base_token, true, &mut evm)?;
+                v2_create_backrun_payload(
+                    p,
+                    backrun_token_in,
+                    base_token,
+                    weth_in,
+                    token_out,
+                    coinbase_bribe,
+                )
+            }
+            (None, target_pool) => {
+                // keep some dust
+                let backrun_in = if is_token_shared(backrun_token_in) {
+                    bought_amount
+                } else {
+                    get_erc20_balance(backrun_token_in, sandwich_address, next_block, &mut evm)?
+                };
+                let backrun_in = match target_pool {
+                    UniswapV2(_) => {
+                        let mut backrun_in_encoded = FiveByteMetaData::encode(backrun_in, 1);
+                        backrun_in_encoded.decrement_four_bytes();
+                        backrun_in_encoded.decode()
+                    }
+                    UniswapV3(_) => {
+                        let backrun_in_encoded = FiveByteMetaData::encode(backrun_in, 1);
+                        backrun_in_encoded.decode()
+                    }
+                };
+
+                match target_pool {
+                    UniswapV2(p) => {
+                        // caluclate backrun_out using encoded backrun_in
+                        let backrun_out =
+                            v2_get_amount_out(backrun_in, p, base_token, false, &mut evm)?;
+                        encoding_loss += backrun_out - WethEncoder::truncate(backrun_out);
+                        v2_create_backrun_payload(
+                            p,
+                            base_token,
+                            backrun_token_in,
+                            backrun_in,
+                            backrun_out,
+                            coinbase_bribe,
+                        )
+                    }
+                    // v3 payload doesn't carry an amount out, only make sure the backrun pays out
+                    UniswapV3(p) => {
+                        let backrun_out =
+                            v3_get_amount_out(backrun_in, p, base_token, false, &mut evm)?;
+                        if backrun_out.is_zero() {
+                            return Err(anyhow!(
+                                "[huffsandwich: V3ZeroOut] backrun sells for nothing"
+                            ));
+                        }
+                        (
+                            v3_create_backrun_payload(
+                                p,
+                                base_token,
+                                backrun_token_in,
+                                backrun_in,
+                                coinbase_bribe,
+                            ),
+                            U256::zero(),
+                        )
+                    }
+                }
+            }
+        };
+
+        // setup evm for backrun transaction
+        let backrun_tx_env = TxEnv {
+            caller: searcher.0.into(),
+            gas_limit: 700000,
+            gas_price: next_block.base_fee_per_gas.into(),
+            gas_priority_fee: None,
+            transact_to: TransactTo::Call(sandwich_address.0.into()),
+            value: backrun_value.into(),
+            data: backrun_data.clone().into(),
+            chain_id: None,
+            nonce: None,
+            access_list: Default::default(),
+        };
+
+        let (backrun_tx_env, mut backrun_gas_used) = simulate_sandwich_tx(
+            &mut evm,
+            backrun_tx_env,
+            searcher,
+            sandwich_address,
+            "backrun",
+        )?;
+
+        // the placeholder's zero bytes are cheaper calldata than the real bribe's, charge them as
+        // nonzero bytes so gas used covers whatever bribe ends up appended
+        if let Some(placeholder) = coinbase_bribe {
+            let zero_bytes = encode_coinbase_bribe(placeholder)
+                .iter()
+                .filter(|byte| **byte == 0)
+                .count() as u64;
+            backrun_gas_used += zero_bytes * (NONZERO_CALLDATA_BYTE_GAS - ZERO_CALLDATA_BYTE_GAS);
         }
-        UniswapV3(_p) => U256::zero(), // we don't need to know backrun out for v3
+        backruns.push((backrun_tx_env, backrun_gas_used));
+    }
+
+    // *´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
+    // *                      GENERATE REPORTS                      */
+    // *.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
+    // caluclate revenue from balance change
+    let post_sandwich_bal =
+        get_erc20_balance(backrun_token_out, sandwich_address, next_block, &mut evm)?;
+
+    let revenue = post_sandwich_bal
+        .checked_sub(sandwich_start_bal)
+        .unwrap_or_default();
+
+    // bribes & guardrails work in eth, other base tokens are valued through their weth pair
+    let (revenue, frontrun_exposure, encoding_loss) = if ingredients.get_start_end_token().is_weth()
+    {
+        (revenue, frontrun_exposure, encoding_loss)
+    } else {
+        let quote_pool = legs[0].0.quote_pool.ok_or(anyhow!(
+            "[huffsandwich: NoQuotePool] can't value {:?} in eth",
+            backrun_token_out
+        ))?;
+        (
+            v2_value_in_weth(revenue, quote_pool, &mut evm)?,
+            frontrun_exposure
+                .into_iter()
+                .map(|(token, amount)| Ok((token, v2_value_in_weth(amount, quote_pool, &mut evm)?)))
+                .collect::<Result<Vec<_>>>()?,
+            v2_value_in_weth(encoding_loss, quote_pool, &mut evm)?,
+        )
     };
 
-    // create tx.data and tx.value for backrun_in
-    let (backrun_data, backrun_value) = match ingredients.get_target_pool() {
-        UniswapV2(p) => v2_create_backrun_payload(p, backrun_token_in, backrun_in, backrun_out),
-        UniswapV3(p) => (
-            v3_create_backrun_payload(p, backrun_token_in, backrun_in),
-            U256::zero(),
+    // filter only passing meat txs
+    let good_meats_only = ingredients
+        .get_meats_ref()
+        .iter()
+        .zip(is_meat_good.iter())
+        .filter(|&(_, &b)| b)
+        .map(|(s, _)| s.to_owned())
+        .collect();
+
+    Ok(SandwichRecipe::new(
+        frontruns,
+        frontrun_exposure,
+        good_meats_only,
+        backruns,
+        revenue,
+        *next_block,
+        bribe_payment,
+        has_dust,
+        encoding_loss,
+    ))
+}
+
+/// Weth lost by a flash payload's encodings (`weth_out` is rounded down by `FiveByteMetaData`,
+/// `weth_repay` is rounded up by `WethEncoder`)
+fn flash_encoding_loss(weth_repay: U256, weth_out: U256) -> U256 {
+    (weth_out - FiveByteMetaData::truncate(weth_out))
+        + (WethEncoder::decode(WethEncoder::encode_ceil(weth_repay)) - weth_repay)
+}
+
+/// Trades an inventory token against weth on its v2 weth pair through the sandwich contract
+/// Returns the tx (with its access list attached) and gas used
+//
+// Arguments:
+// * `pool`: weth pair of `token`
+// * `rebalance`: how much of `token` to buy or sell
+pub fn create_rebalance_tx(
+    pool: UniswapV2Pool,
+    token: Address,
+    rebalance: Rebalance,
+    next_block: &BlockInfo,
+    searcher: Address,
+    sandwich_address: Address,
+    chain: &ChainConfig,
+    shared_backend: SharedBackend,
+) -> Result<(TxEnv, u64)> {
+    let mut evm = EVM::new();
+    evm.database(CacheDB::new(shared_backend));
+    setup_block_state(&mut evm, next_block, chain);
+
+    // sandwich contract's frontrun pays with whichever token is appended to the payload
+    let (base_token, output_token, amount_in) = match rebalance {
+        Rebalance::Buy(amount) => (
+            *WETH_ADDRESS,
+            token,
+            v2_get_amount_in(amount, pool, *WETH_ADDRESS, true, &mut evm)?,
         ),
+        Rebalance::Sell(amount) => (token, *WETH_ADDRESS, amount),
     };
+    let amount_in = WethEncoder::decode(WethEncoder::encode(amount_in));
+    let amount_out = v2_get_amount_out(amount_in, pool, base_token, true, &mut evm)?;
+
+    let (data, value) =
+        v2_create_frontrun_payload(pool, base_token, output_token, amount_in, amount_out);
 
-    // setup evm for backrun transaction
-    let mut backrun_tx_env = TxEnv {
+    let tx_env = TxEnv {
         caller: searcher.0.into(),
         gas_limit: 700000,
         gas_price: next_block.base_fee_per_gas.into(),
         gas_priority_fee: None,
         transact_to: TransactTo::Call(sandwich_address.0.into()),
-        value: backrun_value.into(),
-        data: backrun_data.clone().into(),
+        value: value.into(),
+        data: data.into(),
         chain_id: None,
         nonce: None,
         access_list: Default::default(),
     };
-    evm.env.tx = backrun_tx_env.clone();
 
-    // create access list
+    simulate_sandwich_tx(&mut evm, tx_env, searcher, sandwich_address, "rebalance")
+}
+
+/// Runs a sandwich contract tx on top of `evm` and commits its state changes
+/// Returns the tx (with its access list attached) and gas used
+//
+// Arguments:
+// * `label`: which part of the sandwich the tx is (used in errors)
+fn simulate_sandwich_tx(
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+    mut tx_env: TxEnv,
+    searcher: Address,
+    sandwich_address: Address,
+    label: &str,
+) -> Result<(TxEnv, u64)> {
+    evm.env.tx = tx_env.clone();
+
+    // get access list
     let mut access_list_inspector = AccessListTracer::new(
         Default::default(),
         searcher,
@@ -246,71 +604,194 @@ pub fn create_recipe(
         get_precompiles_for(evm.env.cfg.spec_id),
     );
     evm.inspect_ref(&mut access_list_inspector)
-        .map_err(|e| anyhow!("[huffsandwich: EVM ERROR] frontrun: {:?}", e))
-        .unwrap();
-    let backrun_access_list = access_list_inspector.access_list();
-    backrun_tx_env.access_list = access_list_to_revm(backrun_access_list);
-    evm.env.tx = backrun_tx_env.clone();
+        .map_err(|e| anyhow!("[huffsandwich: EVM ERROR] {}: {:?}", label, e))?;
+    let access_list = access_list_inspector.access_list();
+
+    tx_env.access_list = access_list_to_revm(access_list);
+    evm.env.tx = tx_env.clone();
 
     // run again but now with access list (so that we get accurate gas used)
     // run with a salmonella inspector to flag `suspicious` opcodes
-    let mut salmonella_inspector = SalmonellaInspectoooor::new();
-    let backrun_result = match evm.inspect_commit(&mut salmonella_inspector) {
+    let mut salmonella_inspector =
+        SalmonellaInspectoooor::new().with_trusted_contract(sandwich_address.0.into());
+    let result = match evm.inspect_commit(&mut salmonella_inspector) {
         Ok(result) => result,
-        Err(e) => return Err(anyhow!("[huffsandwich: EVM ERROR] backrun: {:?}", e)),
+        Err(e) => return Err(anyhow!("[huffsandwich: EVM ERROR] {}: {:?}", label, e)),
     };
-    match backrun_result {
-        ExecutionResult::Success { .. } => { /* continue */ }
+    match result {
+        ExecutionResult::Success { .. } => { /* continue operation */ }
         ExecutionResult::Revert { output, .. } => {
-            return Err(anyhow!("[huffsandwich: REVERT] backrun: {:?}", output));
+            return Err(anyhow!("[huffsandwich: REVERT] {}: {:?}", label, output));
         }
         ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow!("[huffsandwich: HALT] backrun: {:?}", reason))
+            return Err(anyhow!("[huffsandwich: HALT] {}: {:?}", label, reason));
         }
     };
     match salmonella_inspector.is_sandwich_safu() {
         IsSandwichSafu::Safu => { /* continue operation */ }
         IsSandwichSafu::NotSafu(not_safu_opcodes) => {
             return Err(anyhow!(
-                "[huffsandwich: BACKRUN_NOT_SAFU] bad_opcodes->{:?}",
+                "[huffsandwich: NOT_SAFU] {}: bad_opcodes->{:?}",
+                label,
                 not_safu_opcodes
             ))
         }
     }
 
-    let backrun_gas_used = backrun_result.gas_used();
+    Ok((tx_env, result.gas_used()))
+}
 
-    // *´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
-    // *                      GENERATE REPORTS                      */
-    // *.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
-    // caluclate revenue from balance change
-    let post_sandwich_bal =
-        get_erc20_balance(backrun_token_out, sandwich_address, next_block, &mut evm)?;
+/// Amounts in v2 payloads are worked out with each pool's configured fee, a pair that charges more
+/// fails its `K` check and reverts the whole tx, one that charges less leaves the sandwich
+/// under-sized
+/// Errors if `pool` doesn't pay what its fee prices (checked once per pool, a pair's fee is fixed)
+//
+// Arguments:
+// * `v2_fee_checks`: pools checked so far, true if their fee matched
+fn check_v2_fee(
+    pool: UniswapV2Pool,
+    base_token: BaseToken,
+    next_block: &BlockInfo,
+    v2_fee_checks: &DashMap<Address, bool>,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<()> {
+    if let Some(fee_matches) = v2_fee_checks.get(&pool.address).map(|m| *m) {
+        return match fee_matches {
+            true => Ok(()),
+            false => Err(anyhow!(
+                "[huffsandwich: FEE MISMATCH] pair {:?} doesn't charge its fee {}",
+                pool.address,
+                pool.fee
+            )),
+        };
+    }
 
-    let revenue = post_sandwich_bal
-        .checked_sub(sandwich_start_bal)
-        .unwrap_or_default();
+    // reads & probe swaps run at the target block's base fee
+    evm.env.tx.gas_price = next_block.base_fee_per_gas.into();
+    evm.env.tx.gas_priority_fee = None;
 
-    // filter only passing meat txs
-    let good_meats_only = ingredients
-        .get_meats_ref()
-        .iter()
-        .zip(is_meat_good.iter())
-        .filter(|&(_, &b)| b)
-        .map(|(s, _)| s.to_owned())
-        .collect();
+    let mut probe = V2SwapProbe::new(pool, base_token, evm)?;
+    // 0.1% of the pair's base token, large enough that rounding can't hide a fee difference
+    let amount_in = probe.reserve_in / 1000;
+    if amount_in.is_zero() {
+        return Ok(());
+    }
+    let priced_out = v2_get_amount_out(amount_in, pool, base_token.address, true, evm)?;
+    probe.credit(amount_in)?;
 
-    Ok(SandwichRecipe::new(
-        frontrun_tx_env,
-        frontrun_gas_used,
-        good_meats_only,
-        backrun_tx_env,
-        backrun_gas_used,
-        revenue,
-        *next_block,
+    // the pair pays up to its `K` limit, which is `priced_out` exactly when the fees agree
+    let pays_priced = probe.swap_succeeds(priced_out)?;
+    let fee_matches = pays_priced && !probe.swap_succeeds(priced_out + 1)?;
+    v2_fee_checks.insert(pool.address, fee_matches);
+    if fee_matches {
+        return Ok(());
+    }
+
+    // largest amount the pair pays, binary searched on the side of `priced_out` it sits on
+    let (mut low, mut high) = match pays_priced {
+        true => (
+            priced_out + 1,
+            probe.reserve_out.saturating_sub(U256::one()),
+        ),
+        false => (U256::zero(), priced_out - 1),
+    };
+    while low < high {
+        let mid = (low + high + 1) / 2;
+        match probe.swap_succeeds(mid)? {
+            true => low = mid,
+            false => high = mid - 1,
+        }
+    }
+
+    Err(anyhow!(
+        "[huffsandwich: FEE MISMATCH] pair {:?} pays {} for {} in, its fee {} prices {}",
+        pool.address,
+        low,
+        amount_in,
+        pool.fee,
+        priced_out
     ))
 }
 
+/// Swaps the base token into a v2 pair on a copy of an evm (nothing is committed to the original)
+struct V2SwapProbe {
+    evm: EVM<CacheDB<SharedBackend>>,
+    pool: UniswapV2Pool,
+    base_token: BaseToken,
+    /// Pair's reserves of the base token and the other token
+    reserve_in: U256,
+    reserve_out: U256,
+}
+
+impl V2SwapProbe {
+    fn new(
+        pool: UniswapV2Pool,
+        base_token: BaseToken,
+        evm: &mut EVM<CacheDB<SharedBackend>>,
+    ) -> Result<Self> {
+        let mut probe = EVM::new();
+        probe.env = evm.env.clone();
+        probe.database(
+            evm.db()
+                .ok_or_else(|| anyhow!("[huffsandwich: NoDatabase]"))?
+                .clone(),
+        );
+        let (reserve_in, reserve_out) =
+            v2_get_reserves(pool, base_token.address, true, &mut probe)?;
+
+        Ok(Self {
+            evm: probe,
+            pool,
+            base_token,
+            reserve_in,
+            reserve_out,
+        })
+    }
+
+    /// Credits the pair with `amount_in` of the base token, as if it was transferred in
+    fn credit(&mut self, amount_in: U256) -> Result<()> {
+        let balance = self.reserve_in + amount_in;
+        self.evm
+            .db()
+            .unwrap()
+            .insert_account_storage(
+                self.base_token.address.0.into(),
+                rU256::from_be_bytes(self.base_token.balance_storage_key(self.pool.address)),
+                balance.into(),
+            )
+            .map_err(|e| anyhow!("[huffsandwich: EVM ERROR] fee probe: {:?}", e))
+    }
+
+    /// True if the pair's `swap` pays out `amount_out` of the other token
+    fn swap_succeeds(&mut self, amount_out: U256) -> Result<bool> {
+        let pair = BaseContract::from(
+            parse_abi(&["function swap(uint256,uint256,address,bytes) external"]).unwrap(),
+        );
+        let amounts_out = match self.base_token.address == self.pool.token_a.min(self.pool.token_b)
+        {
+            true => (U256::zero(), amount_out),
+            false => (amount_out, U256::zero()),
+        };
+
+        self.evm.env.tx.transact_to = TransactTo::Call(self.pool.address.0.into());
+        self.evm.env.tx.caller = (*SUGAR_DADDY).0.into();
+        self.evm.env.tx.value = rU256::ZERO;
+        self.evm.env.tx.data = pair
+            .encode(
+                "swap",
+                (amounts_out.0, amounts_out.1, *SUGAR_DADDY, Bytes::new()),
+            )
+            .unwrap()
+            .0;
+        self.evm.env.tx.nonce = None;
+        self.evm.env.tx.gas_limit = 700000;
+        match self.evm.transact_ref() {
+            Ok(result) => Ok(result.result.is_success()),
+            Err(e) => Err(anyhow!("[huffsandwich: EVM ERROR] fee probe: {:?}", e)),
+        }
+    }
+}
+
 /// Get the balance of a token in an evm (account for tax)
 pub fn get_erc20_balance(
     token: Address,
@@ -327,6 +808,8 @@ pub fn get_erc20_balance(
     evm.env.tx.caller = (*SUGAR_DADDY).into(); // spoof addy with a lot of eth
     evm.env.tx.nonce = None;
     evm.env.tx.gas_price = block.base_fee_per_gas.into();
+    // meats may have left a priority fee above the base fee
+    evm.env.tx.gas_priority_fee = None;
     evm.env.tx.gas_limit = 700000;
     evm.env.tx.value = rU256::ZERO;
 
@@ -358,13 +841,13 @@ pub fn get_erc20_balance(
 
 // Find amount out from an amount in using the k=xy formula
 // note: reserve values taken from evm
-// note: assuming fee is set to 3% for all pools (not case irl)
+// note: fee taken from the pool (configured per factory in `PoolManager`)
 //
 // Arguments:
 // * `amount_in`: amount of token in
 // * `target_pool`: address of pool
-// * `token_in`: address of token in
-// * `token_out`: address of token out
+// * `base_token`: token that the sandwich starts and ends with
+// * `is_frontrun`: true if trading `base_token` for the other token
 // * `evm`: mutable reference to evm used for query
 //
 // Returns:
@@ -373,9 +856,110 @@ pub fn get_erc20_balance(
 pub fn v2_get_amount_out(
     amount_in: U256,
     target_pool: UniswapV2Pool,
+    base_token: Address,
     is_frontrun: bool,
     evm: &mut EVM<CacheDB<SharedBackend>>,
 ) -> Result<U256> {
+    let (reserve_in, reserve_out) = v2_get_reserves(target_pool, base_token, is_frontrun, evm)?;
+    let fee_multiplier = v2_fee_multiplier(target_pool)?;
+
+    let a_in_with_fee: U256 = amount_in * fee_multiplier;
+    let numerator: U256 = a_in_with_fee * reserve_out;
+    let denominator: U256 = reserve_in * V2_FEE_DENOMINATOR + a_in_with_fee;
+    let amount_out: U256 = numerator.checked_div(denominator).unwrap_or(U256::zero());
+
+    Ok(amount_out)
+}
+
+// Find amount in needed to receive an amount out using the k=xy formula
+// note: reserve values taken from evm
+// note: fee taken from the pool (configured per factory in `PoolManager`)
+//
+// Arguments:
+// * `amount_out`: amount of token out
+// * `target_pool`: address of pool
+// * `base_token`: token that the sandwich starts and ends with
+// * `is_frontrun`: true if trading `base_token` for the other token
+// * `evm`: mutable reference to evm used for query
+//
+// Returns:
+// Ok(U256): amount in
+// Err(SimulationError): if pool can't pay out `amount_out`
+pub fn v2_get_amount_in(
+    amount_out: U256,
+    target_pool: UniswapV2Pool,
+    base_token: Address,
+    is_frontrun: bool,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<U256> {
+    let (reserve_in, reserve_out) = v2_get_reserves(target_pool, base_token, is_frontrun, evm)?;
+    let fee_multiplier = v2_fee_multiplier(target_pool)?;
+
+    if amount_out >= reserve_out {
+        return Err(anyhow!(
+            "[get_amount_in_evm: INSUFFICIENT_LIQUIDITY] {:?}",
+            target_pool.address()
+        ));
+    }
+
+    let numerator: U256 = reserve_in * amount_out * V2_FEE_DENOMINATOR;
+    let denominator: U256 = (reserve_out - amount_out) * fee_multiplier;
+    let amount_in: U256 = numerator / denominator + 1;
+
+    Ok(amount_in)
+}
+
+// Value `amount` of a base token in weth at the spot price of its weth pair
+// note: reserve values taken from evm
+//
+// Arguments:
+// * `amount`: amount of base token
+// * `quote_pool`: v2 pool pairing the base token with weth
+// * `evm`: mutable reference to evm used for query
+pub fn v2_value_in_weth(
+    amount: U256,
+    quote_pool: UniswapV2Pool,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<U256> {
+    let (reserve_in, reserve_out) = v2_get_reserves(quote_pool, *WETH_ADDRESS, false, evm)?;
+    Ok((amount * reserve_out)
+        .checked_div(reserve_in)
+        .unwrap_or_default())
+}
+
+// v2 pool fees are stored in units of 1e-5 (e.g. 300 for 0.3%)
+pub(crate) const V2_FEE_DENOMINATOR: u64 = 100_000;
+
+// Multiplier applied to a v2 pool's amount in after its fee (out of `V2_FEE_DENOMINATOR`)
+//
+// Arguments:
+// * `target_pool`: pool whose fee to use
+pub(crate) fn v2_fee_multiplier(target_pool: UniswapV2Pool) -> Result<U256> {
+    let fee = u64::from(target_pool.fee);
+    if fee >= V2_FEE_DENOMINATOR {
+        return Err(anyhow!(
+            "[v2_fee_multiplier: BAD FEE] {:?} has fee {}",
+            target_pool.address(),
+            fee
+        ));
+    }
+
+    Ok(U256::from(V2_FEE_DENOMINATOR - fee))
+}
+
+// Get (reserve_in, reserve_out) of a v2 pool from the evm
+//
+// Arguments:
+// * `target_pool`: address of pool
+// * `base_token`: token that the sandwich starts and ends with
+// * `is_frontrun`: true if trading `base_token` for the other token
+// * `evm`: mutable reference to evm used for query
+pub(crate) fn v2_get_reserves(
+    target_pool: UniswapV2Pool,
+    base_token: Address,
+    is_frontrun: bool,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<(U256, U256)> {
     // get reserves
     evm.env.tx.transact_to = TransactTo::Call(target_pool.address().0.into());
     evm.env.tx.caller = (*SUGAR_DADDY).0.into(); // spoof weth address for its ether
@@ -414,15 +998,15 @@ pub fn v2_get_amount_out(
 
     let other_token = [target_pool.token_a, target_pool.token_b]
         .into_iter()
-        .find(|&t| t != *WETH_ADDRESS)
+        .find(|&t| t != base_token)
         .unwrap();
 
     let (input_token, output_token) = if is_frontrun {
-        // if frontrun we trade WETH -> TOKEN
-        (*WETH_ADDRESS, other_token)
+        // if frontrun we trade BASE -> TOKEN
+        (base_token, other_token)
     } else {
-        // if backrun we trade TOKEN -> WETH
-        (other_token, *WETH_ADDRESS)
+        // if backrun we trade TOKEN -> BASE
+        (other_token, base_token)
     };
 
     let (reserve_in, reserve_out) = match input_token < output_token {
@@ -430,12 +1014,7 @@ pub fn v2_get_amount_out(
         false => (reserves_1, reserves_0),
     };
 
-    let a_in_with_fee: U256 = amount_in * 997;
-    let numerator: U256 = a_in_with_fee * reserve_out;
-    let denominator: U256 = reserve_in * 1000 + a_in_with_fee;
-    let amount_out: U256 = numerator.checked_div(denominator).unwrap_or(U256::zero());
-
-    Ok(amount_out)
+    Ok((reserve_in, reserve_out))
 }
 
 #[cfg(feature = "debug")]
@@ -443,6 +1022,7 @@ fn inject_huff_sandwich(
     db: &mut CacheDB<SharedBackend>,
     huff_sandwich_addy: foundry_evm::executor::B160,
     searcher: foundry_evm::executor::B160,
+    base_token: crate::base_token::BaseToken,
     sandwich_start_bal: U256,
 ) {
     // compile huff contract
@@ -474,8 +1054,9 @@ fn inject_huff_sandwich(
     let huff_sandwich_code = <Bytes as std::str::FromStr>::from_str(huff_sandwich_code).unwrap();
 
     //// insert huff sandwich bytecode
+    // eth balance is used to pay coinbase bribes
     let huff_sandwich_info = foundry_evm::revm::primitives::AccountInfo::new(
-        rU256::ZERO,
+        crate::simulator::eth_to_wei(1),
         0,
         foundry_evm::executor::Bytecode::new_raw(huff_sandwich_code.0),
     );
@@ -490,15 +1071,12 @@ fn inject_huff_sandwich(
     );
     db.insert_account_info(searcher, searcher_info);
 
-    // fund huff sandwich with 200 weth
-    let slot = foundry_evm::revm::primitives::keccak256(&abi::encode(&[
-        abi::Token::Address(huff_sandwich_addy.0.into()),
-        abi::Token::Uint(U256::from(3)),
-    ]));
+    // fund huff sandwich with its base token inventory
+    let slot = base_token.balance_storage_key(huff_sandwich_addy.0.into());
 
     db.insert_account_storage(
-        (*WETH_ADDRESS).into(),
-        slot.into(),
+        base_token.address.into(),
+        rU256::from_be_bytes(slot),
         sandwich_start_bal.into(),
     )
     .unwrap();
@@ -1,278 +1,545 @@
 use anyhow::{anyhow, Result};
-use cfmms::pool::Pool::{UniswapV2, UniswapV3};
-use ethers::{abi, types::U256};
+use cfmms::pool::{
+    Pool::{UniswapV2, UniswapV3},
+    UniswapV2Pool,
+};
+use ethers::types::{Address, Bytes, U256};
 use foundry_evm::{
-    executor::{fork::SharedBackend, Bytecode, ExecutionResult, Output, TransactTo},
+    executor::{fork::SharedBackend, Bytecode, ExecutionResult, Output, TransactTo, TxEnv},
     revm::{
         db::CacheDB,
-        primitives::{keccak256, AccountInfo, Address as rAddress, U256 as rU256},
-        EVM,
+        primitives::{AccountInfo, U256 as rU256},
+        Database, EVM,
     },
 };
+use log::debug;
+use std::collections::HashMap;
 
 use crate::{
-    constants::{
-        LIL_ROUTER_ADDRESS, LIL_ROUTER_CODE, LIL_ROUTER_CONTROLLER, WETH_ADDRESS, WETH_FUND_AMT,
-    },
-    tx_utils::lil_router_interface::{
-        build_swap_v2_data, build_swap_v3_data, decode_swap_v2_result, decode_swap_v3_result,
+    base_token::BaseToken,
+    block_env::ChainConfig,
+    constants::{LIL_ROUTER_ADDRESS, LIL_ROUTER_CODE, LIL_ROUTER_CONTROLLER, WETH_FUND_AMT},
+    tx_utils::{
+        lil_router_interface::{
+            build_swap_v2_data, build_swap_v3_data, decode_swap_v2_result, decode_swap_v3_result,
+        },
+        meat::meat_to_tx_env,
     },
     types::{BlockInfo, RawIngredients},
 };
 
-use super::{eth_to_wei, setup_block_state};
+use super::{
+    eth_to_wei,
+    huff_sandwich::{get_erc20_balance, v2_get_amount_in},
+    search_strategy::{encodable_input, Objective, SearchStrategies, SearchStrategy},
+    setup_block_state,
+    slippage_bound::{find_slippage_bound, SlippageBound},
+    v2_closed_form::find_closed_form_input,
+};
+
+/// How many times each leg is re-optimized when sandwiching several pools
+const JOINT_SEARCH_ROUNDS: usize = 2;
+
+/// Base token (units of 1e18) the lil router holds before each sandwich, revenue is measured
+/// against it
+const LIL_ROUTER_BASE_FUND: u128 = 200;
+
+/// Frontrun input of every leg and the victim's slippage bound found for it
+pub struct OptimalInputs {
+    pub inputs: Vec<U256>,
+    pub slippage_bounds: Vec<Option<SlippageBound>>,
+}
 
-// Juiced implementation of https://research.ijcaonline.org/volume65/number14/pxc3886165.pdf
-// splits range in more intervals, search intervals concurrently, compare, repeat till termination
-pub async fn find_optimal_input(
+/// Finds the optimal frontrun input of every leg
+/// Single leg sandwiches on plain v2 pairs are solved in closed form, anything else (or a closed
+/// form that simulation doesn't confirm) is searched
+/// Searches stop at the largest frontrun the victim's slippage allows when it can be decoded
+/// Legs of multi pool sandwiches are optimized jointly (coordinate search: each leg is searched
+/// with the other legs' inputs fixed, repeated for a few rounds)
+pub async fn find_optimal_inputs(
     ingredients: &RawIngredients,
     target_block: &BlockInfo,
-    weth_inventory: U256,
+    inventory: U256,
+    search_strategies: &SearchStrategies,
+    chain: &ChainConfig,
     shared_backend: SharedBackend,
-) -> Result<U256> {
-    //
-    //            [EXAMPLE WITH 10 BOUND INTERVALS]
-    //
-    //     (first)              (mid)               (last)
-    //        ▼                   ▼                   ▼
-    //        +---+---+---+---+---+---+---+---+---+---+
-    //        |   |   |   |   |   |   |   |   |   |   |
-    //        +---+---+---+---+---+---+---+---+---+---+
-    //        ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲
-    //        0   1   2   3   4   5   6   7   8   9   X
-    //
-    //  * [0, X] = search range
-    //  * Find revenue at each interval
-    //  * Find index of interval with highest revenue
-    //  * Search again with bounds set to adjacent index of highest
-
-    // setup values for search termination
-    let base = U256::from(1000000u64);
-    let tolerance = U256::from(1u64);
-
-    let mut lower_bound = U256::zero();
-    let mut upper_bound = weth_inventory;
-
-    let tolerance = (tolerance * ((upper_bound + lower_bound) / rU256::from(2))) / base;
-
-    // initialize variables for search
-    let l_interval_lower = |i: usize, intervals: &Vec<U256>| intervals[i - 1].clone() + 1;
-    let r_interval_upper = |i: usize, intervals: &Vec<U256>| {
-        intervals[i + 1]
-            .clone()
-            .checked_sub(1.into())
-            .ok_or(anyhow!("r_interval - 1 underflowed"))
-    };
-    let should_loop_terminate = |lower_bound: U256, upper_bound: U256| -> bool {
-        let search_range = match upper_bound.checked_sub(lower_bound) {
-            Some(range) => range,
-            None => return true,
-        };
-        // produces negative result
-        if lower_bound > upper_bound {
-            return true;
-        }
-        // tolerance condition not met
-        if search_range < tolerance {
-            return true;
-        }
-        false
-    };
-    let mut highest_sandwich_input = U256::zero();
-    let number_of_intervals = 15;
-    let mut counter = 0;
-
-    // continue search until termination condition is met (no point seraching down to closest wei)
-    loop {
-        counter += 1;
-        if should_loop_terminate(lower_bound, upper_bound) {
-            break;
+) -> Result<OptimalInputs> {
+    let leg_count = ingredients.get_legs().len();
+    let mut snapshot = SimulationSnapshot::new(ingredients, target_block, chain, shared_backend);
+
+    // bounds are best effort, a leg without one is searched up to its inventory
+    let slippage_bounds: Vec<Option<SlippageBound>> = ingredients
+        .get_legs()
+        .iter()
+        .map(|leg| {
+            find_slippage_bound(ingredients, leg, inventory, &snapshot).unwrap_or_else(|e| {
+                debug!("[search] no slippage bound: {:?}", e);
+                None
+            })
+        })
+        .collect();
+
+    if leg_count == 1 {
+        if let Some(input) = find_closed_form_input(ingredients, inventory, &snapshot).await? {
+            return Ok(OptimalInputs {
+                inputs: vec![input],
+                slippage_bounds,
+            });
         }
+    }
 
-        // split search range into intervals
-        let mut intervals = Vec::new();
-        for i in 0..=number_of_intervals {
-            let diff = upper_bound
-                .checked_sub(lower_bound)
-                .ok_or(anyhow!("upper_bound - lower_bound resulted in underflow"))?;
+    // every search simulation runs on a clone of the warmed snapshot
+    let probe_input = inventory / (2 * leg_count);
+    snapshot.warm(ingredients, vec![probe_input; leg_count]);
 
-            let fraction = diff * i;
-            let divisor = U256::from(number_of_intervals);
-            let interval = lower_bound + (fraction / divisor);
+    let mut inputs = vec![U256::zero(); leg_count];
 
-            intervals.push(interval);
-        }
+    let rounds = if leg_count == 1 {
+        1
+    } else {
+        JOINT_SEARCH_ROUNDS
+    };
+
+    for _ in 0..rounds {
+        for leg in 0..leg_count {
+            // all legs are funded from the same base token inventory
+            let other_inputs = inputs
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != leg)
+                .fold(U256::zero(), |acc, (_, input)| acc + input);
+            let mut leg_inventory = inventory.saturating_sub(other_inputs);
+            if let Some(bound) = slippage_bounds[leg] {
+                leg_inventory = leg_inventory.min(bound.max_frontrun_in);
+            }
 
-        // calculate revenue at each interval concurrently
-        let mut revenues = Vec::new();
-        for bound in &intervals {
-            let sim = tokio::task::spawn(evaluate_sandwich_revenue(
-                *bound,
-                target_block.clone(),
-                shared_backend.clone(),
-                ingredients.clone(),
-            ));
-            revenues.push(sim);
+            // victim reverts on any frontrun
+            if leg_inventory.is_zero() {
+                inputs[leg] = U256::zero();
+                continue;
+            }
+
+            inputs[leg] = find_optimal_input(
+                ingredients,
+                leg_inventory,
+                &inputs,
+                leg,
+                search_strategies.get(&ingredients.get_legs()[leg]),
+                &snapshot,
+            )
+            .await?;
         }
+    }
 
-        let revenues = futures::future::join_all(revenues).await;
+    Ok(OptimalInputs {
+        inputs,
+        slippage_bounds,
+    })
+}
 
-        let revenues = revenues
-            .into_iter()
-            .map(|r| r.unwrap().unwrap_or_default())
-            .collect::<Vec<_>>();
+/// Searches the frontrun input of `leg` with the other legs' inputs fixed
+/// Logs simulations used & convergence of every search
+//
+// Arguments:
+// `inputs`: frontrun inputs of every leg (only `inputs[leg]` is searched, others stay fixed)
+// `leg`: index of the leg to search
+pub(crate) async fn find_optimal_input(
+    ingredients: &RawIngredients,
+    inventory: U256,
+    inputs: &[U256],
+    leg: usize,
+    search_strategy: &dyn SearchStrategy,
+    snapshot: &SimulationSnapshot,
+) -> Result<U256> {
+    let mut objective = Objective::new(ingredients, inputs, leg, snapshot);
+    let report = search_strategy.search(&mut objective, inventory).await?;
+
+    debug!(
+        "[search] pool: {:?}, input: {}, revenue: {}, simulations: {}, rounds: {}, converged: {}",
+        ingredients.get_legs()[leg].target_pool.address(),
+        report.input,
+        report.revenue,
+        objective.get_simulations(),
+        report.rounds,
+        report.converged
+    );
 
-        // find interval that produces highest revenue
-        let (highest_revenue_index, _highest_revenue) = revenues
-            .iter()
-            .enumerate()
-            .max_by(|(_, a), (_, b)| a.cmp(&b))
-            .unwrap();
+    Ok(encodable_input(report.input))
+}
 
-        highest_sandwich_input = intervals[highest_revenue_index];
+/// Lil router fork shared by every simulation of an opportunity, block setup and the accounts &
+/// slots the sandwich touches are loaded once and each simulation runs on a clone
+#[derive(Clone)]
+pub struct SimulationSnapshot {
+    db: CacheDB<SharedBackend>,
+    next_block: BlockInfo,
+    chain: ChainConfig,
+}
 
-        // enhancement: find better way to increase finding opps incase of all rev=0
-        if revenues[highest_revenue_index] == U256::zero() {
-            // most likely there is no sandwich possibility
-            if counter == 10 {
-                return Ok(U256::zero());
-            }
-            // no revenue found, most likely small optimal so decrease range
-            upper_bound = intervals[intervals.len() / 3]
-                .checked_sub(1.into())
-                .ok_or(anyhow!("intervals[intervals.len()/3] - 1 underflowed"))?;
-            continue;
+impl SimulationSnapshot {
+    /// Fork with the lil router injected and funded with the sandwich's base token
+    pub(crate) fn new(
+        ingredients: &RawIngredients,
+        next_block: &BlockInfo,
+        chain: &ChainConfig,
+        shared_backend: SharedBackend,
+    ) -> Self {
+        let mut db = CacheDB::new(shared_backend);
+        inject_lil_router_code(&mut db, ingredients.get_start_end_token());
+
+        Self {
+            db,
+            next_block: next_block.clone(),
+            chain: *chain,
         }
+    }
 
-        // if highest revenue is produced at last interval (upper bound stays fixed)
-        if highest_revenue_index == intervals.len() - 1 {
-            lower_bound = l_interval_lower(highest_revenue_index, &intervals);
-            continue;
-        }
+    /// Runs a sandwich with `frontrun_ins` on a clone to find every account & slot it touches
+    /// (pools, tokens, victim & whatever the victim calls into), then loads their pre sandwich
+    /// state into the snapshot so that later clones don't fetch them from the backend again
+    pub(crate) fn warm(&mut self, ingredients: &RawIngredients, frontrun_ins: Vec<U256>) {
+        let mut evm = self.clone().into_evm();
+        let _ = simulate_sandwich(&mut evm, frontrun_ins, &self.next_block, ingredients);
 
-        // if highest revenue is produced at first interval (lower bound stays fixed)
-        if highest_revenue_index == 0 {
-            upper_bound = r_interval_upper(highest_revenue_index, &intervals)?;
-            continue;
+        let touched = match evm.db() {
+            Some(db) => std::mem::take(&mut db.accounts),
+            None => return,
+        };
+        for (address, account) in touched {
+            let _ = self.db.basic(address);
+            for slot in account.storage.keys() {
+                let _ = self.db.storage(address, *slot);
+            }
         }
-
-        // set bounds to intervals adjacent to highest revenue index and search again
-        lower_bound = l_interval_lower(highest_revenue_index, &intervals);
-        upper_bound = r_interval_upper(highest_revenue_index, &intervals)?;
     }
 
-    Ok(highest_sandwich_input)
+    /// Evm on top of (a clone of) the snapshot, with block state set up
+    pub(crate) fn into_evm(self) -> EVM<CacheDB<SharedBackend>> {
+        let mut evm = EVM::new();
+        evm.database(self.db);
+        setup_block_state(&mut evm, &self.next_block, &self.chain);
+        evm
+    }
 }
 
-async fn evaluate_sandwich_revenue(
-    frontrun_in: U256,
-    next_block: BlockInfo,
-    shared_backend: SharedBackend,
+pub(super) async fn evaluate_sandwich_revenue(
+    frontrun_ins: Vec<U256>,
+    snapshot: SimulationSnapshot,
     ingredients: RawIngredients,
 ) -> Result<U256> {
-    let mut fork_db = CacheDB::new(shared_backend);
-    inject_lil_router_code(&mut fork_db);
+    let next_block = snapshot.next_block.clone();
+    let mut evm = snapshot.into_evm();
+    simulate_sandwich(&mut evm, frontrun_ins, &next_block, &ingredients)
+}
+
+/// Runs the frontrun/s, meats and backrun/s through the lil router on top of `evm`
+/// Returns the revenue of the sandwich
+fn simulate_sandwich(
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+    frontrun_ins: Vec<U256>,
+    next_block: &BlockInfo,
+    ingredients: &RawIngredients,
+) -> Result<U256> {
+    // legs without input are left out of the sandwich
+    let legs: Vec<_> = ingredients
+        .get_legs()
+        .iter()
+        .zip(frontrun_ins)
+        .filter(|(_, frontrun_in)| !frontrun_in.is_zero())
+        .collect();
+
+    if legs.is_empty() {
+        return Ok(U256::zero());
+    }
 
-    let mut evm = EVM::new();
-    evm.database(fork_db);
-    setup_block_state(&mut evm, &next_block);
+    // router holds exactly what inventory legs sell
+    for (leg, frontrun_in) in &legs {
+        if let Some(inventory_token) = leg.inventory_token {
+            fund_lil_router(evm.db().unwrap(), inventory_token, *frontrun_in);
+        }
+    }
 
     /*´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
-    /*                    FRONTRUN TRANSACTION                    */
+    /*                   FRONTRUN TRANSACTION/s                   */
     /*.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
-    let frontrun_data = match ingredients.get_target_pool() {
-        UniswapV2(pool) => build_swap_v2_data(frontrun_in, pool, true),
-        UniswapV3(pool) => build_swap_v3_data(frontrun_in.as_u128().into(), pool, true),
-    };
-
-    evm.env.tx.caller = *LIL_ROUTER_CONTROLLER;
-    evm.env.tx.transact_to = TransactTo::Call(*LIL_ROUTER_ADDRESS);
-    evm.env.tx.data = frontrun_data.0;
-    evm.env.tx.gas_limit = 700000;
-    evm.env.tx.gas_price = next_block.base_fee_per_gas.into();
-    evm.env.tx.value = rU256::ZERO;
-
-    let result = match evm.transact_commit() {
-        Ok(result) => result,
-        Err(e) => return Err(anyhow!("[lilRouter: EVM ERROR] frontrun: {:?}", e)),
-    };
-    let output = match result {
-        ExecutionResult::Success { output, .. } => match output {
-            Output::Call(o) => o,
-            Output::Create(o, _) => o,
-        },
-        ExecutionResult::Revert { output, .. } => {
-            return Err(anyhow!("[lilRouter: REVERT] frontrun: {:?}", output))
+    // router's balance of each intermediary token, legs sharing a token only backrun what they bought
+    let mut token_balances: HashMap<Address, U256> = HashMap::new();
+    let mut backrun_ins = vec![];
+
+    for (leg, frontrun_in) in &legs {
+        let base_token = leg.start_end_token.address;
+
+        // `token->weth` victim, sell inventory into the target pool
+        if leg.inventory_token.is_some() {
+            let target_pool = match leg.target_pool {
+                UniswapV2(p) => p,
+                UniswapV3(_) => {
+                    return Err(anyhow!(
+                        "[lilRouter: UNSUPPORTED] inventory legs can only target v2 pools"
+                    ))
+                }
+            };
+
+            lil_router_swap_v2(
+                evm,
+                *frontrun_in,
+                target_pool,
+                base_token,
+                false,
+                next_block,
+                "frontrun",
+            )?;
+
+            // backrun buys the same amount back
+            backrun_ins.push(*frontrun_in);
+            continue;
         }
-        ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow!("[lilRouter: HALT] frontrun: {:?}", reason))
+
+        // `token->weth` victim, buying on the flash pool and selling on the target pool has the
+        // same outcome as the sandwich contract's flash swap
+        if let Some(flash_pool) = leg.flash_pool {
+            let target_pool = match leg.target_pool {
+                UniswapV2(p) => p,
+                UniswapV3(_) => {
+                    return Err(anyhow!(
+                        "[lilRouter: UNSUPPORTED] flash swap legs can only target v2 pools"
+                    ))
+                }
+            };
+
+            let balance_before = get_erc20_balance(
+                leg.intermediary_token,
+                (*LIL_ROUTER_ADDRESS).into(),
+                next_block,
+                evm,
+            )?;
+            let (_, balance_after) = lil_router_swap_v2(
+                evm,
+                *frontrun_in,
+                flash_pool,
+                base_token,
+                true,
+                next_block,
+                "frontrun",
+            )?;
+            let borrow_amount = balance_after.saturating_sub(balance_before);
+            lil_router_swap_v2(
+                evm,
+                borrow_amount,
+                target_pool,
+                base_token,
+                false,
+                next_block,
+                "frontrun",
+            )?;
+
+            // backrun borrows the same amount back from the target pool
+            backrun_ins.push(borrow_amount);
+            continue;
         }
-    };
-    let (_frontrun_out, backrun_in) = match ingredients.get_target_pool() {
-        UniswapV2(_) => match decode_swap_v2_result(output.into()) {
-            Ok(output) => output,
-            Err(e) => {
-                return Err(anyhow!(
-                    "[lilRouter: FailedToDecodeOutput] frontrun: {:?}",
-                    e
-                ))
+
+        let frontrun_data = match leg.target_pool {
+            UniswapV2(pool) => build_swap_v2_data(*frontrun_in, pool, base_token, true),
+            UniswapV3(pool) => {
+                build_swap_v3_data(frontrun_in.as_u128().into(), pool, base_token, true)
             }
-        },
-        UniswapV3(_) => match decode_swap_v3_result(output.into()) {
-            Ok(output) => output,
-            Err(e) => return Err(anyhow!("lilRouter: FailedToDecodeOutput: {:?}", e)),
-        },
-    };
+        };
+
+        evm.env.tx = lil_router_tx_env(frontrun_data, next_block);
+
+        let result = match evm.transact_commit() {
+            Ok(result) => result,
+            Err(e) => return Err(anyhow!("[lilRouter: EVM ERROR] frontrun: {:?}", e)),
+        };
+        let output = match result {
+            ExecutionResult::Success { output, .. } => match output {
+                Output::Call(o) => o,
+                Output::Create(o, _) => o,
+            },
+            ExecutionResult::Revert { output, .. } => {
+                return Err(anyhow!("[lilRouter: REVERT] frontrun: {:?}", output))
+            }
+            ExecutionResult::Halt { reason, .. } => {
+                return Err(anyhow!("[lilRouter: HALT] frontrun: {:?}", reason))
+            }
+        };
+        let (_frontrun_out, after_balance) = match leg.target_pool {
+            UniswapV2(_) => match decode_swap_v2_result(output.into()) {
+                Ok(output) => output,
+                Err(e) => {
+                    return Err(anyhow!(
+                        "[lilRouter: FailedToDecodeOutput] frontrun: {:?}",
+                        e
+                    ))
+                }
+            },
+            UniswapV3(_) => match decode_swap_v3_result(output.into()) {
+                Ok(output) => output,
+                Err(e) => return Err(anyhow!("lilRouter: FailedToDecodeOutput: {:?}", e)),
+            },
+        };
+
+        let before_balance = token_balances
+            .insert(leg.intermediary_token, after_balance)
+            .unwrap_or_default();
+        backrun_ins.push(after_balance.saturating_sub(before_balance));
+    }
 
     /*´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
     /*                     MEAT TRANSACTION/s                     */
     /*.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
     for meat in ingredients.get_meats_ref().iter() {
-        evm.env.tx.caller = rAddress::from_slice(&meat.from.0);
-        evm.env.tx.transact_to =
-            TransactTo::Call(rAddress::from_slice(&meat.to.unwrap_or_default().0));
-        evm.env.tx.data = meat.input.0.clone();
-        evm.env.tx.value = meat.value.into();
-        evm.env.tx.chain_id = meat.chain_id.map(|id| id.as_u64());
-        // evm.env.tx.nonce = Some(meat.nonce.as_u64()); /** ignore nonce check for now **/
-        evm.env.tx.gas_limit = meat.gas.as_u64();
-        match meat.transaction_type {
-            Some(ethers::types::U64([0])) => {
-                // legacy tx
-                evm.env.tx.gas_price = meat.gas_price.unwrap_or_default().into();
-            }
-            Some(_) => {
-                // type 2 tx
-                evm.env.tx.gas_priority_fee = meat.max_priority_fee_per_gas.map(|mpf| mpf.into());
-                evm.env.tx.gas_price = meat.max_fee_per_gas.unwrap_or_default().into();
-            }
-            None => {
-                // legacy tx
-                evm.env.tx.gas_price = meat.gas_price.unwrap_or_default().into();
-            }
-        }
+        evm.env.tx = meat_to_tx_env(meat)?;
 
-        let _res = evm.transact_commit();
+        // reverting meats are fine (they are dropped from the bundle), invalid ones aren't
+        if let Err(e) = evm.transact_commit() {
+            return Err(anyhow!("[lilRouter: EVM ERROR] meat: {:?}", e));
+        }
     }
 
     /*´:°•.°+.*•´.*:˚.°*.˚•´.°:°•.°•.*•´.*:˚.°*.˚•´.°:°•.°+.*•´.*:*/
-    /*                    BACKRUN TRANSACTION                     */
+    /*                    BACKRUN TRANSACTION/s                   */
     /*.•°:°.´+˚.*°.˚:*.´•*.+°.•°:´*.´•*.•°.•°:°.´:•˚°.*°.˚:*.´+°.•*/
-    let backrun_data = match ingredients.get_target_pool() {
-        UniswapV2(pool) => build_swap_v2_data(backrun_in, pool, false),
-        UniswapV3(pool) => build_swap_v3_data(backrun_in.as_u128().into(), pool, false),
-    };
+    let mut post_sandwich_balance = U256::zero();
+
+    for ((leg, _), backrun_in) in legs.iter().zip(backrun_ins) {
+        let base_token = leg.start_end_token.address;
+
+        // buy back what was sold from inventory, leftover weth is the revenue
+        if leg.inventory_token.is_some() {
+            let target_pool = match leg.target_pool {
+                UniswapV2(p) => p,
+                UniswapV3(_) => {
+                    return Err(anyhow!(
+                        "[lilRouter: UNSUPPORTED] inventory legs can only target v2 pools"
+                    ))
+                }
+            };
+
+            let weth_in = v2_get_amount_in(backrun_in, target_pool, base_token, true, evm)?;
+            lil_router_swap_v2(
+                evm,
+                weth_in,
+                target_pool,
+                base_token,
+                true,
+                next_block,
+                "backrun",
+            )?;
+            post_sandwich_balance =
+                get_erc20_balance(base_token, (*LIL_ROUTER_ADDRESS).into(), next_block, evm)?;
+            continue;
+        }
+
+        // buy back what was sold in the frontrun on the target pool and sell it on the flash pool
+        if let Some(flash_pool) = leg.flash_pool {
+            let target_pool = match leg.target_pool {
+                UniswapV2(p) => p,
+                UniswapV3(_) => {
+                    return Err(anyhow!(
+                        "[lilRouter: UNSUPPORTED] flash swap legs can only target v2 pools"
+                    ))
+                }
+            };
+
+            let weth_in = v2_get_amount_in(backrun_in, target_pool, base_token, true, evm)?;
+            let balance_before = get_erc20_balance(
+                leg.intermediary_token,
+                (*LIL_ROUTER_ADDRESS).into(),
+                next_block,
+                evm,
+            )?;
+            let (_, balance_after) = lil_router_swap_v2(
+                evm,
+                weth_in,
+                target_pool,
+                base_token,
+                true,
+                next_block,
+                "backrun",
+            )?;
+            let (_, after_balance) = lil_router_swap_v2(
+                evm,
+                balance_after.saturating_sub(balance_before),
+                flash_pool,
+                base_token,
+                false,
+                next_block,
+                "backrun",
+            )?;
+            post_sandwich_balance = after_balance;
+            continue;
+        }
 
-    evm.env.tx.caller = *LIL_ROUTER_CONTROLLER;
-    evm.env.tx.transact_to = TransactTo::Call(*LIL_ROUTER_ADDRESS);
-    evm.env.tx.data = backrun_data.0;
-    evm.env.tx.gas_limit = 700000;
-    evm.env.tx.gas_price = next_block.base_fee_per_gas.into();
-    evm.env.tx.value = rU256::ZERO;
+        let backrun_data = match leg.target_pool {
+            UniswapV2(pool) => build_swap_v2_data(backrun_in, pool, base_token, false),
+            UniswapV3(pool) => {
+                build_swap_v3_data(backrun_in.as_u128().into(), pool, base_token, false)
+            }
+        };
+
+        evm.env.tx = lil_router_tx_env(backrun_data, next_block);
+
+        let result = match evm.transact_commit() {
+            Ok(result) => result,
+            Err(e) => return Err(anyhow!("[lilRouter: EVM ERROR] backrun: {:?}", e)),
+        };
+        let output = match result {
+            ExecutionResult::Success { output, .. } => match output {
+                Output::Call(o) => o,
+                Output::Create(o, _) => o,
+            },
+            ExecutionResult::Revert { output, .. } => {
+                return Err(anyhow!("[lilRouter: REVERT] backrun: {:?}", output))
+            }
+            ExecutionResult::Halt { reason, .. } => {
+                return Err(anyhow!("[lilRouter: HALT] backrun: {:?}", reason))
+            }
+        };
+        // every leg ends in weth, so the last backrun's balance accounts for all legs
+        let (_backrun_out, after_balance) = match leg.target_pool {
+            UniswapV2(_) => match decode_swap_v2_result(output.into()) {
+                Ok(output) => output,
+                Err(e) => return Err(anyhow!("[lilRouter: FailedToDecodeOutput] {:?}", e)),
+            },
+            UniswapV3(_) => match decode_swap_v3_result(output.into()) {
+                Ok(output) => output,
+                Err(e) => return Err(anyhow!("[lilRouter: FailedToDecodeOutput] {:?}", e)),
+            },
+        };
+        post_sandwich_balance = after_balance;
+    }
+
+    let revenue = post_sandwich_balance
+        .checked_sub(eth_to_wei(LIL_ROUTER_BASE_FUND).into())
+        .unwrap_or_default();
+
+    Ok(revenue)
+}
+
+/// Swap `amount_in` on a v2 pool through the lil router
+/// Returns (amount out, router's balance of the output token after the swap)
+//
+// Arguments:
+// * `is_frontrun`: true if trading `base_token` for the other token
+// * `label`: which part of the sandwich the swap is (used in errors)
+fn lil_router_swap_v2(
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+    amount_in: U256,
+    pool: UniswapV2Pool,
+    base_token: Address,
+    is_frontrun: bool,
+    next_block: &BlockInfo,
+    label: &str,
+) -> Result<(U256, U256)> {
+    evm.env.tx = lil_router_tx_env(
+        build_swap_v2_data(amount_in, pool, base_token, is_frontrun),
+        next_block,
+    );
 
     let result = match evm.transact_commit() {
         Ok(result) => result,
-        Err(e) => return Err(anyhow!("[lilRouter: EVM ERROR] backrun: {:?}", e)),
+        Err(e) => return Err(anyhow!("[lilRouter: EVM ERROR] {}: {:?}", label, e)),
     };
     let output = match result {
         ExecutionResult::Success { output, .. } => match output {
@@ -280,32 +547,19 @@ async fn evaluate_sandwich_revenue(
             Output::Create(o, _) => o,
         },
         ExecutionResult::Revert { output, .. } => {
-            return Err(anyhow!("[lilRouter: REVERT] backrun: {:?}", output))
+            return Err(anyhow!("[lilRouter: REVERT] {}: {:?}", label, output))
         }
         ExecutionResult::Halt { reason, .. } => {
-            return Err(anyhow!("[lilRouter: HALT] backrun: {:?}", reason))
+            return Err(anyhow!("[lilRouter: HALT] {}: {:?}", label, reason))
         }
     };
-    let (_backrun_out, post_sandwich_balance) = match ingredients.get_target_pool() {
-        UniswapV2(_) => match decode_swap_v2_result(output.into()) {
-            Ok(output) => output,
-            Err(e) => return Err(anyhow!("[lilRouter: FailedToDecodeOutput] {:?}", e)),
-        },
-        UniswapV3(_) => match decode_swap_v3_result(output.into()) {
-            Ok(output) => output,
-            Err(e) => return Err(anyhow!("[lilRouter: FailedToDecodeOutput] {:?}", e)),
-        },
-    };
 
-    let revenue = post_sandwich_balance
-        .checked_sub((*WETH_FUND_AMT).into())
-        .unwrap_or_default();
-
-    Ok(revenue)
+    decode_swap_v2_result(output.into())
+        .map_err(|e| anyhow!("[lilRouter: FailedToDecodeOutput] {}: {:?}", label, e))
 }
 
 /// Inserts custom minimal router contract into evm instance for simulations
-fn inject_lil_router_code(db: &mut CacheDB<SharedBackend>) {
+fn inject_lil_router_code(db: &mut CacheDB<SharedBackend>, base_token: BaseToken) {
     // insert lilRouter bytecode
     let lil_router_info = AccountInfo::new(
         rU256::ZERO,
@@ -318,12 +572,34 @@ fn inject_lil_router_code(db: &mut CacheDB<SharedBackend>) {
     let controller_info = AccountInfo::new(*WETH_FUND_AMT, 0, Bytecode::default());
     db.insert_account_info(*LIL_ROUTER_CONTROLLER, controller_info);
 
-    // fund lilRouter with 200 weth
-    let slot = keccak256(&abi::encode(&[
-        abi::Token::Address((*LIL_ROUTER_ADDRESS).into()),
-        abi::Token::Uint(U256::from(3)),
-    ]));
+    // fund lilRouter with the base token
+    fund_lil_router(db, base_token, eth_to_wei(LIL_ROUTER_BASE_FUND).into());
+}
+
+/// Sets lilRouter's balance of `token` through its `balanceOf` mapping slot
+fn fund_lil_router(db: &mut CacheDB<SharedBackend>, token: BaseToken, amount: U256) {
+    let slot = token.balance_storage_key((*LIL_ROUTER_ADDRESS).into());
 
-    db.insert_account_storage((*WETH_ADDRESS).into(), slot.into(), eth_to_wei(200))
-        .unwrap();
+    db.insert_account_storage(
+        token.address.into(),
+        rU256::from_be_bytes(slot),
+        amount.into(),
+    )
+    .unwrap();
+}
+
+/// Call from the lil router's controller (every field is set, nothing is left over from meats)
+fn lil_router_tx_env(data: Bytes, next_block: &BlockInfo) -> TxEnv {
+    TxEnv {
+        caller: *LIL_ROUTER_CONTROLLER,
+        gas_limit: 700000,
+        gas_price: next_block.base_fee_per_gas.into(),
+        gas_priority_fee: None,
+        transact_to: TransactTo::Call(*LIL_ROUTER_ADDRESS),
+        value: rU256::ZERO,
+        data: data.0,
+        chain_id: None,
+        nonce: None,
+        access_list: Default::default(),
+    }
 }
@@ -2,6 +2,7 @@ use foundry_evm::{
     executor::InstructionResult,
     revm::{
         interpreter::{opcode, Interpreter},
+        primitives::Address as rAddress,
         Database, EVMData, Inspector,
     },
 };
@@ -39,6 +40,7 @@ pub struct SalmonellaInspectoooor {
     suspicious_opcodes: Vec<OpCode>,
     gas_opcode_counter: u64,
     call_opcode_counter: u64,
+    trusted_contract: Option<rAddress>,
 }
 
 impl SalmonellaInspectoooor {
@@ -48,9 +50,23 @@ impl SalmonellaInspectoooor {
             suspicious_opcodes: Vec::new(),
             gas_opcode_counter: 0,
             call_opcode_counter: 0,
+            trusted_contract: None,
         }
     }
 
+    // skip checks for opcodes executed by a contract we control (e.g. our sandwich contract
+    // reading `COINBASE` to pay bribes)
+    //
+    // Arguments:
+    // * `contract`: address of the trusted contract
+    //
+    // Returns:
+    // `Self`: inspector that ignores opcodes from `contract`
+    pub fn with_trusted_contract(mut self, contract: rAddress) -> Self {
+        self.trusted_contract = Some(contract);
+        self
+    }
+
     // checks if opportunity is safu
     //
     // Arguments:
@@ -82,6 +98,10 @@ impl<DB: Database> Inspector<DB> for SalmonellaInspectoooor {
         _data: &mut EVMData<'_, DB>,
         _is_static: bool,
     ) -> InstructionResult {
+        if self.trusted_contract == Some(interp.contract.address) {
+            return InstructionResult::Continue;
+        }
+
         let executed_opcode = interp.current_opcode();
 
         let mut add_suspicious = |opcode: OpCode| self.suspicious_opcodes.push(opcode);
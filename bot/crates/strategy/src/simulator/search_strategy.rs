@@ -0,0 +1,665 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use cfmms::pool::Pool;
+use ethers::types::U256;
+
+use crate::{
+    tx_utils::huff_sandwich_interface::common::weth_encoder::{
+        WethEncoder, WETH_ENCODING_MULTIPLE,
+    },
+    types::{RawIngredients, SandwichLeg},
+};
+
+use super::lil_router::{evaluate_sandwich_revenue, SimulationSnapshot};
+
+/// Searches stop once the search range is narrower than this many parts per million of the
+/// largest input
+const TOLERANCE_PPM: u64 = 1;
+
+/// Upper bound on rounds for sequential searches (golden section & brent)
+const MAX_ROUNDS: usize = 200;
+
+/// Finds the frontrun input of a leg that produces the highest revenue
+#[async_trait]
+pub trait SearchStrategy: Send + Sync {
+    /// Search `[0, upper_bound]`, revenue is evaluated through `objective`
+    async fn search(
+        &self,
+        objective: &mut dyn SearchObjective,
+        upper_bound: U256,
+    ) -> Result<SearchReport>;
+}
+
+/// Revenue as a function of the searched frontrun input
+#[async_trait]
+pub trait SearchObjective: Send {
+    /// Revenue at each of `frontrun_ins` (evaluated concurrently where possible)
+    async fn evaluate_many(&mut self, frontrun_ins: &[U256]) -> Vec<U256>;
+
+    /// Revenue at `frontrun_in`
+    async fn evaluate(&mut self, frontrun_in: U256) -> U256;
+}
+
+/// Outcome of a search, logged with the simulations it took so that strategies can be tuned on
+/// real opportunities
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SearchReport {
+    pub input: U256,
+    pub revenue: U256,
+    /// Rounds of the search loop
+    pub rounds: usize,
+    /// False if the search gave up before narrowing down to the tolerance
+    pub converged: bool,
+}
+
+/// Revenue of one leg's frontrun input with the other legs' inputs fixed
+/// Inputs are evaluated as the sandwich contract receives them (see `encodable_input`)
+pub struct Objective<'a> {
+    ingredients: &'a RawIngredients,
+    /// Frontrun inputs of every leg (only `inputs[leg]` is searched)
+    inputs: &'a [U256],
+    leg: usize,
+    /// Every simulation runs on a clone of this snapshot
+    snapshot: &'a SimulationSnapshot,
+    /// Sandwich simulations run so far
+    simulations: usize,
+}
+
+impl<'a> Objective<'a> {
+    pub(crate) fn new(
+        ingredients: &'a RawIngredients,
+        inputs: &'a [U256],
+        leg: usize,
+        snapshot: &'a SimulationSnapshot,
+    ) -> Self {
+        Self {
+            ingredients,
+            inputs,
+            leg,
+            snapshot,
+            simulations: 0,
+        }
+    }
+
+    pub fn get_simulations(&self) -> usize {
+        self.simulations
+    }
+}
+
+#[async_trait]
+impl SearchObjective for Objective<'_> {
+    /// Revenue at each of `frontrun_ins`, simulated concurrently (failed simulations have no
+    /// revenue)
+    async fn evaluate_many(&mut self, frontrun_ins: &[U256]) -> Vec<U256> {
+        let sims = frontrun_ins
+            .iter()
+            .map(|frontrun_in| {
+                let mut inputs = self.inputs.to_vec();
+                inputs[self.leg] = encodable_input(*frontrun_in);
+                tokio::task::spawn(evaluate_sandwich_revenue(
+                    inputs,
+                    self.snapshot.clone(),
+                    self.ingredients.clone(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        self.simulations += sims.len();
+
+        futures::future::join_all(sims)
+            .await
+            .into_iter()
+            .map(|r| r.unwrap().unwrap_or_default())
+            .collect()
+    }
+
+    /// Revenue at `frontrun_in`
+    async fn evaluate(&mut self, frontrun_in: U256) -> U256 {
+        let mut inputs = self.inputs.to_vec();
+        inputs[self.leg] = encodable_input(frontrun_in);
+        self.simulations += 1;
+
+        evaluate_sandwich_revenue(inputs, self.snapshot.clone(), self.ingredients.clone())
+            .await
+            .unwrap_or_default()
+    }
+}
+
+/// Frontrun input as the sandwich contract receives it
+/// Inputs are passed through `tx.value` encoded by `WethEncoder`, which drops the lowest digits,
+/// searching only over values it represents exactly keeps the bundle at the evaluated amount
+pub(crate) fn encodable_input(frontrun_in: U256) -> U256 {
+    WethEncoder::truncate(frontrun_in)
+}
+
+/// Search range narrow enough to stop at (no point searching down to the closest wei, or
+/// between two encodable inputs)
+fn tolerance(upper_bound: U256) -> U256 {
+    (upper_bound * TOLERANCE_PPM / 1_000_000).max(*WETH_ENCODING_MULTIPLE)
+}
+
+// Juiced implementation of https://research.ijcaonline.org/volume65/number14/pxc3886165.pdf
+// splits range in more intervals, search intervals concurrently, compare, repeat till termination
+pub struct ParallelGrid {
+    pub intervals: usize,
+    /// Rounds without any revenue before giving up
+    pub max_zero_rounds: usize,
+}
+
+impl ParallelGrid {
+    async fn search_range(
+        &self,
+        objective: &mut dyn SearchObjective,
+        mut lower_bound: U256,
+        mut upper_bound: U256,
+    ) -> Result<SearchReport> {
+        //
+        //            [EXAMPLE WITH 10 BOUND INTERVALS]
+        //
+        //     (first)              (mid)               (last)
+        //        ▼                   ▼                   ▼
+        //        +---+---+---+---+---+---+---+---+---+---+
+        //        |   |   |   |   |   |   |   |   |   |   |
+        //        +---+---+---+---+---+---+---+---+---+---+
+        //        ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲   ▲
+        //        0   1   2   3   4   5   6   7   8   9   X
+        //
+        //  * [0, X] = search range
+        //  * Find revenue at each interval
+        //  * Find index of interval with highest revenue
+        //  * Search again with bounds set to adjacent index of highest
+
+        let tolerance = tolerance((upper_bound + lower_bound) / 2);
+
+        // initialize variables for search
+        let l_interval_lower = |i: usize, intervals: &Vec<U256>| intervals[i - 1] + 1;
+        let r_interval_upper = |i: usize, intervals: &Vec<U256>| {
+            intervals[i + 1]
+                .checked_sub(1.into())
+                .ok_or(anyhow!("r_interval - 1 underflowed"))
+        };
+        let should_loop_terminate = |lower_bound: U256, upper_bound: U256| -> bool {
+            match upper_bound.checked_sub(lower_bound) {
+                Some(search_range) => search_range < tolerance,
+                // produces negative result
+                None => true,
+            }
+        };
+        let mut report = SearchReport::default();
+
+        // continue search until termination condition is met
+        loop {
+            if should_loop_terminate(lower_bound, upper_bound) {
+                report.converged = true;
+                break;
+            }
+            report.rounds += 1;
+
+            // split search range into intervals
+            let diff = upper_bound - lower_bound;
+            let intervals: Vec<U256> = (0..=self.intervals)
+                .map(|i| lower_bound + diff * i / self.intervals)
+                .collect();
+
+            // calculate revenue at each interval concurrently
+            let revenues = objective.evaluate_many(&intervals).await;
+
+            // find interval that produces highest revenue
+            let (highest_revenue_index, highest_revenue) = revenues
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.cmp(b))
+                .unwrap();
+
+            report.input = intervals[highest_revenue_index];
+            report.revenue = *highest_revenue;
+
+            // enhancement: find better way to increase finding opps incase of all rev=0
+            if highest_revenue.is_zero() {
+                // most likely there is no sandwich possibility
+                if report.rounds == self.max_zero_rounds {
+                    return Ok(SearchReport {
+                        rounds: report.rounds,
+                        ..Default::default()
+                    });
+                }
+                // no revenue found, most likely small optimal so decrease range
+                upper_bound = intervals[intervals.len() / 3]
+                    .checked_sub(1.into())
+                    .ok_or(anyhow!("intervals[intervals.len()/3] - 1 underflowed"))?;
+                continue;
+            }
+
+            // if highest revenue is produced at last interval (upper bound stays fixed)
+            if highest_revenue_index == intervals.len() - 1 {
+                lower_bound = l_interval_lower(highest_revenue_index, &intervals);
+                continue;
+            }
+
+            // if highest revenue is produced at first interval (lower bound stays fixed)
+            if highest_revenue_index == 0 {
+                upper_bound = r_interval_upper(highest_revenue_index, &intervals)?;
+                continue;
+            }
+
+            // set bounds to intervals adjacent to highest revenue index and search again
+            lower_bound = l_interval_lower(highest_revenue_index, &intervals);
+            upper_bound = r_interval_upper(highest_revenue_index, &intervals)?;
+        }
+
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl SearchStrategy for ParallelGrid {
+    async fn search(
+        &self,
+        objective: &mut dyn SearchObjective,
+        upper_bound: U256,
+    ) -> Result<SearchReport> {
+        self.search_range(objective, U256::zero(), upper_bound)
+            .await
+    }
+}
+
+/// Coarse log scale grid (`upper_bound / 2^k`) to find the order of magnitude of the optimal
+/// input, refined with a parallel grid around the best point
+/// Finds small optimal inputs in a large range without shrinking the range round by round
+pub struct LogGrid {
+    /// Points on the coarse grid, the smallest is `upper_bound / 2^(points - 1)`
+    pub points: usize,
+    /// Grid used to refine around the best coarse point
+    pub refine: ParallelGrid,
+}
+
+#[async_trait]
+impl SearchStrategy for LogGrid {
+    async fn search(
+        &self,
+        objective: &mut dyn SearchObjective,
+        upper_bound: U256,
+    ) -> Result<SearchReport> {
+        let coarse: Vec<U256> = (0..self.points)
+            .map(|k| upper_bound >> k)
+            .filter(|input| !input.is_zero())
+            .collect();
+        if coarse.is_empty() {
+            return Ok(SearchReport::default());
+        }
+
+        let revenues = objective.evaluate_many(&coarse).await;
+        let (best, best_revenue) = revenues
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.cmp(b))
+            .unwrap();
+        if best_revenue.is_zero() {
+            return Ok(SearchReport {
+                rounds: 1,
+                ..Default::default()
+            });
+        }
+
+        // neighbours on the coarse grid are half & double the best point
+        let lower = coarse.get(best + 1).copied().unwrap_or_default();
+        let upper = match best {
+            0 => coarse[0],
+            _ => coarse[best - 1],
+        };
+        let mut report = self.refine.search_range(objective, lower, upper).await?;
+        report.rounds += 1;
+
+        // refined grid can't do worse than the coarse point it started from
+        if report.revenue < *best_revenue {
+            report.input = coarse[best];
+            report.revenue = *best_revenue;
+        }
+
+        Ok(report)
+    }
+}
+
+/// Golden section search, one simulation per round (assumes revenue is unimodal in the input)
+pub struct GoldenSection;
+
+#[async_trait]
+impl SearchStrategy for GoldenSection {
+    async fn search(
+        &self,
+        objective: &mut dyn SearchObjective,
+        upper_bound: U256,
+    ) -> Result<SearchReport> {
+        // 1/phi
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let tolerance = to_f64(tolerance(upper_bound)).max(1.0);
+
+        let (mut a, mut b) = (0.0, to_f64(upper_bound));
+        let mut c = b - ratio * (b - a);
+        let mut d = a + ratio * (b - a);
+        let mut fc = objective.evaluate(from_f64(c)).await;
+        let mut fd = objective.evaluate(from_f64(d)).await;
+
+        let mut report = SearchReport::default();
+        while b - a > tolerance && report.rounds < MAX_ROUNDS {
+            report.rounds += 1;
+
+            if fc >= fd {
+                b = d;
+                (d, fd) = (c, fc);
+                c = b - ratio * (b - a);
+                fc = objective.evaluate(from_f64(c)).await;
+            } else {
+                a = c;
+                (c, fc) = (d, fd);
+                d = a + ratio * (b - a);
+                fd = objective.evaluate(from_f64(d)).await;
+            }
+        }
+
+        let (input, revenue) = if fc >= fd { (c, fc) } else { (d, fd) };
+        report.input = if revenue.is_zero() {
+            U256::zero()
+        } else {
+            from_f64(input)
+        };
+        report.revenue = revenue;
+        report.converged = b - a <= tolerance;
+
+        Ok(report)
+    }
+}
+
+/// Brent's method, parabolic interpolation through the best three points with golden section
+/// steps as fallback (fewer simulations than golden section when revenue is smooth)
+pub struct Brent;
+
+#[async_trait]
+impl SearchStrategy for Brent {
+    async fn search(
+        &self,
+        objective: &mut dyn SearchObjective,
+        upper_bound: U256,
+    ) -> Result<SearchReport> {
+        // 1 - 1/phi
+        let golden = (3.0 - 5f64.sqrt()) / 2.0;
+        let tolerance = to_f64(tolerance(upper_bound)).max(1.0);
+
+        // minimize the negated revenue
+        let (mut a, mut b) = (0.0, to_f64(upper_bound));
+        let mut x = a + golden * (b - a);
+        let (mut w, mut v) = (x, x);
+        let mut revenue = objective.evaluate(from_f64(x)).await;
+        let mut fx = -to_f64(revenue);
+        let (mut fw, mut fv) = (fx, fx);
+        let (mut d, mut e): (f64, f64) = (0.0, 0.0);
+
+        let mut report = SearchReport::default();
+        while report.rounds < MAX_ROUNDS {
+            let midpoint = (a + b) / 2.0;
+            if (x - midpoint).abs() <= 2.0 * tolerance - (b - a) / 2.0 {
+                report.converged = true;
+                break;
+            }
+            report.rounds += 1;
+
+            let mut golden_step = true;
+            if e.abs() > tolerance {
+                // fit a parabola through x, w & v
+                let r = (x - w) * (fx - fv);
+                let mut q = (x - v) * (fx - fw);
+                let mut p = (x - v) * q - (x - w) * r;
+                q = 2.0 * (q - r);
+                if q > 0.0 {
+                    p = -p;
+                }
+                q = q.abs();
+
+                // only take the parabolic step if it falls within bounds and shrinks
+                if p.abs() < (q * e / 2.0).abs() && p > q * (a - x) && p < q * (b - x) {
+                    e = d;
+                    d = p / q;
+                    let u = x + d;
+                    if u - a < 2.0 * tolerance || b - u < 2.0 * tolerance {
+                        d = tolerance.copysign(midpoint - x);
+                    }
+                    golden_step = false;
+                }
+            }
+            if golden_step {
+                e = if x >= midpoint { a - x } else { b - x };
+                d = golden * e;
+            }
+
+            let u = if d.abs() >= tolerance {
+                x + d
+            } else {
+                x + tolerance.copysign(d)
+            };
+            let revenue_u = objective.evaluate(from_f64(u)).await;
+            let fu = -to_f64(revenue_u);
+
+            if fu <= fx {
+                if u >= x {
+                    a = x;
+                } else {
+                    b = x;
+                }
+                (v, fv) = (w, fw);
+                (w, fw) = (x, fx);
+                (x, fx) = (u, fu);
+                revenue = revenue_u;
+            } else {
+                if u < x {
+                    a = u;
+                } else {
+                    b = u;
+                }
+                if fu <= fw || w == x {
+                    (v, fv) = (w, fw);
+                    (w, fw) = (u, fu);
+                } else if fu <= fv || v == x || v == w {
+                    (v, fv) = (u, fu);
+                }
+            }
+        }
+
+        report.revenue = revenue;
+        report.input = if revenue.is_zero() {
+            U256::zero()
+        } else {
+            from_f64(x)
+        };
+
+        Ok(report)
+    }
+}
+
+// f64 keeps ~15 significant digits, plenty for a 1e-6 tolerance
+fn to_f64(value: U256) -> f64 {
+    value.min(U256::from(u128::MAX)).as_u128() as f64
+}
+
+fn from_f64(value: f64) -> U256 {
+    U256::from(value.max(0.0) as u128)
+}
+
+/// Config used to select and parametrize a search strategy
+///
+/// Parsed from strings of the form:
+/// * `grid:<intervals>`
+/// * `log-grid:<points>:<refine_intervals>`
+/// * `golden`
+/// * `brent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchStrategyConfig {
+    ParallelGrid { intervals: usize },
+    LogGrid { points: usize, intervals: usize },
+    GoldenSection,
+    Brent,
+}
+
+impl Default for SearchStrategyConfig {
+    fn default() -> Self {
+        SearchStrategyConfig::ParallelGrid { intervals: 15 }
+    }
+}
+
+impl SearchStrategyConfig {
+    /// Create the strategy described by this config
+    pub fn into_strategy(self) -> Box<dyn SearchStrategy> {
+        match self {
+            SearchStrategyConfig::ParallelGrid { intervals } => Box::new(ParallelGrid {
+                intervals,
+                max_zero_rounds: 10,
+            }),
+            SearchStrategyConfig::LogGrid { points, intervals } => Box::new(LogGrid {
+                points,
+                refine: ParallelGrid {
+                    intervals,
+                    max_zero_rounds: 1,
+                },
+            }),
+            SearchStrategyConfig::GoldenSection => Box::new(GoldenSection),
+            SearchStrategyConfig::Brent => Box::new(Brent),
+        }
+    }
+}
+
+fn parse_count(value: &str, min: usize) -> Result<usize> {
+    let count = value
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| anyhow!("Failed to parse \"{}\" as a count", value))?;
+    if count < min {
+        return Err(anyhow!("count {} is smaller than {}", count, min));
+    }
+    Ok(count)
+}
+
+impl FromStr for SearchStrategyConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.trim().splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        let args: Vec<&str> = parts
+            .next()
+            .map(|a| a.split(':').collect())
+            .unwrap_or_default();
+
+        match (name, args.as_slice()) {
+            ("grid", [intervals]) => Ok(SearchStrategyConfig::ParallelGrid {
+                intervals: parse_count(intervals, 2)?,
+            }),
+            ("log-grid", [points, intervals]) => Ok(SearchStrategyConfig::LogGrid {
+                points: parse_count(points, 2)?,
+                intervals: parse_count(intervals, 2)?,
+            }),
+            ("golden", []) => Ok(SearchStrategyConfig::GoldenSection),
+            ("brent", []) => Ok(SearchStrategyConfig::Brent),
+            _ => Err(anyhow!("Unknown search strategy \"{}\"", s)),
+        }
+    }
+}
+
+/// Search strategy used for each pool type
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SearchConfig {
+    pub v2: SearchStrategyConfig,
+    pub v3: SearchStrategyConfig,
+}
+
+/// Strategies created from a `SearchConfig`
+pub struct SearchStrategies {
+    v2: Box<dyn SearchStrategy>,
+    v3: Box<dyn SearchStrategy>,
+}
+
+impl SearchStrategies {
+    pub fn new(config: SearchConfig) -> Self {
+        Self {
+            v2: config.v2.into_strategy(),
+            v3: config.v3.into_strategy(),
+        }
+    }
+
+    /// Strategy used to search `leg`'s frontrun input (flash swap & inventory legs are v2)
+    pub fn get(&self, leg: &SandwichLeg) -> &dyn SearchStrategy {
+        match leg.target_pool {
+            Pool::UniswapV2(_) => self.v2.as_ref(),
+            Pool::UniswapV3(_) => self.v3.as_ref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::utils::parse_ether;
+
+    use super::*;
+
+    /// Unimodal revenue (parabola through 1 eth at `peak`, positive over `[0, 100 eth]`)
+    struct Parabola {
+        peak: U256,
+    }
+
+    impl Parabola {
+        fn revenue(&self, input: U256) -> U256 {
+            let distance = match input > self.peak {
+                true => input - self.peak,
+                false => self.peak - input,
+            };
+            let curvature = U256::exp10(22);
+            U256::exp10(18).saturating_sub(distance * distance / curvature)
+        }
+    }
+
+    #[async_trait]
+    impl SearchObjective for Parabola {
+        async fn evaluate_many(&mut self, frontrun_ins: &[U256]) -> Vec<U256> {
+            frontrun_ins
+                .iter()
+                .map(|input| self.revenue(*input))
+                .collect()
+        }
+
+        async fn evaluate(&mut self, frontrun_in: U256) -> U256 {
+            self.revenue(frontrun_in)
+        }
+    }
+
+    /// every strategy converges on the peak of a unimodal revenue, wherever it sits in the range
+    #[tokio::test]
+    async fn search_strategies_converge_on_unimodal_revenue() {
+        let upper_bound = parse_ether("100").unwrap();
+
+        for config in ["grid:15", "log-grid:24:8", "golden", "brent"] {
+            let strategy = SearchStrategyConfig::from_str(config)
+                .unwrap()
+                .into_strategy();
+
+            for peak in ["0.05", "13.37", "99"] {
+                let mut objective = Parabola {
+                    peak: parse_ether(peak).unwrap(),
+                };
+                let report = strategy.search(&mut objective, upper_bound).await.unwrap();
+
+                assert!(report.converged, "{} didn't converge on {}", config, peak);
+                let distance = match report.input > objective.peak {
+                    true => report.input - objective.peak,
+                    false => objective.peak - report.input,
+                };
+                assert!(
+                    distance <= upper_bound / 10_000,
+                    "{} stopped at {} for a peak at {}",
+                    config,
+                    report.input,
+                    peak
+                );
+                assert_eq!(report.revenue, objective.revenue(report.input));
+            }
+        }
+    }
+}
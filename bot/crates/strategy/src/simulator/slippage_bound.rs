@@ -0,0 +1,140 @@
+use anyhow::Result;
+use cfmms::pool::Pool::{UniswapV2, UniswapV3};
+use ethers::types::U256;
+use std::fmt;
+
+use crate::{
+    tx_utils::swap_interface::{decode_victim_swap, SwapProtocol, VictimSwap},
+    types::{RawIngredients, SandwichLeg},
+};
+
+use super::{
+    lil_router::SimulationSnapshot, uniswap_v3::v3_get_sandwiched_amount_out,
+    v2_closed_form::V2Sandwich,
+};
+
+/// How close (in parts per million of the upper bound) the v3 bound is searched
+const V3_BOUND_PRECISION_PPM: u64 = 100;
+
+/// How much a leg's frontrun can move the price before its victim's swap reverts
+#[derive(Debug, Clone, Copy)]
+pub struct SlippageBound {
+    /// Largest frontrun input that still leaves the victim its `amountOutMin` (capped at the
+    /// search's upper bound)
+    pub max_frontrun_in: U256,
+    /// Share of the victim's expected output it is willing to give up (parts per million)
+    pub headroom_ppm: u64,
+}
+
+impl fmt::Display for SlippageBound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.2}% headroom, max frontrun {}",
+            self.headroom_ppm as f64 / 1e4,
+            self.max_frontrun_in
+        )
+    }
+}
+
+/// Bounds a leg's frontrun by the victim's slippage, decoded from its router calldata
+/// Returns `None` when the bound can't be derived (flash swap or inventory leg, several swaps in
+/// the meats, multi hop victim, victim on another pool type), the search then runs up to
+/// `max_input`
+pub(crate) fn find_slippage_bound(
+    ingredients: &RawIngredients,
+    leg: &SandwichLeg,
+    max_input: U256,
+    snapshot: &SimulationSnapshot,
+) -> Result<Option<SlippageBound>> {
+    if leg.flash_pool.is_some() || leg.inventory_token.is_some() {
+        return Ok(None);
+    }
+
+    // prerequisites (e.g. approvals) don't decode as swaps, anything else moves the pool too
+    let mut swaps = ingredients
+        .get_meats_ref()
+        .iter()
+        .filter_map(decode_victim_swap);
+    let victim = match (swaps.next(), swaps.next()) {
+        (Some(swap), None) => swap,
+        _ => return Ok(None),
+    };
+
+    let base_token = leg.start_end_token.address;
+    if !victim.is_single_hop(base_token, leg.intermediary_token) {
+        return Ok(None);
+    }
+
+    let mut evm = snapshot.clone().into_evm();
+
+    match (leg.target_pool, victim.protocol) {
+        (UniswapV2(pool), SwapProtocol::UniswapV2) => {
+            let sandwich = match V2Sandwich::new(pool, base_token, &victim, &mut evm)? {
+                Some(sandwich) => sandwich,
+                None => return Ok(None),
+            };
+
+            Ok(Some(SlippageBound {
+                max_frontrun_in: sandwich.max_safe_frontrun_in(max_input),
+                headroom_ppm: headroom_ppm(
+                    sandwich.victim_expected_out(),
+                    sandwich.victim_out_min(),
+                ),
+            }))
+        }
+        (UniswapV3(pool), SwapProtocol::UniswapV3) if victim.fees == [pool.fee] => {
+            let mut victim_out = |frontrun_in| {
+                v3_get_sandwiched_amount_out(
+                    frontrun_in,
+                    victim.amount_in,
+                    pool,
+                    base_token,
+                    &mut evm,
+                )
+            };
+
+            let expected_out = victim_out(U256::zero())?;
+            Ok(Some(SlippageBound {
+                max_frontrun_in: max_v3_frontrun_in(&victim, max_input, &mut victim_out),
+                headroom_ppm: headroom_ppm(expected_out, victim.amount_out_min),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Largest frontrun up to `max_input` that leaves the victim its `amountOutMin`
+/// Bisects on the native v3 swap math (victim's output falls as the frontrun grows), errors
+/// count as a reverting victim
+fn max_v3_frontrun_in(
+    victim: &VictimSwap,
+    max_input: U256,
+    victim_out: &mut impl FnMut(U256) -> Result<U256>,
+) -> U256 {
+    let survives = |out: Result<U256>| out.map_or(false, |out| out >= victim.amount_out_min);
+
+    if survives(victim_out(max_input)) {
+        return max_input;
+    }
+
+    let precision = (max_input * V3_BOUND_PRECISION_PPM / 1_000_000).max(U256::one());
+    let (mut lower, mut upper) = (U256::zero(), max_input);
+    while upper - lower > precision {
+        let mid = lower + (upper - lower) / 2;
+        match survives(victim_out(mid)) {
+            true => lower = mid,
+            false => upper = mid,
+        }
+    }
+
+    // stay on the side where the victim still goes through
+    lower
+}
+
+fn headroom_ppm(expected_out: U256, out_min: U256) -> u64 {
+    if expected_out.is_zero() {
+        return 0;
+    }
+    (expected_out.saturating_sub(out_min) * 1_000_000 / expected_out).as_u64()
+}
@@ -0,0 +1,698 @@
+use anyhow::{anyhow, Result};
+use cfmms::pool::UniswapV3Pool;
+use ethers::abi::{self, parse_abi, Address, ParamType};
+use ethers::prelude::{BaseContract, Lazy};
+use ethers::types::{Bytes, I256, U256, U512};
+use foundry_evm::executor::{fork::SharedBackend, ExecutionResult, Output, TransactTo};
+use foundry_evm::revm::{db::CacheDB, primitives::U256 as rU256, EVM};
+use std::cell::RefCell;
+
+use crate::constants::SUGAR_DADDY;
+
+// Native port of the uniswap v3 swap math (TickMath, SqrtPriceMath, SwapMath, TickBitmap)
+// https://github.com/Uniswap/v3-core/tree/main/contracts/libraries
+
+const MIN_TICK: i32 = -887272;
+const MAX_TICK: i32 = 887272;
+
+/// Fees are expressed in hundredths of a bip
+const FEE_DENOMINATOR: u32 = 1_000_000;
+
+static MIN_SQRT_RATIO: Lazy<U256> = Lazy::new(|| U256::from(4295128739u64));
+static MAX_SQRT_RATIO: Lazy<U256> =
+    Lazy::new(|| U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap());
+
+static V3_POOL_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(
+        parse_abi(&[
+            "function slot0() external view returns (uint160,int24,uint16,uint16,uint16,uint8,bool)",
+            "function liquidity() external view returns (uint128)",
+            "function tickSpacing() external view returns (int24)",
+            "function tickBitmap(int16) external view returns (uint256)",
+            "function ticks(int24) external view returns (uint128,int128,uint256,uint256,int56,uint160,uint32,bool)",
+        ])
+        .unwrap(),
+    )
+});
+
+// Find amount out from an amount in by walking the pool's initialized ticks
+// note: pool state (slot0, liquidity, tick bitmap, ticks) taken from evm
+//
+// Arguments:
+// * `amount_in`: amount of token in
+// * `target_pool`: v3 pool to swap on
+// * `base_token`: token that the sandwich starts and ends with
+// * `is_frontrun`: true if trading `base_token` for the other token
+// * `evm`: mutable reference to evm used for query
+//
+// Returns:
+// Ok(U256): amount out
+// Err(SimulationError): if error during caluclation
+pub fn v3_get_amount_out(
+    amount_in: U256,
+    target_pool: UniswapV3Pool,
+    base_token: Address,
+    is_frontrun: bool,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<U256> {
+    let zero_for_one = v3_zero_for_one(target_pool, base_token, is_frontrun);
+    let (state, tick_spacing) = v3_get_swap_state(target_pool.address, evm)?;
+
+    let (amount_out, _) = v3_swap_from_state(
+        state,
+        target_pool,
+        tick_spacing,
+        zero_for_one,
+        amount_in,
+        evm,
+    )?;
+    Ok(amount_out)
+}
+
+// Find a victim's amount out when a frontrun in the same direction lands right before it
+//
+// Arguments:
+// * `frontrun_in`: amount of `base_token` in of the frontrun
+// * `victim_in`: amount of `base_token` in of the victim
+// * `target_pool`: v3 pool both swap on
+// * `base_token`: token that the sandwich starts and ends with
+// * `evm`: mutable reference to evm used for query
+//
+// Returns:
+// Ok(U256): victim's amount out
+// Err(SimulationError): if error during caluclation
+pub fn v3_get_sandwiched_amount_out(
+    frontrun_in: U256,
+    victim_in: U256,
+    target_pool: UniswapV3Pool,
+    base_token: Address,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<U256> {
+    let zero_for_one = v3_zero_for_one(target_pool, base_token, true);
+    let (state, tick_spacing) = v3_get_swap_state(target_pool.address, evm)?;
+
+    let (_, state) = v3_swap_from_state(
+        state,
+        target_pool,
+        tick_spacing,
+        zero_for_one,
+        frontrun_in,
+        evm,
+    )?;
+    let (victim_out, _) = v3_swap_from_state(
+        state,
+        target_pool,
+        tick_spacing,
+        zero_for_one,
+        victim_in,
+        evm,
+    )?;
+    Ok(victim_out)
+}
+
+/// Swap direction of a trade on `target_pool`
+fn v3_zero_for_one(target_pool: UniswapV3Pool, base_token: Address, is_frontrun: bool) -> bool {
+    let other_token = match base_token == target_pool.token_a {
+        true => target_pool.token_b,
+        false => target_pool.token_a,
+    };
+    let input_token = if is_frontrun { base_token } else { other_token };
+    input_token < if is_frontrun { other_token } else { base_token }
+}
+
+/// Current price, tick & liquidity of a pool (plus its tick spacing) taken from evm
+fn v3_get_swap_state(
+    pool: Address,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<(SwapState, i32)> {
+    let slot0 = v3_call(pool, V3_POOL_ABI.encode("slot0", ()).unwrap(), evm)?;
+    let slot0 = abi::decode(&[ParamType::Uint(160), ParamType::Int(24)], &slot0)?;
+    let sqrt_price_x96 = slot0[0].clone().into_uint().unwrap();
+    let tick = I256::from_raw(slot0[1].clone().into_int().unwrap()).as_i32();
+
+    let liquidity = v3_call(pool, V3_POOL_ABI.encode("liquidity", ()).unwrap(), evm)?;
+    let liquidity = U256::from_big_endian(&liquidity[..32]).as_u128();
+
+    let tick_spacing = v3_call(pool, V3_POOL_ABI.encode("tickSpacing", ()).unwrap(), evm)?;
+    let tick_spacing = I256::from_raw(U256::from_big_endian(&tick_spacing[..32])).as_i32();
+
+    let state = SwapState {
+        sqrt_price_x96,
+        tick,
+        liquidity,
+    };
+    Ok((state, tick_spacing))
+}
+
+/// Exact input swap starting from `state`, initialized ticks are read from evm
+/// Returns the amount out and the pool state after the swap
+fn v3_swap_from_state(
+    state: SwapState,
+    target_pool: UniswapV3Pool,
+    tick_spacing: i32,
+    zero_for_one: bool,
+    amount_in: U256,
+    evm: &mut EVM<CacheDB<SharedBackend>>,
+) -> Result<(U256, SwapState)> {
+    let pool = target_pool.address;
+    // both tick lookups query the same evm
+    let evm = RefCell::new(evm);
+
+    swap_exact_input_with_state(
+        state,
+        target_pool.fee,
+        tick_spacing,
+        zero_for_one,
+        amount_in,
+        |word_pos| {
+            let data = V3_POOL_ABI
+                .encode("tickBitmap", I256::from(word_pos))
+                .unwrap();
+            let word = v3_call(pool, data, &mut evm.borrow_mut())?;
+            Ok(U256::from_big_endian(&word[..32]))
+        },
+        |tick| {
+            let data = V3_POOL_ABI.encode("ticks", I256::from(tick)).unwrap();
+            let info = v3_call(pool, data, &mut evm.borrow_mut())?;
+            let info = abi::decode(&[ParamType::Uint(128), ParamType::Int(128)], &info)?;
+            Ok(I256::from_raw(info[1].clone().into_int().unwrap()).as_i128())
+        },
+    )
+}
+
+/// Pool state that changes while a swap crosses ticks
+#[derive(Debug, Clone, Copy)]
+pub struct SwapState {
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    pub liquidity: u128,
+}
+
+/// Port of `UniswapV3Pool.swap` for exact input swaps without a price limit
+/// `tick_bitmap` returns the bitmap word at a word position, `liquidity_net` the net liquidity of
+/// an initialized tick
+pub fn swap_exact_input(
+    state: SwapState,
+    fee: u32,
+    tick_spacing: i32,
+    zero_for_one: bool,
+    amount_in: U256,
+    tick_bitmap: impl FnMut(i16) -> Result<U256>,
+    liquidity_net: impl FnMut(i32) -> Result<i128>,
+) -> Result<U256> {
+    let (amount_out, _) = swap_exact_input_with_state(
+        state,
+        fee,
+        tick_spacing,
+        zero_for_one,
+        amount_in,
+        tick_bitmap,
+        liquidity_net,
+    )?;
+    Ok(amount_out)
+}
+
+/// Same as `swap_exact_input`, also returns the pool state after the swap
+pub fn swap_exact_input_with_state(
+    mut state: SwapState,
+    fee: u32,
+    tick_spacing: i32,
+    zero_for_one: bool,
+    amount_in: U256,
+    mut tick_bitmap: impl FnMut(i16) -> Result<U256>,
+    mut liquidity_net: impl FnMut(i32) -> Result<i128>,
+) -> Result<(U256, SwapState)> {
+    let sqrt_price_limit = match zero_for_one {
+        true => *MIN_SQRT_RATIO + 1,
+        false => *MAX_SQRT_RATIO - 1,
+    };
+
+    let mut amount_remaining = amount_in;
+    let mut amount_out = U256::zero();
+
+    while !amount_remaining.is_zero() && state.sqrt_price_x96 != sqrt_price_limit {
+        let (tick_next, initialized) = next_initialized_tick_within_one_word(
+            state.tick,
+            tick_spacing,
+            zero_for_one,
+            &mut tick_bitmap,
+        )?;
+        let tick_next = tick_next.clamp(MIN_TICK, MAX_TICK);
+        let sqrt_price_next = get_sqrt_ratio_at_tick(tick_next)?;
+
+        let sqrt_price_target = match zero_for_one {
+            true => sqrt_price_next.max(sqrt_price_limit),
+            false => sqrt_price_next.min(sqrt_price_limit),
+        };
+
+        let (sqrt_price_x96, step_in, step_out, fee_amount) = compute_swap_step(
+            state.sqrt_price_x96,
+            sqrt_price_target,
+            state.liquidity,
+            amount_remaining,
+            fee,
+        )?;
+        state.sqrt_price_x96 = sqrt_price_x96;
+        amount_remaining -= step_in + fee_amount;
+        amount_out += step_out;
+
+        if state.sqrt_price_x96 != sqrt_price_next {
+            // swap ended inside the current range (all input consumed)
+            let (lower, upper) = match zero_for_one {
+                true => (tick_next, state.tick),
+                false => (state.tick, tick_next - 1),
+            };
+            state.tick = get_tick_at_sqrt_ratio(state.sqrt_price_x96, lower, upper)?;
+            break;
+        }
+
+        if initialized {
+            let net = liquidity_net(tick_next)?;
+            let net = if zero_for_one { -net } else { net };
+            state.liquidity = match net < 0 {
+                true => state.liquidity.checked_sub(net.unsigned_abs()),
+                false => state.liquidity.checked_add(net as u128),
+            }
+            .ok_or_else(|| anyhow!("[uniswap_v3: LiquidityOverflow] at tick {}", tick_next))?;
+        }
+
+        state.tick = if zero_for_one {
+            tick_next - 1
+        } else {
+            tick_next
+        };
+    }
+
+    Ok((amount_out, state))
+}
+
+/// Port of `TickMath.getSqrtRatioAtTick`
+pub fn get_sqrt_ratio_at_tick(tick: i32) -> Result<U256> {
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        return Err(anyhow!("[uniswap_v3: TickOutOfRange] {}", tick));
+    }
+
+    let ratio_const = |hex: &str| U256::from_str_radix(hex, 16).unwrap();
+
+    let mut ratio = match abs_tick & 0x1 != 0 {
+        true => ratio_const("fffcb933bd6fad37aa2d162d1a594001"),
+        false => ratio_const("100000000000000000000000000000000"),
+    };
+
+    for (bit, magic) in [
+        (0x2, "fff97272373d413259a46990580e213a"),
+        (0x4, "fff2e50f5f656932ef12357cf3c7fdcc"),
+        (0x8, "ffe5caca7e10e4e61c3624eaa0941cd0"),
+        (0x10, "ffcb9843d60f6159c9db58835c926644"),
+        (0x20, "ff973b41fa98c081472e6896dfb254c0"),
+        (0x40, "ff2ea16466c96a3843ec78b326b52861"),
+        (0x80, "fe5dee046a99a2a811c461f1969c3053"),
+        (0x100, "fcbe86c7900a88aedcffc83b479aa3a4"),
+        (0x200, "f987a7253ac413176f2b074cf7815e54"),
+        (0x400, "f3392b0822b70005940c7a398e4b70f3"),
+        (0x800, "e7159475a2c29b7443b29c7fa6e889d9"),
+        (0x1000, "d097f3bdfd2022b8845ad8f792aa5825"),
+        (0x2000, "a9f746462d870fdf8a65dc1f90e061e5"),
+        (0x4000, "70d869a156d2a1b890bb3df62baf32f7"),
+        (0x8000, "31be135f97d08fd981231505542fcfa6"),
+        (0x10000, "9aa508b5b7a84e1c677de54f3e99bc9"),
+        (0x20000, "5d6af8dedb81196699c329225ee604"),
+        (0x40000, "2216e584f5fa1ea926041bedfe98"),
+        (0x80000, "48a170391f7dc42444e8fa2"),
+    ] {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * ratio_const(magic)) >> 128;
+        }
+    }
+
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // round up to a Q64.96 so that it always ends in the same tick when converted back
+    let rounding = match (ratio % (U256::one() << 32)).is_zero() {
+        true => U256::zero(),
+        false => U256::one(),
+    };
+    Ok((ratio >> 32) + rounding)
+}
+
+/// Port of `SwapMath.computeSwapStep` for exact input
+/// Returns (next sqrt price, amount in, amount out, fee amount)
+pub fn compute_swap_step(
+    sqrt_price_current: U256,
+    sqrt_price_target: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    fee: u32,
+) -> Result<(U256, U256, U256, U256)> {
+    let zero_for_one = sqrt_price_current >= sqrt_price_target;
+    let fee_denominator = U256::from(FEE_DENOMINATOR);
+    let fee = U256::from(fee);
+
+    let amount_remaining_less_fee =
+        mul_div(amount_remaining, fee_denominator - fee, fee_denominator)?;
+
+    let amount_in_to_target = match zero_for_one {
+        true => get_amount0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?,
+        false => get_amount1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)?,
+    };
+
+    let sqrt_price_next = match amount_remaining_less_fee >= amount_in_to_target {
+        true => sqrt_price_target,
+        false => get_next_sqrt_price_from_input(
+            sqrt_price_current,
+            liquidity,
+            amount_remaining_less_fee,
+            zero_for_one,
+        )?,
+    };
+    let reached_target = sqrt_price_next == sqrt_price_target;
+
+    let (amount_in, amount_out) = match zero_for_one {
+        true => (
+            match reached_target {
+                true => amount_in_to_target,
+                false => get_amount0_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?,
+            },
+            get_amount1_delta(sqrt_price_next, sqrt_price_current, liquidity, false)?,
+        ),
+        false => (
+            match reached_target {
+                true => amount_in_to_target,
+                false => get_amount1_delta(sqrt_price_current, sqrt_price_next, liquidity, true)?,
+            },
+            get_amount0_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?,
+        ),
+    };
+
+    let fee_amount = match reached_target {
+        // whatever is left of the input after the swap is taken as fee
+        false => amount_remaining - amount_in,
+        true => mul_div_rounding_up(amount_in, fee, fee_denominator - fee)?,
+    };
+
+    Ok((sqrt_price_next, amount_in, amount_out, fee_amount))
+}
+
+/// Port of `SqrtPriceMath.getAmount0Delta`
+pub fn get_amount0_delta(
+    sqrt_ratio_a: U256,
+    sqrt_ratio_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256> {
+    let (sqrt_ratio_a, sqrt_ratio_b) = sort(sqrt_ratio_a, sqrt_ratio_b);
+    if sqrt_ratio_a.is_zero() {
+        return Err(anyhow!("[uniswap_v3: ZeroPrice]"));
+    }
+
+    let numerator1 = U256::from(liquidity) << 96;
+    let numerator2 = sqrt_ratio_b - sqrt_ratio_a;
+
+    match round_up {
+        true => Ok(div_rounding_up(
+            mul_div_rounding_up(numerator1, numerator2, sqrt_ratio_b)?,
+            sqrt_ratio_a,
+        )),
+        false => Ok(mul_div(numerator1, numerator2, sqrt_ratio_b)? / sqrt_ratio_a),
+    }
+}
+
+/// Port of `SqrtPriceMath.getAmount1Delta`
+pub fn get_amount1_delta(
+    sqrt_ratio_a: U256,
+    sqrt_ratio_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256> {
+    let (sqrt_ratio_a, sqrt_ratio_b) = sort(sqrt_ratio_a, sqrt_ratio_b);
+    let q96 = U256::one() << 96;
+
+    match round_up {
+        true => mul_div_rounding_up(liquidity.into(), sqrt_ratio_b - sqrt_ratio_a, q96),
+        false => mul_div(liquidity.into(), sqrt_ratio_b - sqrt_ratio_a, q96),
+    }
+}
+
+/// Tick of a price known to lie in `[lower, upper]`, same result as `TickMath.getTickAtSqrtRatio`
+/// (greatest tick whose sqrt ratio is at most `sqrt_price`)
+fn get_tick_at_sqrt_ratio(sqrt_price: U256, mut lower: i32, mut upper: i32) -> Result<i32> {
+    while lower < upper {
+        let mid = upper - (upper - lower) / 2;
+        match get_sqrt_ratio_at_tick(mid)? <= sqrt_price {
+            true => lower = mid,
+            false => upper = mid - 1,
+        }
+    }
+    Ok(lower)
+}
+
+/// Port of `SqrtPriceMath.getNextSqrtPriceFromInput`
+fn get_next_sqrt_price_from_input(
+    sqrt_price: U256,
+    liquidity: u128,
+    amount_in: U256,
+    zero_for_one: bool,
+) -> Result<U256> {
+    if sqrt_price.is_zero() || liquidity == 0 {
+        return Err(anyhow!("[uniswap_v3: NoLiquidity]"));
+    }
+
+    if amount_in.is_zero() {
+        return Ok(sqrt_price);
+    }
+
+    let liquidity = U256::from(liquidity);
+
+    match zero_for_one {
+        // getNextSqrtPriceFromAmount0RoundingUp (adding token0)
+        true => {
+            let numerator1 = liquidity << 96;
+            let (product, overflowed) = amount_in.overflowing_mul(sqrt_price);
+            if !overflowed {
+                let (denominator, overflowed) = numerator1.overflowing_add(product);
+                if !overflowed {
+                    return mul_div_rounding_up(numerator1, sqrt_price, denominator);
+                }
+            }
+            Ok(div_rounding_up(
+                numerator1,
+                numerator1 / sqrt_price + amount_in,
+            ))
+        }
+        // getNextSqrtPriceFromAmount1RoundingDown (adding token1)
+        false => {
+            let quotient = match amount_in <= (U256::one() << 160) - 1 {
+                true => (amount_in << 96) / liquidity,
+                false => mul_div(amount_in, U256::one() << 96, liquidity)?,
+            };
+            Ok(sqrt_price + quotient)
+        }
+    }
+}
+
+/// Port of `TickBitmap.nextInitializedTickWithinOneWord`
+/// Returns the next tick to cross and whether it is initialized
+fn next_initialized_tick_within_one_word(
+    tick: i32,
+    tick_spacing: i32,
+    lte: bool,
+    tick_bitmap: &mut impl FnMut(i16) -> Result<U256>,
+) -> Result<(i32, bool)> {
+    let mut compressed = tick / tick_spacing;
+    if tick < 0 && tick % tick_spacing != 0 {
+        // round towards negative infinity
+        compressed -= 1;
+    }
+
+    if lte {
+        let (word_pos, bit_pos) = position(compressed);
+        // all the 1s at or to the right of the current bit_pos
+        let mask = (U256::one() << bit_pos as usize) - 1 + (U256::one() << bit_pos as usize);
+        let masked = tick_bitmap(word_pos)? & mask;
+
+        let initialized = !masked.is_zero();
+        let next = match initialized {
+            true => {
+                (compressed - (bit_pos as i32 - most_significant_bit(masked) as i32)) * tick_spacing
+            }
+            false => (compressed - bit_pos as i32) * tick_spacing,
+        };
+        Ok((next, initialized))
+    } else {
+        // start from the word of the next tick, since the current tick state doesn't matter
+        let (word_pos, bit_pos) = position(compressed + 1);
+        // all the 1s at or to the left of the bit_pos
+        let mask = !((U256::one() << bit_pos as usize) - 1);
+        let masked = tick_bitmap(word_pos)? & mask;
+
+        let initialized = !masked.is_zero();
+        let next = match initialized {
+            true => {
+                (compressed + 1 + (masked.trailing_zeros() as i32 - bit_pos as i32)) * tick_spacing
+            }
+            false => (compressed + 1 + (255 - bit_pos as i32)) * tick_spacing,
+        };
+        Ok((next, initialized))
+    }
+}
+
+/// Word and bit position of a compressed tick in the tick bitmap
+fn position(compressed: i32) -> (i16, u8) {
+    ((compressed >> 8) as i16, (compressed & 0xff) as u8)
+}
+
+fn most_significant_bit(x: U256) -> u32 {
+    x.bits() as u32 - 1
+}
+
+fn sort(a: U256, b: U256) -> (U256, U256) {
+    match a > b {
+        true => (b, a),
+        false => (a, b),
+    }
+}
+
+/// Port of `FullMath.mulDiv` (512 bit intermediate product)
+fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    if denominator.is_zero() {
+        return Err(anyhow!("[uniswap_v3: DivisionByZero]"));
+    }
+    U256::try_from(a.full_mul(b) / U512::from(denominator))
+        .map_err(|_| anyhow!("[uniswap_v3: MulDivOverflow]"))
+}
+
+/// Port of `FullMath.mulDivRoundingUp`
+fn mul_div_rounding_up(a: U256, b: U256, denominator: U256) -> Result<U256> {
+    let result = mul_div(a, b, denominator)?;
+    match (a.full_mul(b) % U512::from(denominator)).is_zero() {
+        true => Ok(result),
+        false => result
+            .checked_add(U256::one())
+            .ok_or_else(|| anyhow!("[uniswap_v3: MulDivOverflow]")),
+    }
+}
+
+/// Port of `UnsafeMath.divRoundingUp`
+fn div_rounding_up(x: U256, y: U256) -> U256 {
+    let rounding = match (x % y).is_zero() {
+        true => U256::zero(),
+        false => U256::one(),
+    };
+    x / y + rounding
+}
+
+/// Read only call to a v3 pool in the evm
+fn v3_call(pool: Address, data: Bytes, evm: &mut EVM<CacheDB<SharedBackend>>) -> Result<Bytes> {
+    evm.env.tx.transact_to = TransactTo::Call(pool.0.into());
+    evm.env.tx.caller = (*SUGAR_DADDY).0.into();
+    evm.env.tx.value = rU256::ZERO;
+    evm.env.tx.data = data.0;
+    evm.env.tx.nonce = None;
+    let result = match evm.transact_ref() {
+        Ok(result) => result.result,
+        Err(e) => return Err(anyhow!("[uniswap_v3: EVM ERROR] {:?}", e)),
+    };
+    let output: Bytes = match result {
+        ExecutionResult::Success { output, .. } => match output {
+            Output::Call(o) => o.into(),
+            Output::Create(o, _) => o.into(),
+        },
+        ExecutionResult::Revert { output, .. } => {
+            return Err(anyhow!("[uniswap_v3: EVM REVERTED] {:?}", output))
+        }
+        ExecutionResult::Halt { reason, .. } => {
+            return Err(anyhow!("[uniswap_v3: EVM HALT] {:?}", reason))
+        }
+    };
+
+    if output.len() < 32 {
+        return Err(anyhow!("[uniswap_v3: BadOutput] {:?}", output));
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base_token::BaseToken;
+    use ethers::{
+        providers::{Provider, Ws},
+        types::U64,
+    };
+    use foundry_evm::executor::fork::{BlockchainDb, BlockchainDbMeta};
+    use std::{collections::BTreeSet, sync::Arc};
+
+    static WSS_RPC: &str = "ws://localhost:8545";
+    static UNISWAP_V3_QUOTER: &str = "0xb27308f9F90D607463bb33eA1BeBb41C27CE5AB6";
+
+    /// native v3 swap math has to match what the pool pays out (quoted through uniswap's quoter)
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn v3_swap_math_matches_evm() {
+        let client = Arc::new(Provider::new(Ws::connect(WSS_RPC).await.unwrap()));
+
+        let pool = UniswapV3Pool::new_from_address(
+            "0x62CBac19051b130746Ec4CF96113aF5618F3A212"
+                .parse()
+                .unwrap(),
+            client.clone(),
+        )
+        .await
+        .unwrap();
+        let weth = BaseToken::weth().address;
+        let token: Address = "0x24C19F7101c1731b85F1127EaA0407732E36EcDD"
+            .parse()
+            .unwrap();
+
+        let shared_backend = SharedBackend::spawn_backend_thread(
+            client.clone(),
+            BlockchainDb::new(
+                BlockchainDbMeta {
+                    cfg_env: Default::default(),
+                    block_env: Default::default(),
+                    hosts: BTreeSet::from(["".to_string()]),
+                },
+                None,
+            ),
+            Some(U64::from(16863224).into()),
+        );
+        let mut evm = EVM::new();
+        evm.database(CacheDB::new(shared_backend));
+
+        let quoter = BaseContract::from(
+            parse_abi(&["function quoteExactInputSingle(address,address,uint24,uint256,uint160) external returns (uint256)"])
+                .unwrap(),
+        );
+        let quoter_address: Address = UNISWAP_V3_QUOTER.parse().unwrap();
+
+        for amount_in in [U256::exp10(15), U256::exp10(17), U256::exp10(19)] {
+            for (token_in, token_out, is_frontrun) in [(weth, token, true), (token, weth, false)] {
+                let native =
+                    v3_get_amount_out(amount_in, pool, weth, is_frontrun, &mut evm).unwrap();
+
+                evm.env.tx.transact_to = TransactTo::Call(quoter_address.0.into());
+                evm.env.tx.data = quoter
+                    .encode(
+                        "quoteExactInputSingle",
+                        (token_in, token_out, pool.fee, amount_in, U256::zero()),
+                    )
+                    .unwrap()
+                    .0;
+                let quoted: U256 = match evm.transact_ref().unwrap().result {
+                    ExecutionResult::Success {
+                        output: Output::Call(output),
+                        ..
+                    } => quoter
+                        .decode_output("quoteExactInputSingle", output)
+                        .unwrap(),
+                    result => panic!("quoter failed {:?}", result),
+                };
+
+                assert_eq!(native, quoted);
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use anyhow::{anyhow, Result};
+use cfmms::pool::{Pool::UniswapV2, UniswapV2Pool};
+use ethers::types::{Address, U256, U512};
+use foundry_evm::{
+    executor::fork::SharedBackend,
+    revm::{db::CacheDB, EVM},
+};
+
+use crate::{
+    tx_utils::{
+        huff_sandwich_interface::common::weth_encoder::WETH_ENCODING_MULTIPLE,
+        swap_interface::{decode_victim_swap, SwapProtocol, VictimSwap},
+    },
+    types::RawIngredients,
+};
+
+use super::{
+    huff_sandwich::{v2_fee_multiplier, v2_get_reserves, V2_FEE_DENOMINATOR},
+    lil_router::{evaluate_sandwich_revenue, SimulationSnapshot},
+    search_strategy::encodable_input,
+};
+
+/// How far (in parts per million) the simulated revenue may be off the closed form's before the
+/// pair/token is considered to not behave like a plain v2 pair (e.g. transfer tax)
+const CONFIRMATION_TOLERANCE_PPM: u64 = 1_000;
+
+/// Finds the optimal frontrun input of a sandwich on a plain v2 pair straight from the pair's
+/// reserves and the victim's router call, then confirms it with a single simulation
+/// Returns `None` when the sandwich can't be solved this way (multiple legs or meats, flash swap
+/// or inventory leg, victim not decodable, simulation disagrees), callers fall back to searching
+/// Errors if no frontrun is profitable (not sandwichable, nothing left to search)
+pub(crate) async fn find_closed_form_input(
+    ingredients: &RawIngredients,
+    inventory: U256,
+    snapshot: &SimulationSnapshot,
+) -> Result<Option<U256>> {
+    let (leg, meat) = match (
+        ingredients.get_legs().as_slice(),
+        ingredients.get_meats_ref().as_slice(),
+    ) {
+        ([leg], [meat]) => (leg, meat),
+        _ => return Ok(None),
+    };
+
+    let pool = match leg.target_pool {
+        UniswapV2(p) if leg.flash_pool.is_none() && leg.inventory_token.is_none() => p,
+        _ => return Ok(None),
+    };
+
+    // victim has to buy the intermediary token with the base token on this pair only
+    let base_token = leg.start_end_token.address;
+    let victim = match decode_victim_swap(meat) {
+        Some(swap)
+            if swap.protocol == SwapProtocol::UniswapV2
+                && swap.is_single_hop(base_token, leg.intermediary_token) =>
+        {
+            swap
+        }
+        _ => return Ok(None),
+    };
+
+    let mut evm = snapshot.clone().into_evm();
+    let sandwich = match V2Sandwich::new(pool, base_token, &victim, &mut evm)? {
+        Some(sandwich) => sandwich,
+        None => return Ok(None),
+    };
+
+    let frontrun_in = sandwich.optimal_encodable_frontrun_in(inventory);
+    let expected_revenue = sandwich.revenue(frontrun_in).unwrap_or_default();
+    if expected_revenue.is_zero() {
+        return Err(anyhow!(
+            "[closedForm: NoRevenue] victim's slippage leaves no room for a profitable frontrun"
+        ));
+    }
+
+    let revenue =
+        evaluate_sandwich_revenue(vec![frontrun_in], snapshot.clone(), ingredients.clone())
+            .await
+            .unwrap_or_default();
+
+    match is_confirmed(expected_revenue, revenue) {
+        true => Ok(Some(frontrun_in)),
+        false => Ok(None),
+    }
+}
+
+/// Does the simulated revenue agree with the closed form's (the pair's integer math is exact, so
+/// revenue above it is as suspicious as revenue below it)
+fn is_confirmed(expected_revenue: U256, simulated_revenue: U256) -> bool {
+    let tolerance = expected_revenue * CONFIRMATION_TOLERANCE_PPM / 1_000_000;
+    match simulated_revenue >= expected_revenue {
+        true => simulated_revenue - expected_revenue <= tolerance,
+        false => expected_revenue - simulated_revenue <= tolerance,
+    }
+}
+
+/// Sandwich on a plain v2 pair (same integer math as `UniswapV2Pair`)
+pub(super) struct V2Sandwich {
+    /// Pair's reserve of the base token
+    reserve_base: U256,
+    /// Pair's reserve of the intermediary token
+    reserve_token: U256,
+    /// Amount in multiplier after the pair's fee (out of `V2_FEE_DENOMINATOR`)
+    fee_multiplier: U256,
+    /// Base token the victim sells
+    victim_in: U256,
+    /// Least intermediary token the victim accepts
+    victim_out_min: U256,
+}
+
+impl V2Sandwich {
+    /// Sandwich of `victim` on `pool` at the pair's current reserves, `None` if the pair is empty
+    pub(super) fn new(
+        pool: UniswapV2Pool,
+        base_token: Address,
+        victim: &VictimSwap,
+        evm: &mut EVM<CacheDB<SharedBackend>>,
+    ) -> Result<Option<Self>> {
+        let (reserve_base, reserve_token) = v2_get_reserves(pool, base_token, true, evm)?;
+        if reserve_base.is_zero() || reserve_token.is_zero() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            reserve_base,
+            reserve_token,
+            fee_multiplier: v2_fee_multiplier(pool)?,
+            victim_in: victim.amount_in,
+            victim_out_min: victim.amount_out_min,
+        }))
+    }
+
+    /// Victim's output without a frontrun
+    pub(super) fn victim_expected_out(&self) -> U256 {
+        self.get_amount_out(self.victim_in, self.reserve_base, self.reserve_token)
+    }
+
+    /// Least intermediary token the victim accepts
+    pub(super) fn victim_out_min(&self) -> U256 {
+        self.victim_out_min
+    }
+
+    fn get_amount_out(&self, amount_in: U256, reserve_in: U256, reserve_out: U256) -> U256 {
+        let a_in_with_fee = amount_in * self.fee_multiplier;
+        (a_in_with_fee * reserve_out) / (reserve_in * V2_FEE_DENOMINATOR + a_in_with_fee)
+    }
+
+    /// Revenue of a sandwich with `frontrun_in`, `None` if the victim's swap would revert
+    fn revenue(&self, frontrun_in: U256) -> Option<U256> {
+        let frontrun_out = self.get_amount_out(frontrun_in, self.reserve_base, self.reserve_token);
+        let reserve_base = self.reserve_base + frontrun_in;
+        let reserve_token = self.reserve_token - frontrun_out;
+
+        let victim_out = self.get_amount_out(self.victim_in, reserve_base, reserve_token);
+        if victim_out < self.victim_out_min {
+            return None;
+        }
+        let reserve_base = reserve_base + self.victim_in;
+        let reserve_token = reserve_token - victim_out;
+
+        let backrun_out = self.get_amount_out(frontrun_out, reserve_token, reserve_base);
+        Some(backrun_out.saturating_sub(frontrun_in))
+    }
+
+    /// Largest frontrun that still leaves the victim its `amountOutMin`
+    //
+    // With x/y the base/token reserves, v/m the victim's amountIn/amountOutMin and g/D the fee
+    // multiplier, the victim's output after a frontrun of `a` is
+    //   g*v*y*x*D / ((D*x + g*a) * (D*(x + a) + g*v))
+    // so the bound is the positive root of
+    //   g*D*a^2 + (D^2*x + g*(D*x + g*v))*a + D*x*(D*x + g*v) - g*v*y*x*D/m = 0
+    fn max_frontrun_in(&self) -> U256 {
+        // victim without slippage protection is only bounded by inventory
+        if self.victim_out_min.is_zero() {
+            return U256::MAX;
+        }
+
+        let x = U512::from(self.reserve_base);
+        let y = U512::from(self.reserve_token);
+        let v = U512::from(self.victim_in);
+        let m = U512::from(self.victim_out_min);
+        let g = U512::from(self.fee_multiplier);
+        let d = U512::from(V2_FEE_DENOMINATOR);
+
+        let product_bound = g * v * y * x * d / m;
+        let constant = d * x * (d * x + g * v);
+        // victim reverts even without a frontrun
+        if constant > product_bound {
+            return U256::zero();
+        }
+
+        let a = g * d;
+        let b = d * d * x + g * (d * x + g * v);
+        let c = product_bound - constant;
+        let root = (b * b + U512::from(4) * a * c).integer_sqrt();
+
+        U256::try_from((root - b) / (U512::from(2) * a)).unwrap_or(U256::MAX)
+    }
+
+    /// Largest frontrun up to `max_input` that the victim's swap survives (exact)
+    pub(super) fn max_safe_frontrun_in(&self, max_input: U256) -> U256 {
+        let mut upper = self.max_frontrun_in().min(max_input);
+
+        // closed form bound can be a few wei off the pair's integer math, tighten it exactly
+        if self.revenue(upper).is_none() {
+            let mut lower = U256::zero();
+            while lower < upper {
+                let mid = upper - (upper - lower) / 2;
+                match self.revenue(mid) {
+                    Some(_) => lower = mid,
+                    None => upper = mid - 1,
+                }
+            }
+        }
+
+        upper
+    }
+
+    /// Most profitable frontrun up to `max_input`
+    fn optimal_frontrun_in(&self, max_input: U256) -> U256 {
+        let mut upper = self.max_safe_frontrun_in(max_input);
+
+        // revenue is concave below the bound (peaks there unless the victim's slippage is loose)
+        let mut lower = U256::zero();
+        while upper - lower > U256::from(2) {
+            let third = (upper - lower) / 3;
+            let (left, right) = (lower + third, upper - third);
+            match self.revenue(left) < self.revenue(right) {
+                true => lower = left,
+                false => upper = right,
+            }
+        }
+
+        let mut optimal = lower;
+        while optimal < upper && self.revenue(optimal + 1) > self.revenue(optimal) {
+            optimal += U256::one();
+        }
+        optimal
+    }
+
+    /// Most profitable frontrun up to `max_input` that the bundle can carry (revenue is concave,
+    /// so it is one of the two encodable inputs around the optimum)
+    fn optimal_encodable_frontrun_in(&self, max_input: U256) -> U256 {
+        let optimal = self.optimal_frontrun_in(max_input);
+        let below = encodable_input(optimal);
+        if below == optimal {
+            return optimal;
+        }
+
+        let above = encodable_input(optimal + *WETH_ENCODING_MULTIPLE);
+        match above <= max_input && self.revenue(above) > self.revenue(below) {
+            true => above,
+            false => below,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::parse_ether;
+
+    #[test]
+    fn simulation_within_tolerance_confirms_closed_form() {
+        let expected = parse_ether("1").unwrap();
+        let tolerance = expected * CONFIRMATION_TOLERANCE_PPM / 1_000_000;
+
+        assert!(is_confirmed(expected, expected));
+        assert!(is_confirmed(expected, expected - tolerance));
+        assert!(is_confirmed(expected, expected + tolerance));
+    }
+
+    /// transfer tax (or a fee above the configured one) eats into the backrun
+    #[test]
+    fn short_simulation_rejects_closed_form() {
+        let expected = parse_ether("1").unwrap();
+        let tolerance = expected * CONFIRMATION_TOLERANCE_PPM / 1_000_000;
+
+        assert!(!is_confirmed(expected, expected - tolerance - 1));
+        assert!(!is_confirmed(expected, U256::zero()));
+    }
+
+    /// revenue measured against the wrong starting balance overshoots any closed form
+    #[test]
+    fn excess_simulation_rejects_closed_form() {
+        let expected = parse_ether("1").unwrap();
+        let tolerance = expected * CONFIRMATION_TOLERANCE_PPM / 1_000_000;
+
+        assert!(!is_confirmed(expected, expected + tolerance + 1));
+        assert!(!is_confirmed(
+            expected,
+            expected + parse_ether("131").unwrap()
+        ));
+    }
+}
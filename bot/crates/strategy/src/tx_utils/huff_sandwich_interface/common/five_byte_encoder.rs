@@ -19,7 +19,7 @@ impl FiveByteMetaData {
 
         while byte_shift < 32 {
             // lossy encoding as we lose bits due to division
-            let encoded_amount = amount / 2u128.pow(8 * byte_shift as u32);
+            let encoded_amount = amount >> (8 * byte_shift as usize);
 
             // if we can fit the value in 4 bytes, we can encode it
             if encoded_amount <= U256::from(2).pow((4 * 8).into()) - 1 {
@@ -44,8 +44,12 @@ impl FiveByteMetaData {
 
     /// Decodes the 5 bytes back to a 32 byte value (lossy)
     pub fn decode(&self) -> U256 {
-        let value: u128 = (self.four_bytes as u128) << (self.byte_shift as u32 * 8);
-        return U256::from(value);
+        U256::from(self.four_bytes) << (self.byte_shift as usize * 8)
+    }
+
+    /// Largest value up to `amount` that survives encoding unchanged
+    pub fn truncate(amount: U256) -> U256 {
+        Self::encode(amount, 0).decode()
     }
 
     /// Finalize by encoding into five bytes for a specific param index
@@ -63,3 +67,30 @@ impl FiveByteMetaData {
         encoded
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::sample_amounts;
+    use super::*;
+
+    /// five byte encoding keeps the 4 most significant bytes, decoded values survive encoding
+    /// unchanged
+    #[test]
+    fn five_byte_encoder_round_trips() {
+        for amount in sample_amounts(256) {
+            let encoded = FiveByteMetaData::encode(amount, 0);
+            let decoded = encoded.decode();
+            assert!(decoded <= amount);
+            assert_eq!(FiveByteMetaData::truncate(amount), decoded);
+            assert_eq!(FiveByteMetaData::encode(decoded, 0).decode(), decoded);
+
+            match amount <= U256::from(u32::MAX) {
+                // small values are kept as is
+                true => assert_eq!(decoded, amount),
+                // only bytes below the 4 kept bytes are lost (shift is whole bytes, so up to 7 more
+                // bits than the bare 32 bit window)
+                false => assert!((amount - decoded) < U256::one() << (amount.bits() - 24)),
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 /// This Module file holds common methods used for both v2 and v3 methods
+use ethers::types::U256;
 
 /// Utils to encode (and decode) 32 bytes to 5 bytes of calldata
 pub mod five_byte_encoder;
@@ -6,28 +7,61 @@ pub mod five_byte_encoder;
 /// Utils to encode (and decode) weth to `tx.value`
 pub mod weth_encoder;
 
-// Declare the array as static
-static FUNCTION_NAMES: [&str; 8] = [
-    "v2_backrun0",
-    "v2_frontrun0",
-    "v2_backrun1",
-    "v2_frontrun1",
-    "v3_backrun0",
-    "v3_frontrun0",
-    "v3_backrun1",
-    "v3_frontrun1",
+// JUMPDEST of each function's astral gate (gates are 5 bytes apart, except for the padding that
+// puts the uniswap v2 callback on 0x10)
+static FUNCTION_JUMP_DESTS: [(&str, u8); 9] = [
+    ("v2_backrun0", 0x05),
+    ("v2_frontrun0", 0x0a),
+    ("v2_backrun1", 0x15),
+    ("v2_frontrun1", 0x1a),
+    ("v3_backrun0", 0x1f),
+    ("v3_frontrun0", 0x24),
+    ("v3_backrun1", 0x29),
+    ("v3_frontrun1", 0x2e),
+    ("v2_flash", 0x42),
 ];
 
 pub fn get_jump_dest_from_sig(function_name: &str) -> u8 {
-    let starting_index = 0x05;
-
-    // find index of associated JUMPDEST (sig)
-    for (i, &name) in FUNCTION_NAMES.iter().enumerate() {
+    // find associated JUMPDEST (sig)
+    for &(name, jump_dest) in FUNCTION_JUMP_DESTS.iter() {
         if name == function_name {
-            return (i as u8 * 5) + starting_index;
+            return jump_dest;
         }
     }
 
     // not found (force jump to invalid JUMPDEST)
     0x00
 }
+
+/// Encodes an exact coinbase bribe to be appended after a backrun payload (16 bytes)
+/// contract reads the last 16 bytes of calldata and transfers that amount to `block.coinbase`
+pub fn encode_coinbase_bribe(amount: U256) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    amount.to_big_endian(&mut bytes);
+    bytes[16..].to_vec()
+}
+
+/// Amounts spread over every magnitude (powers of two, their neighbours & pseudo random values)
+#[cfg(test)]
+fn sample_amounts(max_bits: usize) -> Vec<U256> {
+    let mut amounts = vec![U256::zero()];
+    for bits in 0..max_bits {
+        let power = U256::one() << bits;
+        amounts.extend([power - 1, power, power + 1]);
+    }
+
+    // xorshift, deterministic so failures reproduce
+    let mut state = 0x2545f4914f6cdd1d_u64;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..10_000 {
+        let value = U256([next(), next(), next(), next()]);
+        amounts.push(value >> (next() as usize % max_bits + (256 - max_bits)));
+    }
+
+    amounts
+}
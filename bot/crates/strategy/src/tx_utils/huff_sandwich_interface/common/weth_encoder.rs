@@ -11,8 +11,41 @@ impl WethEncoder {
         value / *WETH_ENCODING_MULTIPLE
     }
 
+    /// Encodes a weth value rounding up, used when the contract has to pay at least `value`
+    pub fn encode_ceil(value: U256) -> U256 {
+        (value + *WETH_ENCODING_MULTIPLE - 1) / *WETH_ENCODING_MULTIPLE
+    }
+
     /// Decodes by multiplying amount by weth constant
     pub fn decode(value: U256) -> U256 {
         value * *WETH_ENCODING_MULTIPLE
     }
+
+    /// Largest value up to `value` that survives encoding unchanged
+    pub fn truncate(value: U256) -> U256 {
+        Self::decode(Self::encode(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::sample_amounts;
+    use super::*;
+
+    /// values passed through `tx.value` decode to the largest multiple of the encoding constant
+    /// below them, and those multiples survive encoding unchanged
+    #[test]
+    fn weth_encoder_round_trips() {
+        for amount in sample_amounts(128) {
+            let truncated = WethEncoder::decode(WethEncoder::encode(amount));
+            assert!(truncated <= amount);
+            assert!(amount - truncated < *WETH_ENCODING_MULTIPLE);
+            assert_eq!(WethEncoder::truncate(amount), truncated);
+            assert_eq!(WethEncoder::truncate(truncated), truncated);
+
+            let ceiled = WethEncoder::decode(WethEncoder::encode_ceil(amount));
+            assert!(ceiled >= amount);
+            assert!(ceiled - amount < *WETH_ENCODING_MULTIPLE);
+        }
+    }
 }
@@ -5,43 +5,53 @@ use ethers::types::{Address, U256};
 use crate::constants::WETH_ADDRESS;
 
 use super::common::{
-    five_byte_encoder::FiveByteMetaData, get_jump_dest_from_sig, weth_encoder::WethEncoder,
+    encode_coinbase_bribe, five_byte_encoder::FiveByteMetaData, get_jump_dest_from_sig,
+    weth_encoder::WethEncoder,
 };
 
+/// dev: `base_token` is appended to the payload when it isn't weth (contract pays with it instead)
 pub fn v2_create_frontrun_payload(
     pool: UniswapV2Pool,
+    base_token: Address,
     output_token: Address,
     amount_in: U256,
     amount_out: U256, // amount_out is needed to be passed due to taxed tokens
 ) -> (Vec<u8>, U256) {
-    let jump_dest = get_jump_dest_from_sig(if *WETH_ADDRESS < output_token {
+    let jump_dest = get_jump_dest_from_sig(if base_token < output_token {
         "v2_frontrun0"
     } else {
         "v2_frontrun1"
     });
 
     let five_bytes =
-        FiveByteMetaData::encode(amount_out, if *WETH_ADDRESS < output_token { 1 } else { 0 });
+        FiveByteMetaData::encode(amount_out, if base_token < output_token { 1 } else { 0 });
 
-    let (payload, _) = eth_encode_packed::abi::encode_packed(&[
+    let (mut payload, _) = eth_encode_packed::abi::encode_packed(&[
         SolidityDataType::NumberWithShift(jump_dest.into(), TakeLastXBytes(8)),
         SolidityDataType::Address(pool.address().0.into()),
         SolidityDataType::Bytes(&five_bytes.finalize_to_bytes()),
     ]);
 
+    if base_token != *WETH_ADDRESS {
+        payload.extend(base_token.0);
+    }
+
     let encoded_call_value = WethEncoder::encode(amount_in);
 
     (payload, encoded_call_value)
 }
 
 /// dev: amount_out is needed to be passed due to taxed tokens
+/// dev: `coinbase_bribe` is appended to the payload and paid to `block.coinbase` by the contract
 pub fn v2_create_backrun_payload(
     pool: UniswapV2Pool,
+    base_token: Address,
     input_token: Address,
     amount_in: U256,
     amount_out: U256, // amount_out is needed to be passed due to taxed tokens
+    coinbase_bribe: Option<U256>,
 ) -> (Vec<u8>, U256) {
-    let jump_dest = get_jump_dest_from_sig(if *WETH_ADDRESS < input_token {
+    let jump_dest = get_jump_dest_from_sig(if base_token < input_token {
         "v2_backrun0"
     } else {
         "v2_backrun1"
@@ -49,14 +59,57 @@ pub fn v2_create_backrun_payload(
 
     let five_bytes = FiveByteMetaData::encode(amount_in, 1);
 
-    let (payload, _) = eth_encode_packed::abi::encode_packed(&[
+    let (mut payload, _) = eth_encode_packed::abi::encode_packed(&[
         SolidityDataType::NumberWithShift(jump_dest.into(), TakeLastXBytes(8)),
         SolidityDataType::Address(pool.address().0.into()),
         SolidityDataType::Address(input_token.0.into()),
         SolidityDataType::Bytes(&five_bytes.finalize_to_bytes()),
     ]);
 
+    if let Some(bribe) = coinbase_bribe {
+        payload.extend(encode_coinbase_bribe(bribe));
+    }
+
     let encoded_call_value = WethEncoder::encode(amount_out);
 
     (payload, encoded_call_value)
 }
+
+/// Borrow `token_amount` of `token` from `flash_pool`, sell it into `sell_pool` and repay
+/// `flash_pool` with `weth_repay` (used to sandwich `token->weth` trades without holding the token)
+/// dev: `weth_repay` is rounded up when encoded so that the flash pool's k check passes
+/// dev: `coinbase_bribe` is appended to the payload and paid to `block.coinbase` by the contract
+pub fn v2_create_flash_payload(
+    flash_pool: UniswapV2Pool,
+    sell_pool: UniswapV2Pool,
+    token: Address,
+    token_amount: U256,
+    weth_repay: U256,
+    weth_out: U256,
+    coinbase_bribe: Option<U256>,
+) -> (Vec<u8>, U256) {
+    let weth_out_five_bytes =
+        FiveByteMetaData::encode(weth_out, if *WETH_ADDRESS < token { 0 } else { 1 });
+    let token_out_five_bytes =
+        FiveByteMetaData::encode(token_amount, if token < *WETH_ADDRESS { 0 } else { 1 });
+
+    let (mut payload, _) = eth_encode_packed::abi::encode_packed(&[
+        SolidityDataType::NumberWithShift(
+            get_jump_dest_from_sig("v2_flash").into(),
+            TakeLastXBytes(8),
+        ),
+        SolidityDataType::Address(flash_pool.address().0.into()),
+        SolidityDataType::Address(sell_pool.address().0.into()),
+        SolidityDataType::Address(token.0.into()),
+        SolidityDataType::Bytes(&weth_out_five_bytes.finalize_to_bytes()),
+        SolidityDataType::Bytes(&token_out_five_bytes.finalize_to_bytes()),
+    ]);
+
+    if let Some(bribe) = coinbase_bribe {
+        payload.extend(encode_coinbase_bribe(bribe));
+    }
+
+    let encoded_call_value = WethEncoder::encode_ceil(weth_repay);
+
+    (payload, encoded_call_value)
+}
@@ -8,17 +8,20 @@ use ethers::{
 use crate::constants::WETH_ADDRESS;
 
 use super::common::{
-    five_byte_encoder::FiveByteMetaData, get_jump_dest_from_sig, weth_encoder::WethEncoder,
+    encode_coinbase_bribe, five_byte_encoder::FiveByteMetaData, get_jump_dest_from_sig,
+    weth_encoder::WethEncoder,
 };
 
+/// dev: `base_token` is appended to the payload when it isn't weth (contract pays with it instead)
 pub fn v3_create_frontrun_payload(
     pool: UniswapV3Pool,
+    base_token: Address,
     output_token: Address,
     amount_in: U256,
 ) -> (Vec<u8>, U256) {
-    let (payload, _) = eth_encode_packed::abi::encode_packed(&[
+    let (mut payload, _) = eth_encode_packed::abi::encode_packed(&[
         SolidityDataType::NumberWithShift(
-            get_jump_dest_from_sig(if *WETH_ADDRESS < output_token {
+            get_jump_dest_from_sig(if base_token < output_token {
                 "v3_frontrun0"
             } else {
                 "v3_frontrun1"
@@ -30,21 +33,28 @@ pub fn v3_create_frontrun_payload(
         SolidityDataType::Bytes(&get_pool_key_hash(pool).to_vec()),
     ]);
 
+    if base_token != *WETH_ADDRESS {
+        payload.extend(base_token.0);
+    }
+
     let encoded_value = WethEncoder::encode(amount_in);
 
     (payload, encoded_value)
 }
 
+/// dev: `coinbase_bribe` is appended to the payload and paid to `block.coinbase` by the contract
 pub fn v3_create_backrun_payload(
     pool: UniswapV3Pool,
+    base_token: Address,
     input_token: Address,
     amount_in: U256,
+    coinbase_bribe: Option<U256>,
 ) -> Vec<u8> {
     let five_bytes = FiveByteMetaData::encode(U256::from(amount_in), 2);
 
-    let (payload, _) = eth_encode_packed::abi::encode_packed(&[
+    let (mut payload, _) = eth_encode_packed::abi::encode_packed(&[
         SolidityDataType::NumberWithShift(
-            get_jump_dest_from_sig(if *WETH_ADDRESS < input_token {
+            get_jump_dest_from_sig(if base_token < input_token {
                 "v3_backrun0"
             } else {
                 "v3_backrun1"
@@ -58,6 +68,10 @@ pub fn v3_create_backrun_payload(
         SolidityDataType::Bytes(&five_bytes.finalize_to_bytes()),
     ]);
 
+    if let Some(bribe) = coinbase_bribe {
+        payload.extend(encode_coinbase_bribe(bribe));
+    }
+
     payload
 }
 
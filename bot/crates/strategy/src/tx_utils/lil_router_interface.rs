@@ -1,25 +1,28 @@
 use cfmms::pool::{UniswapV2Pool, UniswapV3Pool};
 use ethers::{abi::parse_abi, prelude::*};
 
-use crate::constants::WETH_ADDRESS;
-
 // Build the data for the lil_router contract's calculateSwapV2 function
-pub fn build_swap_v2_data(amount_in: U256, pool: UniswapV2Pool, is_frontrun: bool) -> Bytes {
+pub fn build_swap_v2_data(
+    amount_in: U256,
+    pool: UniswapV2Pool,
+    base_token: Address,
+    is_frontrun: bool,
+) -> Bytes {
     let lil_router_contract = BaseContract::from(parse_abi(&[
         "function calculateSwapV2(uint amountIn, address targetPair, address inputToken, address outputToken) external returns (uint amountOut, uint realAfterBalance)",
     ]).unwrap());
 
     let other_token = [pool.token_a, pool.token_b]
         .into_iter()
-        .find(|&t| t != *WETH_ADDRESS)
+        .find(|&t| t != base_token)
         .unwrap();
 
     let (input_token, output_token) = if is_frontrun {
-        // if frontrun we trade WETH -> TOKEN
-        (*WETH_ADDRESS, other_token)
+        // if frontrun we trade BASE -> TOKEN
+        (base_token, other_token)
     } else {
-        // if backrun we trade TOKEN -> WETH
-        (other_token, *WETH_ADDRESS)
+        // if backrun we trade TOKEN -> BASE
+        (other_token, base_token)
     };
 
     lil_router_contract
@@ -31,22 +34,27 @@ pub fn build_swap_v2_data(amount_in: U256, pool: UniswapV2Pool, is_frontrun: boo
 }
 
 // Build the data for the lil_router contract's calculateSwapV3 function
-pub fn build_swap_v3_data(amount_in: I256, pool: UniswapV3Pool, is_frontrun: bool) -> Bytes {
+pub fn build_swap_v3_data(
+    amount_in: I256,
+    pool: UniswapV3Pool,
+    base_token: Address,
+    is_frontrun: bool,
+) -> Bytes {
     let lil_router_contract = BaseContract::from(parse_abi(&[
         "function calculateSwapV3(int amountIn, address targetPoolAddress, address inputToken, address outputToken) public returns (uint amountOut, uint realAfterBalance)",
     ]).unwrap());
 
     let other_token = [pool.token_a, pool.token_b]
         .into_iter()
-        .find(|&t| t != *WETH_ADDRESS)
+        .find(|&t| t != base_token)
         .unwrap();
 
     let (input_token, output_token) = if is_frontrun {
-        // if frontrun we trade WETH -> TOKEN
-        (*WETH_ADDRESS, other_token)
+        // if frontrun we trade BASE -> TOKEN
+        (base_token, other_token)
     } else {
-        // if backrun we trade TOKEN -> WETH
-        (other_token, *WETH_ADDRESS)
+        // if backrun we trade TOKEN -> BASE
+        (other_token, base_token)
     };
 
     lil_router_contract
@@ -0,0 +1,369 @@
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, Transaction, U256};
+use foundry_evm::{
+    executor::{TransactTo, TxEnv},
+    revm::{db::DatabaseRef, primitives::CreateScheme},
+    utils::{h160_to_b160, ru256_to_u256},
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::Debug,
+};
+
+use crate::{block_env::ChainConfig, helpers::access_list_to_revm, types::BlockInfo};
+
+/// EIP-2718 transaction types
+const LEGACY_TX_TYPE: u64 = 0;
+const EIP2930_TX_TYPE: u64 = 1;
+const EIP1559_TX_TYPE: u64 = 2;
+const BLOB_TX_TYPE: u64 = 3;
+
+/// Most a meat pays per gas (`maxFeePerGas` for 1559 txs, `gasPrice` for the others)
+pub fn meat_max_fee(meat: &Transaction) -> U256 {
+    match meat.transaction_type.map(|tx_type| tx_type.as_u64()) {
+        Some(EIP1559_TX_TYPE) => meat.max_fee_per_gas,
+        _ => meat.gas_price,
+    }
+    .unwrap_or_default()
+}
+
+/// Converts a meat into the env it executes with (nonce included, so the evm checks it)
+/// Errors on tx types that can't go in a bundle: blob txs (mempool copies come without their
+/// sidecar, so they can't be rebroadcast) and unknown types
+pub fn meat_to_tx_env(meat: &Transaction) -> Result<TxEnv> {
+    let missing = |field| anyhow!("meat {:?} has no {}", meat.hash, field);
+
+    let (gas_price, gas_priority_fee) = match meat.transaction_type.map(|t| t.as_u64()) {
+        None | Some(LEGACY_TX_TYPE) | Some(EIP2930_TX_TYPE) => {
+            (meat.gas_price.ok_or_else(|| missing("gasPrice"))?, None)
+        }
+        Some(EIP1559_TX_TYPE) => (
+            meat.max_fee_per_gas
+                .ok_or_else(|| missing("maxFeePerGas"))?,
+            Some(
+                meat.max_priority_fee_per_gas
+                    .ok_or_else(|| missing("maxPriorityFeePerGas"))?,
+            ),
+        ),
+        Some(BLOB_TX_TYPE) => {
+            return Err(anyhow!(
+                "meat {:?} is a blob tx, can't bundle it",
+                meat.hash
+            ))
+        }
+        Some(tx_type) => {
+            return Err(anyhow!(
+                "meat {:?} has unsupported tx type {}",
+                meat.hash,
+                tx_type
+            ))
+        }
+    };
+
+    Ok(TxEnv {
+        caller: h160_to_b160(meat.from),
+        gas_limit: meat.gas.as_u64(),
+        gas_price: gas_price.into(),
+        gas_priority_fee: gas_priority_fee.map(|fee| fee.into()),
+        transact_to: match meat.to {
+            Some(to) => TransactTo::Call(h160_to_b160(to)),
+            None => TransactTo::Create(CreateScheme::Create),
+        },
+        value: meat.value.into(),
+        data: meat.input.0.clone(),
+        chain_id: meat.chain_id.map(|id| id.as_u64()),
+        nonce: Some(meat.nonce.as_u64()),
+        // legacy txs don't carry one
+        access_list: meat
+            .access_list
+            .clone()
+            .map(access_list_to_revm)
+            .unwrap_or_default(),
+    })
+}
+
+/// Checks that the meats can be included in `next_block` on top of `db` (the parent block's
+/// state): bundleable type, chain id, max fee against base fee, sender nonce & balance
+/// Meats from the same sender are checked in order, each one paying its full gas limit at its max
+/// fee (what the mempool checks too)
+pub fn validate_meats<D>(
+    meats: &[Transaction],
+    next_block: &BlockInfo,
+    chain: &ChainConfig,
+    db: &D,
+) -> Result<()>
+where
+    D: DatabaseRef,
+    D::Error: Debug,
+{
+    // sender => (next nonce, balance left)
+    let mut senders: HashMap<Address, (u64, U256)> = HashMap::new();
+
+    for meat in meats {
+        let tx_env = meat_to_tx_env(meat)?;
+
+        if let Some(chain_id) = tx_env.chain_id {
+            if chain_id != chain.chain_id {
+                return Err(anyhow!(
+                    "meat {:?} is for chain {}, not {}",
+                    meat.hash,
+                    chain_id,
+                    chain.chain_id
+                ));
+            }
+        }
+
+        let max_fee = ru256_to_u256(tx_env.gas_price);
+        if max_fee < next_block.base_fee_per_gas {
+            return Err(anyhow!(
+                "meat {:?} max fee {} is below base fee {}",
+                meat.hash,
+                max_fee,
+                next_block.base_fee_per_gas
+            ));
+        }
+
+        let (nonce, balance) = match senders.entry(meat.from) {
+            Entry::Occupied(sender) => sender.into_mut(),
+            Entry::Vacant(sender) => {
+                let account = db
+                    .basic(h160_to_b160(meat.from))
+                    .map_err(|e| anyhow!("failed to load meat sender {:?}: {:?}", meat.from, e))?
+                    .unwrap_or_default();
+                sender.insert((account.nonce, ru256_to_u256(account.balance)))
+            }
+        };
+
+        if meat.nonce != U256::from(*nonce) {
+            return Err(anyhow!(
+                "meat {:?} nonce {} doesn't follow sender's nonce {}",
+                meat.hash,
+                meat.nonce,
+                nonce
+            ));
+        }
+
+        let cost = U256::from(tx_env.gas_limit)
+            .checked_mul(max_fee)
+            .and_then(|gas_cost| gas_cost.checked_add(meat.value))
+            .ok_or_else(|| anyhow!("meat {:?} cost overflows", meat.hash))?;
+        if cost > *balance {
+            return Err(anyhow!(
+                "meat {:?} costs up to {} but sender only has {}",
+                meat.hash,
+                cost,
+                balance
+            ));
+        }
+
+        *nonce += 1;
+        *balance -= cost;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{
+        transaction::eip2930::{AccessList, AccessListItem},
+        H256, U64,
+    };
+    use foundry_evm::revm::{
+        db::{CacheDB, EmptyDB},
+        primitives::{AccountInfo, Bytecode, U256 as rU256},
+    };
+
+    /// every bundleable tx type converts to the env it executes with, blob txs and txs missing
+    /// their fee fields are rejected
+    #[test]
+    fn meat_to_tx_env_handles_tx_types() {
+        let access_list = AccessList(vec![AccessListItem {
+            address: "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+                .parse()
+                .unwrap(),
+            storage_keys: vec![H256::from_low_u64_be(3)],
+        }]);
+        let meat = Transaction {
+            from: Address::from_low_u64_be(1),
+            to: Some(
+                "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D"
+                    .parse()
+                    .unwrap(),
+            ),
+            nonce: U256::from(7),
+            gas: U256::from(200_000),
+            gas_price: Some(U256::from(30_000_000_000u64)),
+            chain_id: Some(U256::one()),
+            ..Default::default()
+        };
+
+        // legacy (with and without a type)
+        for transaction_type in [None, Some(U64::zero())] {
+            let tx_env = meat_to_tx_env(&Transaction {
+                transaction_type,
+                ..meat.clone()
+            })
+            .unwrap();
+            assert_eq!(tx_env.gas_price, rU256::from(30_000_000_000u64));
+            assert_eq!(tx_env.gas_priority_fee, None);
+            assert_eq!(tx_env.nonce, Some(7));
+            assert_eq!(tx_env.chain_id, Some(1));
+            assert!(tx_env.access_list.is_empty());
+        }
+
+        // eip-2930 keeps its gas price and access list
+        let tx_env = meat_to_tx_env(&Transaction {
+            transaction_type: Some(U64::from(1)),
+            access_list: Some(access_list.clone()),
+            ..meat.clone()
+        })
+        .unwrap();
+        assert_eq!(tx_env.gas_price, rU256::from(30_000_000_000u64));
+        assert_eq!(tx_env.gas_priority_fee, None);
+        assert_eq!(tx_env.access_list.len(), 1);
+        assert_eq!(tx_env.access_list[0].1, vec![rU256::from(3)]);
+
+        // eip-1559 pays up to its max fee
+        let tx_env = meat_to_tx_env(&Transaction {
+            transaction_type: Some(U64::from(2)),
+            max_fee_per_gas: Some(U256::from(40_000_000_000u64)),
+            max_priority_fee_per_gas: Some(U256::from(1_000_000_000u64)),
+            access_list: Some(access_list),
+            ..meat.clone()
+        })
+        .unwrap();
+        assert_eq!(tx_env.gas_price, rU256::from(40_000_000_000u64));
+        assert_eq!(tx_env.gas_priority_fee, Some(rU256::from(1_000_000_000u64)));
+        assert_eq!(tx_env.access_list.len(), 1);
+
+        // missing fee fields error instead of panicking
+        assert!(meat_to_tx_env(&Transaction {
+            gas_price: None,
+            ..meat.clone()
+        })
+        .is_err());
+        assert!(meat_to_tx_env(&Transaction {
+            transaction_type: Some(U64::from(2)),
+            ..meat.clone()
+        })
+        .is_err());
+
+        // blob txs can't go in a bundle
+        assert!(meat_to_tx_env(&Transaction {
+            transaction_type: Some(U64::from(3)),
+            ..meat
+        })
+        .is_err());
+    }
+
+    /// Sender with nonce 7 and 0.01 eth, a 30 gwei meat from them and the block it targets
+    fn setup() -> (Transaction, BlockInfo, ChainConfig, CacheDB<EmptyDB>) {
+        let sender = Address::from_low_u64_be(1);
+        let meat = Transaction {
+            from: sender,
+            to: Some(Address::from_low_u64_be(2)),
+            nonce: U256::from(7),
+            gas: U256::from(100_000),
+            gas_price: Some(U256::from(30_000_000_000u64)),
+            chain_id: Some(U256::one()),
+            ..Default::default()
+        };
+        let next_block = BlockInfo {
+            base_fee_per_gas: U256::from(20_000_000_000u64),
+            ..Default::default()
+        };
+
+        let mut db = CacheDB::new(EmptyDB::default());
+        db.insert_account_info(
+            h160_to_b160(sender),
+            AccountInfo::new(
+                rU256::from(10_000_000_000_000_000u64),
+                7,
+                Bytecode::default(),
+            ),
+        );
+
+        (meat, next_block, ChainConfig::default(), db)
+    }
+
+    #[test]
+    fn validate_meats_accepts_includable_meats() {
+        let (meat, next_block, chain, db) = setup();
+        let next_meat = Transaction {
+            nonce: U256::from(8),
+            ..meat.clone()
+        };
+
+        assert!(validate_meats(&[meat, next_meat], &next_block, &chain, &db).is_ok());
+    }
+
+    #[test]
+    fn validate_meats_rejects_other_chain() {
+        let (meat, next_block, chain, db) = setup();
+        let meat = Transaction {
+            chain_id: Some(U256::from(5)),
+            ..meat
+        };
+
+        let err = validate_meats(&[meat], &next_block, &chain, &db).unwrap_err();
+        assert!(err.to_string().contains("is for chain 5"), "{}", err);
+    }
+
+    #[test]
+    fn validate_meats_rejects_max_fee_below_base_fee() {
+        let (meat, next_block, chain, db) = setup();
+        let next_block = BlockInfo {
+            base_fee_per_gas: U256::from(31_000_000_000u64),
+            ..next_block
+        };
+
+        let err = validate_meats(&[meat], &next_block, &chain, &db).unwrap_err();
+        assert!(err.to_string().contains("is below base fee"), "{}", err);
+    }
+
+    /// nonces have to start at the sender's and follow each other without gaps or repeats
+    #[test]
+    fn validate_meats_rejects_out_of_order_nonce() {
+        let (meat, next_block, chain, db) = setup();
+
+        for nonces in [vec![6], vec![8], vec![7, 7], vec![7, 9]] {
+            let meats: Vec<Transaction> = nonces
+                .into_iter()
+                .map(|nonce| Transaction {
+                    nonce: U256::from(nonce),
+                    ..meat.clone()
+                })
+                .collect();
+
+            let err = validate_meats(&meats, &next_block, &chain, &db).unwrap_err();
+            assert!(err.to_string().contains("doesn't follow"), "{}", err);
+        }
+    }
+
+    /// each meat pays its full gas limit at its max fee out of what the previous ones left
+    #[test]
+    fn validate_meats_rejects_unaffordable_meat() {
+        let (meat, next_block, chain, db) = setup();
+
+        // 0.003 eth of gas each, the fourth one is over the sender's 0.01 eth
+        let meats: Vec<Transaction> = (7..11)
+            .map(|nonce| Transaction {
+                nonce: U256::from(nonce),
+                ..meat.clone()
+            })
+            .collect();
+        assert!(validate_meats(&meats[..3], &next_block, &chain, &db).is_ok());
+        let err = validate_meats(&meats, &next_block, &chain, &db).unwrap_err();
+        assert!(err.to_string().contains("but sender only has"), "{}", err);
+
+        // value counts towards the cost too
+        let meat = Transaction {
+            value: U256::exp10(16),
+            ..meat
+        };
+        let err = validate_meats(&[meat], &next_block, &chain, &db).unwrap_err();
+        assert!(err.to_string().contains("but sender only has"), "{}", err);
+    }
+}
@@ -1,2 +1,7 @@
 pub(crate) mod huff_sandwich_interface;
 pub(crate) mod lil_router_interface;
+pub mod meat;
+pub(crate) mod swap_interface;
+pub(crate) mod uniswap_v2_router_interface;
+pub(crate) mod uniswap_v3_router_interface;
+pub(crate) mod universal_router_interface;
@@ -0,0 +1,164 @@
+use ethers::{
+    abi::{parse_abi, Detokenize},
+    prelude::*,
+};
+
+use crate::constants::KNOWN_SWAP_ROUTERS;
+
+use super::{
+    uniswap_v2_router_interface::decode_v2_router_swap,
+    uniswap_v3_router_interface::decode_v3_router_swap,
+    universal_router_interface::decode_universal_router_swap,
+};
+
+static MULTICALL_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(
+        parse_abi(&[
+            "function multicall(uint256 deadline, bytes[] data) external payable returns (bytes[] results)",
+        ])
+        .unwrap(),
+    )
+});
+
+static MULTICALL_NO_DEADLINE_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(
+        parse_abi(&["function multicall(bytes[] data) external payable returns (bytes[] results)"])
+            .unwrap(),
+    )
+});
+
+/// Which kind of pools a victim's swap goes through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapProtocol {
+    UniswapV2,
+    UniswapV3,
+}
+
+/// Exact input swap decoded from a victim's calldata
+#[derive(Debug, Clone)]
+pub struct VictimSwap {
+    pub protocol: SwapProtocol,
+    pub amount_in: U256,
+    pub amount_out_min: U256,
+    /// Tokens swapped through, from token in to token out
+    pub path: Vec<Address>,
+    /// Fee tier of each hop (v3 only, empty for v2)
+    pub fees: Vec<u32>,
+}
+
+impl VictimSwap {
+    /// True if the swap is a single hop from `token_in` to `token_out`
+    pub fn is_single_hop(&self, token_in: Address, token_out: Address) -> bool {
+        self.path == [token_in, token_out]
+    }
+}
+
+// Decode a victim's exact input swap through the uniswap v2 router, the v3 swap routers (including
+// swaps wrapped in a `multicall`) or the universal router
+//
+// Returns:
+// Some(VictimSwap): if `tx` makes exactly one exact input swap through a known router
+// None: for any other call, or calls to other contracts (which may share a router's selectors)
+pub fn decode_victim_swap(tx: &Transaction) -> Option<VictimSwap> {
+    if !tx.to.map_or(false, |to| KNOWN_SWAP_ROUTERS.contains(&to)) {
+        return None;
+    }
+
+    decode_swap_call(&tx.input, tx.value)
+}
+
+fn decode_swap_call(input: &[u8], value: U256) -> Option<VictimSwap> {
+    if let Some(calls) = decode_multicall(input) {
+        // a single path can't describe several swaps
+        let mut swaps = calls
+            .iter()
+            .filter_map(|call| decode_swap_call(call, value));
+        return match (swaps.next(), swaps.next()) {
+            (Some(swap), None) => Some(swap),
+            _ => None,
+        };
+    }
+
+    decode_v2_router_swap(input, value)
+        .or_else(|| decode_v3_router_swap(input))
+        .or_else(|| decode_universal_router_swap(input, value))
+}
+
+fn decode_multicall(input: &[u8]) -> Option<Vec<Bytes>> {
+    decode_call::<(U256, Vec<Bytes>)>(&MULTICALL_ABI, "multicall", input)
+        .map(|(_, calls)| calls)
+        .or_else(|| decode_call(&MULTICALL_NO_DEADLINE_ABI, "multicall", input))
+}
+
+/// Decode the arguments of `input` if it calls `name` on `contract`
+pub(super) fn decode_call<D: Detokenize>(
+    contract: &BaseContract,
+    name: &str,
+    input: &[u8],
+) -> Option<D> {
+    let function = contract.abi().function(name).ok()?;
+    if input.get(0..4)? != function.short_signature() {
+        return None;
+    }
+    contract.decode(name, input).ok()
+}
+
+/// Decode a v3 encoded path (`token | fee | token | fee | ... | token`)
+/// Returns the tokens and the fee of each hop
+pub(super) fn decode_v3_path(path: &[u8]) -> Option<(Vec<Address>, Vec<u32>)> {
+    const ADDRESS_SIZE: usize = 20;
+    const FEE_SIZE: usize = 3;
+
+    if path.len() < ADDRESS_SIZE || (path.len() - ADDRESS_SIZE) % (ADDRESS_SIZE + FEE_SIZE) != 0 {
+        return None;
+    }
+
+    let mut tokens = vec![Address::from_slice(&path[..ADDRESS_SIZE])];
+    let mut fees = vec![];
+    for hop in path[ADDRESS_SIZE..].chunks(ADDRESS_SIZE + FEE_SIZE) {
+        fees.push(u32::from_be_bytes([0, hop[0], hop[1], hop[2]]));
+        tokens.push(Address::from_slice(&hop[FEE_SIZE..]));
+    }
+
+    Some((tokens, fees))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap_tx(to: Address) -> Transaction {
+        let router = BaseContract::from(parse_abi(&[
+            "function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline) external returns (uint256[] amounts)",
+        ]).unwrap());
+        let input = router
+            .encode(
+                "swapExactTokensForTokens",
+                (
+                    U256::exp10(18),
+                    U256::one(),
+                    vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)],
+                    Address::zero(),
+                    U256::MAX,
+                ),
+            )
+            .unwrap();
+
+        Transaction {
+            to: Some(to),
+            input,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn swaps_through_known_routers_are_decoded() {
+        assert!(decode_victim_swap(&swap_tx(KNOWN_SWAP_ROUTERS[0])).is_some());
+    }
+
+    /// any contract can expose a router's selectors, only the routers' swaps are trusted
+    #[test]
+    fn calls_to_other_contracts_are_skipped() {
+        assert!(decode_victim_swap(&swap_tx(Address::from_low_u64_be(1))).is_none());
+    }
+}
@@ -0,0 +1,104 @@
+use ethers::{abi::parse_abi, prelude::*};
+
+use super::swap_interface::{decode_call, SwapProtocol, VictimSwap};
+
+static V2_ROUTER_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(parse_abi(&[
+        "function swapExactTokensForTokens(uint amountIn, uint amountOutMin, address[] path, address to, uint deadline) external returns (uint[] amounts)",
+        "function swapExactETHForTokens(uint amountOutMin, address[] path, address to, uint deadline) external payable returns (uint[] amounts)",
+    ]).unwrap())
+});
+
+// `SwapRouter02` exposes the v2 swap without a deadline (checked by its `multicall` instead), an
+// `amountIn` of 0 (`CONTRACT_BALANCE`) swaps whatever the router holds
+static SWAP_ROUTER_02_V2_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(parse_abi(&[
+        "function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to) external payable returns (uint256 amountOut)",
+    ]).unwrap())
+});
+
+// Decode a victim's exact input swap through a uniswap v2 style router
+// note: fee on transfer variants are left out, their output can't be known from reserves
+//
+// Arguments:
+// * `input`: calldata of the call
+// * `value`: eth sent along with the call
+//
+// Returns:
+// Some(VictimSwap): if `input` calls `swapExactTokensForTokens` or `swapExactETHForTokens`
+// None: for any other call, or if the amount in is the router's balance (not known from calldata)
+pub fn decode_v2_router_swap(input: &[u8], value: U256) -> Option<VictimSwap> {
+    let v2_swap = |amount_in, amount_out_min, path| VictimSwap {
+        protocol: SwapProtocol::UniswapV2,
+        amount_in,
+        amount_out_min,
+        path,
+        fees: vec![],
+    };
+
+    if let Some((amount_in, amount_out_min, path, _, _)) =
+        decode_call::<(U256, U256, Vec<Address>, Address, U256)>(
+            &V2_ROUTER_ABI,
+            "swapExactTokensForTokens",
+            input,
+        )
+    {
+        return Some(v2_swap(amount_in, amount_out_min, path));
+    }
+
+    if let Some((amount_out_min, path, _, _)) = decode_call::<(U256, Vec<Address>, Address, U256)>(
+        &V2_ROUTER_ABI,
+        "swapExactETHForTokens",
+        input,
+    ) {
+        return Some(v2_swap(value, amount_out_min, path));
+    }
+
+    if let Some((amount_in, amount_out_min, path, _)) =
+        decode_call::<(U256, U256, Vec<Address>, Address)>(
+            &SWAP_ROUTER_02_V2_ABI,
+            "swapExactTokensForTokens",
+            input,
+        )
+    {
+        if amount_in.is_zero() {
+            return None;
+        }
+        return Some(v2_swap(amount_in, amount_out_min, path));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router_02_swap(amount_in: U256, path: Vec<Address>) -> Bytes {
+        SWAP_ROUTER_02_V2_ABI
+            .encode(
+                "swapExactTokensForTokens",
+                (amount_in, U256::one(), path, Address::zero()),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn router_02_swap_decodes_amount_in() {
+        let path = vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)];
+        let amount_in = U256::exp10(18);
+
+        let swap =
+            decode_v2_router_swap(&router_02_swap(amount_in, path.clone()), U256::zero()).unwrap();
+        assert_eq!(swap.amount_in, amount_in);
+        assert_eq!(swap.path, path);
+    }
+
+    /// swaps of the router's own balance don't say how much the victim puts in
+    #[test]
+    fn router_02_contract_balance_swap_is_skipped() {
+        let path = vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)];
+
+        assert!(decode_v2_router_swap(&router_02_swap(U256::zero(), path), U256::zero()).is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use ethers::{abi::parse_abi, prelude::*};
+
+use super::swap_interface::{decode_call, decode_v3_path, SwapProtocol, VictimSwap};
+
+static SWAP_ROUTER_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(parse_abi(&[
+        "function exactInputSingle((address,address,uint24,address,uint256,uint256,uint256,uint160) params) external payable returns (uint256 amountOut)",
+        "function exactInput((bytes,address,uint256,uint256,uint256) params) external payable returns (uint256 amountOut)",
+    ]).unwrap())
+});
+
+// `SwapRouter02` dropped the deadline from the params (checked by its `multicall` instead), an
+// `amountIn` of 0 (`CONTRACT_BALANCE`) swaps whatever the router holds
+static SWAP_ROUTER_02_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(parse_abi(&[
+        "function exactInputSingle((address,address,uint24,address,uint256,uint256,uint160) params) external payable returns (uint256 amountOut)",
+        "function exactInput((bytes,address,uint256,uint256) params) external payable returns (uint256 amountOut)",
+    ]).unwrap())
+});
+
+// Decode a victim's exact input swap through uniswap's v3 `SwapRouter` or `SwapRouter02`
+//
+// Arguments:
+// * `input`: calldata of the call (v3 routers always take an explicit `amountIn`, even for eth)
+//
+// Returns:
+// Some(VictimSwap): if `input` calls `exactInputSingle` or `exactInput`
+// None: for any other call, or if the amount in is the router's balance (not known from calldata)
+pub fn decode_v3_router_swap(input: &[u8]) -> Option<VictimSwap> {
+    let v3_swap = |amount_in, amount_out_min, (path, fees)| VictimSwap {
+        protocol: SwapProtocol::UniswapV3,
+        amount_in,
+        amount_out_min,
+        path,
+        fees,
+    };
+
+    if let Some((token_in, token_out, fee, _, _, amount_in, amount_out_min, _)) =
+        decode_call::<(Address, Address, u32, Address, U256, U256, U256, U256)>(
+            &SWAP_ROUTER_ABI,
+            "exactInputSingle",
+            input,
+        )
+    {
+        return Some(v3_swap(
+            amount_in,
+            amount_out_min,
+            (vec![token_in, token_out], vec![fee]),
+        ));
+    }
+
+    if let Some((path, _, _, amount_in, amount_out_min)) =
+        decode_call::<(Bytes, Address, U256, U256, U256)>(&SWAP_ROUTER_ABI, "exactInput", input)
+    {
+        return Some(v3_swap(amount_in, amount_out_min, decode_v3_path(&path)?));
+    }
+
+    if let Some((token_in, token_out, fee, _, amount_in, amount_out_min, _)) =
+        decode_call::<(Address, Address, u32, Address, U256, U256, U256)>(
+            &SWAP_ROUTER_02_ABI,
+            "exactInputSingle",
+            input,
+        )
+    {
+        if amount_in.is_zero() {
+            return None;
+        }
+        return Some(v3_swap(
+            amount_in,
+            amount_out_min,
+            (vec![token_in, token_out], vec![fee]),
+        ));
+    }
+
+    if let Some((path, _, amount_in, amount_out_min)) =
+        decode_call::<(Bytes, Address, U256, U256)>(&SWAP_ROUTER_02_ABI, "exactInput", input)
+    {
+        if amount_in.is_zero() {
+            return None;
+        }
+        return Some(v3_swap(amount_in, amount_out_min, decode_v3_path(&path)?));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(tokens: &[Address], fee: u32) -> Bytes {
+        let mut path = tokens[0].as_bytes().to_vec();
+        for token in &tokens[1..] {
+            path.extend(&fee.to_be_bytes()[1..]);
+            path.extend(token.as_bytes());
+        }
+        path.into()
+    }
+
+    #[test]
+    fn router_02_swaps_decode_amount_in() {
+        let (token_in, token_out) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let amount_in = U256::exp10(18);
+
+        let single = SWAP_ROUTER_02_ABI
+            .encode(
+                "exactInputSingle",
+                ((
+                    token_in,
+                    token_out,
+                    3000u32,
+                    Address::zero(),
+                    amount_in,
+                    U256::one(),
+                    U256::zero(),
+                ),),
+            )
+            .unwrap();
+        let swap = decode_v3_router_swap(&single).unwrap();
+        assert_eq!(swap.amount_in, amount_in);
+        assert!(swap.is_single_hop(token_in, token_out));
+
+        let multi = SWAP_ROUTER_02_ABI
+            .encode(
+                "exactInput",
+                ((
+                    path(&[token_in, token_out], 500),
+                    Address::zero(),
+                    amount_in,
+                    U256::one(),
+                ),),
+            )
+            .unwrap();
+        let swap = decode_v3_router_swap(&multi).unwrap();
+        assert_eq!(swap.amount_in, amount_in);
+        assert_eq!(swap.fees, vec![500]);
+    }
+
+    /// swaps of the router's own balance don't say how much the victim puts in
+    #[test]
+    fn router_02_contract_balance_swaps_are_skipped() {
+        let (token_in, token_out) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+
+        let single = SWAP_ROUTER_02_ABI
+            .encode(
+                "exactInputSingle",
+                ((
+                    token_in,
+                    token_out,
+                    3000u32,
+                    Address::zero(),
+                    U256::zero(),
+                    U256::one(),
+                    U256::zero(),
+                ),),
+            )
+            .unwrap();
+        assert!(decode_v3_router_swap(&single).is_none());
+
+        let multi = SWAP_ROUTER_02_ABI
+            .encode(
+                "exactInput",
+                ((
+                    path(&[token_in, token_out], 500),
+                    Address::zero(),
+                    U256::zero(),
+                    U256::one(),
+                ),),
+            )
+            .unwrap();
+        assert!(decode_v3_router_swap(&multi).is_none());
+    }
+}
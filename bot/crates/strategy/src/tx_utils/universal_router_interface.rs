@@ -0,0 +1,257 @@
+use ethers::{
+    abi::{self, parse_abi, ParamType},
+    prelude::*,
+};
+
+use super::swap_interface::{decode_call, decode_v3_path, SwapProtocol, VictimSwap};
+
+static UNIVERSAL_ROUTER_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(
+        parse_abi(&[
+            "function execute(bytes commands, bytes[] inputs, uint256 deadline) external payable",
+        ])
+        .unwrap(),
+    )
+});
+
+static UNIVERSAL_ROUTER_NO_DEADLINE_ABI: Lazy<BaseContract> = Lazy::new(|| {
+    BaseContract::from(
+        parse_abi(&["function execute(bytes commands, bytes[] inputs) external payable"]).unwrap(),
+    )
+});
+
+/// Universal router commands (lower 6 bits of each command byte, upper bits are flags)
+const COMMAND_TYPE_MASK: u8 = 0x3f;
+const V3_SWAP_EXACT_IN: u8 = 0x00;
+const V2_SWAP_EXACT_IN: u8 = 0x08;
+
+/// `amountIn` placeholder for "everything the router holds" (e.g. eth wrapped by a previous command)
+static CONTRACT_BALANCE: Lazy<U256> = Lazy::new(|| U256::one() << 255);
+
+// Decode a victim's exact input swap through uniswap's universal router
+// note: only commands with a single exact input swap are decoded (split routes can't be
+// described by one path)
+//
+// Arguments:
+// * `input`: calldata of the call
+// * `value`: eth sent along with the call (swapped when the swap spends the router's balance)
+//
+// Returns:
+// Some(VictimSwap): if `input` calls `execute` with exactly one `V2_SWAP_EXACT_IN` or
+// `V3_SWAP_EXACT_IN` command
+// None: for any other call, or if the swap spends the router's balance and no eth was sent
+pub fn decode_universal_router_swap(input: &[u8], value: U256) -> Option<VictimSwap> {
+    let (commands, inputs) =
+        decode_call::<(Bytes, Vec<Bytes>, U256)>(&UNIVERSAL_ROUTER_ABI, "execute", input)
+            .map(|(commands, inputs, _)| (commands, inputs))
+            .or_else(|| decode_call(&UNIVERSAL_ROUTER_NO_DEADLINE_ABI, "execute", input))?;
+
+    let mut swaps = commands
+        .iter()
+        .zip(inputs.iter())
+        .filter_map(|(command, input)| match command & COMMAND_TYPE_MASK {
+            V2_SWAP_EXACT_IN => Some(decode_v2_swap_exact_in(input)),
+            V3_SWAP_EXACT_IN => Some(decode_v3_swap_exact_in(input)),
+            _ => None,
+        });
+
+    let mut swap = match (swaps.next(), swaps.next()) {
+        (Some(swap), None) => swap?,
+        _ => return None,
+    };
+
+    // only eth sent along is known from calldata, tokens moved in by other commands (e.g. permit2
+    // transfers) aren't
+    if swap.amount_in == *CONTRACT_BALANCE {
+        if value.is_zero() {
+            return None;
+        }
+        swap.amount_in = value;
+    }
+
+    Some(swap)
+}
+
+// `V2_SWAP_EXACT_IN` input: (recipient, amountIn, amountOutMin, path, payerIsUser)
+fn decode_v2_swap_exact_in(input: &[u8]) -> Option<VictimSwap> {
+    let tokens = abi::decode(
+        &[
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Array(Box::new(ParamType::Address)),
+            ParamType::Bool,
+        ],
+        input,
+    )
+    .ok()?;
+
+    Some(VictimSwap {
+        protocol: SwapProtocol::UniswapV2,
+        amount_in: tokens[1].clone().into_uint()?,
+        amount_out_min: tokens[2].clone().into_uint()?,
+        path: tokens[3]
+            .clone()
+            .into_array()?
+            .into_iter()
+            .map(|token| token.into_address())
+            .collect::<Option<Vec<_>>>()?,
+        fees: vec![],
+    })
+}
+
+// `V3_SWAP_EXACT_IN` input: (recipient, amountIn, amountOutMin, path, payerIsUser)
+fn decode_v3_swap_exact_in(input: &[u8]) -> Option<VictimSwap> {
+    let tokens = abi::decode(
+        &[
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Bytes,
+            ParamType::Bool,
+        ],
+        input,
+    )
+    .ok()?;
+    let (path, fees) = decode_v3_path(&tokens[3].clone().into_bytes()?)?;
+
+    Some(VictimSwap {
+        protocol: SwapProtocol::UniswapV3,
+        amount_in: tokens[1].clone().into_uint()?,
+        amount_out_min: tokens[2].clone().into_uint()?,
+        path,
+        fees,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::Token;
+
+    use super::*;
+
+    fn path() -> Vec<Address> {
+        vec![Address::from_low_u64_be(1), Address::from_low_u64_be(2)]
+    }
+
+    fn v2_swap_exact_in(amount_in: U256, path: Vec<Address>) -> Bytes {
+        abi::encode(&[
+            Token::Address(Address::zero()),
+            Token::Uint(amount_in),
+            Token::Uint(U256::one()),
+            Token::Array(path.into_iter().map(Token::Address).collect()),
+            Token::Bool(true),
+        ])
+        .into()
+    }
+
+    fn execute(commands: Vec<u8>, inputs: Vec<Bytes>) -> Bytes {
+        UNIVERSAL_ROUTER_ABI
+            .encode("execute", (Bytes::from(commands), inputs, U256::MAX))
+            .unwrap()
+    }
+
+    #[test]
+    fn execute_with_deadline_decodes_swap() {
+        let amount_in = U256::exp10(18);
+        let input = execute(
+            vec![V2_SWAP_EXACT_IN],
+            vec![v2_swap_exact_in(amount_in, path())],
+        );
+
+        let swap = decode_universal_router_swap(&input, U256::zero()).unwrap();
+        assert!(matches!(swap.protocol, SwapProtocol::UniswapV2));
+        assert_eq!(swap.amount_in, amount_in);
+        assert_eq!(swap.amount_out_min, U256::one());
+        assert_eq!(swap.path, path());
+    }
+
+    #[test]
+    fn execute_without_deadline_decodes_swap() {
+        let amount_in = U256::exp10(18);
+        let input = UNIVERSAL_ROUTER_NO_DEADLINE_ABI
+            .encode(
+                "execute",
+                (
+                    Bytes::from(vec![V2_SWAP_EXACT_IN]),
+                    vec![v2_swap_exact_in(amount_in, path())],
+                ),
+            )
+            .unwrap();
+
+        let swap = decode_universal_router_swap(&input, U256::zero()).unwrap();
+        assert_eq!(swap.amount_in, amount_in);
+        assert_eq!(swap.path, path());
+    }
+
+    #[test]
+    fn v3_swap_decodes_path_and_fees() {
+        let mut encoded_path = path()[0].as_bytes().to_vec();
+        encoded_path.extend_from_slice(&[0x00, 0x0b, 0xb8]);
+        encoded_path.extend_from_slice(path()[1].as_bytes());
+        let swap_input = abi::encode(&[
+            Token::Address(Address::zero()),
+            Token::Uint(U256::exp10(18)),
+            Token::Uint(U256::one()),
+            Token::Bytes(encoded_path),
+            Token::Bool(true),
+        ]);
+        let input = execute(vec![V3_SWAP_EXACT_IN], vec![swap_input.into()]);
+
+        let swap = decode_universal_router_swap(&input, U256::zero()).unwrap();
+        assert!(matches!(swap.protocol, SwapProtocol::UniswapV3));
+        assert_eq!(swap.path, path());
+        assert_eq!(swap.fees, vec![3000]);
+    }
+
+    /// the upper bits of a command (e.g. "allow revert") don't change its type
+    #[test]
+    fn command_flags_are_masked() {
+        let input = execute(
+            vec![0x80 | V2_SWAP_EXACT_IN],
+            vec![v2_swap_exact_in(U256::exp10(18), path())],
+        );
+
+        assert!(decode_universal_router_swap(&input, U256::zero()).is_some());
+    }
+
+    /// e.g. `WRAP_ETH` followed by a swap of the wrapped eth
+    #[test]
+    fn contract_balance_swaps_the_eth_sent() {
+        let value = U256::exp10(17);
+        let input = execute(
+            vec![0x0b, V2_SWAP_EXACT_IN],
+            vec![
+                Bytes::default(),
+                v2_swap_exact_in(*CONTRACT_BALANCE, path()),
+            ],
+        );
+
+        let swap = decode_universal_router_swap(&input, value).unwrap();
+        assert_eq!(swap.amount_in, value);
+    }
+
+    #[test]
+    fn contract_balance_swap_without_eth_is_skipped() {
+        let input = execute(
+            vec![V2_SWAP_EXACT_IN],
+            vec![v2_swap_exact_in(*CONTRACT_BALANCE, path())],
+        );
+
+        assert!(decode_universal_router_swap(&input, U256::zero()).is_none());
+    }
+
+    /// split routes can't be described by one path
+    #[test]
+    fn multiple_swaps_are_skipped() {
+        let input = execute(
+            vec![V2_SWAP_EXACT_IN, V2_SWAP_EXACT_IN],
+            vec![
+                v2_swap_exact_in(U256::exp10(18), path()),
+                v2_swap_exact_in(U256::exp10(18), path()),
+            ],
+        );
+
+        assert!(decode_universal_router_swap(&input, U256::zero()).is_none());
+    }
+}
@@ -1,23 +1,26 @@
-use std::sync::Arc;
-
 use anyhow::ensure;
 use anyhow::{anyhow, Result};
 use artemis_core::{
     collectors::block_collector::NewBlock, executors::flashbots_executor::FlashbotsBundle,
 };
-use cfmms::pool::Pool;
-use ethers::providers::Middleware;
+use cfmms::pool::{Pool, UniswapV2Pool};
 use ethers::signers::LocalWallet;
-use ethers::signers::Signer;
 use ethers::types::{
     Address, Block, Bytes, Eip1559TransactionRequest, Transaction, H256, U256, U64,
 };
 use ethers_flashbots::BundleRequest;
 use foundry_evm::executor::TxEnv;
 
+use crate::base_token::{BaseToken, BaseTokenConfig};
+use crate::block_env::ChainConfig;
+use crate::bribe_policy::{BribePayment, BribePolicyConfig};
 use crate::constants::DUST_OVERPAY;
+use crate::guardrails::GuardrailConfig;
 use crate::helpers::access_list_to_ethers;
 use crate::helpers::sign_eip1559;
+use crate::inventory::InventoryConfig;
+use crate::simulator::search_strategy::SearchConfig;
+use crate::tx_utils::huff_sandwich_interface::common::encode_coinbase_bribe;
 
 /// Core Event enum for current strategy
 #[derive(Debug, Clone)]
@@ -39,6 +42,32 @@ pub struct StratConfig {
     pub sandwich_address: Address,
     pub sandwich_inception_block: U64,
     pub searcher_signer: LocalWallet,
+    pub bribe_policy: BribePolicyConfig,
+    pub bribe_payment: BribePayment,
+    pub guardrails: GuardrailConfig,
+    pub base_tokens: BaseTokenConfig,
+    pub inventory: InventoryConfig,
+    pub search: SearchConfig,
+    pub chain: ChainConfig,
+}
+
+/// A pool that is frontrun before and backrun after the meats
+#[derive(Clone, Copy)]
+pub struct SandwichLeg {
+    /// Which token do start and end sandwich with
+    pub start_end_token: BaseToken,
+    /// Which token do we hold for duration of sandwich
+    pub intermediary_token: Address,
+    /// Which pool are we targetting
+    pub target_pool: Pool,
+    /// Set for `token->weth` victims, the intermediary token is borrowed from this pool through a
+    /// flash swap instead of being bought with weth
+    pub flash_pool: Option<UniswapV2Pool>,
+    /// Weth pair used to value `start_end_token` in eth (`None` when starting with weth)
+    pub quote_pool: Option<UniswapV2Pool>,
+    /// Set for `token->weth` victims when the intermediary token is held in inventory, frontrun
+    /// sells inventory and backrun buys the same amount back (profit stays in `start_end_token`)
+    pub inventory_token: Option<BaseToken>,
 }
 
 /// Information on potential sandwichable opportunity
@@ -46,43 +75,64 @@ pub struct StratConfig {
 pub struct RawIngredients {
     /// Victim tx/s to be used in sandwich
     meats: Vec<Transaction>,
-    /// Which token do start and end sandwich with
-    start_end_token: Address,
-    /// Which token do we hold for duration of sandwich
-    intermediary_token: Address,
-    /// Which pool are we targetting
-    target_pool: Pool,
+    /// Pools to sandwich, all frontruns land before the meats and all backruns after
+    legs: Vec<SandwichLeg>,
 }
 
 impl RawIngredients {
     pub fn new(
         meats: Vec<Transaction>,
-        start_end_token: Address,
+        start_end_token: BaseToken,
         intermediary_token: Address,
         target_pool: Pool,
     ) -> Self {
+        Self::from_leg(
+            meats,
+            SandwichLeg {
+                start_end_token,
+                intermediary_token,
+                target_pool,
+                flash_pool: None,
+                quote_pool: None,
+                inventory_token: None,
+            },
+        )
+    }
+
+    /// Sandwich a single pool described by `leg`
+    pub fn from_leg(meats: Vec<Transaction>, leg: SandwichLeg) -> Self {
         Self {
             meats,
-            start_end_token,
-            intermediary_token,
-            target_pool,
+            legs: vec![leg],
         }
     }
 
-    pub fn get_start_end_token(&self) -> Address {
-        self.start_end_token
+    /// Sandwich several pools touched by the same meats in one bundle
+    pub fn new_multi_pool(meats: Vec<Transaction>, legs: Vec<SandwichLeg>) -> Self {
+        Self { meats, legs }
+    }
+
+    /// Start/end token of the first leg
+    pub fn get_start_end_token(&self) -> BaseToken {
+        self.legs[0].start_end_token
     }
 
+    /// Intermediary token of the first leg
     pub fn get_intermediary_token(&self) -> Address {
-        self.intermediary_token
+        self.legs[0].intermediary_token
     }
 
     pub fn get_meats_ref(&self) -> &Vec<Transaction> {
         &self.meats
     }
 
+    /// Target pool of the first leg
     pub fn get_target_pool(&self) -> Pool {
-        self.target_pool
+        self.legs[0].target_pool
+    }
+
+    pub fn get_legs(&self) -> &Vec<SandwichLeg> {
+        &self.legs
     }
 
     // Used for logging
@@ -105,21 +155,17 @@ pub struct BlockInfo {
     pub number: U64,
     pub base_fee_per_gas: U256,
     pub timestamp: U256,
-    // These are optional because we don't know these values for `next_block`
+    // This is optional because we don't know it for `next_block`
     pub gas_used: Option<U256>,
     pub gas_limit: Option<U256>,
+    /// `mixHash` of the block (beacon chain randao post merge)
+    pub prevrandao: H256,
 }
 
 impl BlockInfo {
-    /// Returns block info for next block
-    pub fn get_next_block(&self) -> BlockInfo {
-        BlockInfo {
-            number: self.number + 1,
-            base_fee_per_gas: calculate_next_block_base_fee(&self),
-            timestamp: self.timestamp + 12,
-            gas_used: None,
-            gas_limit: None,
-        }
+    /// Base fee of the block built on top of this one
+    pub fn next_base_fee(&self) -> U256 {
+        calculate_next_block_base_fee(self)
     }
 }
 
@@ -137,6 +183,7 @@ impl TryFrom<Block<H256>> for BlockInfo {
                 "could not parse base fee when setting up `block_manager`"
             ))?,
             timestamp: value.timestamp,
+            prevrandao: value.mix_hash.unwrap_or_default(),
         })
     }
 }
@@ -149,6 +196,7 @@ impl From<NewBlock> for BlockInfo {
             timestamp: value.timestamp,
             gas_used: Some(value.gas_used),
             gas_limit: Some(value.gas_limit),
+            prevrandao: value.mix_hash,
         }
     }
 }
@@ -186,34 +234,47 @@ fn calculate_next_block_base_fee(block: &BlockInfo) -> U256 {
 }
 
 /// All details for capturing a sandwich opp
+#[derive(Clone)]
 pub struct SandwichRecipe {
-    frontrun: TxEnv,
-    frontrun_gas_used: u64,
+    /// One (tx, gas used) per leg, bundled before the meats
+    frontruns: Vec<(TxEnv, u64)>,
+    /// (intermediary token, frontrun input valued in eth) of every leg
+    frontrun_exposure: Vec<(Address, U256)>,
     meats: Vec<Transaction>,
-    backrun: TxEnv,
-    backrun_gas_used: u64,
+    /// One (tx, gas used) per leg, bundled after the meats (last one pays the bribe)
+    backruns: Vec<(TxEnv, u64)>,
+    /// Profit before gas and bribe (valued in eth)
     revenue: U256,
     target_block: BlockInfo,
+    bribe_payment: BribePayment,
+    /// Does the sandwich contract already hold dust of the intermediary token
+    has_dust: bool,
+    /// Revenue lost to the sandwich contract's lossy calldata encodings (valued in eth)
+    encoding_loss: U256,
 }
 
 impl SandwichRecipe {
     pub fn new(
-        frontrun: TxEnv,
-        frontrun_gas_used: u64,
+        frontruns: Vec<(TxEnv, u64)>,
+        frontrun_exposure: Vec<(Address, U256)>,
         meats: Vec<Transaction>,
-        backrun: TxEnv,
-        backrun_gas_used: u64,
+        backruns: Vec<(TxEnv, u64)>,
         revenue: U256,
         target_block: BlockInfo,
+        bribe_payment: BribePayment,
+        has_dust: bool,
+        encoding_loss: U256,
     ) -> Self {
         Self {
-            frontrun,
-            frontrun_gas_used,
+            frontruns,
+            frontrun_exposure,
             meats,
-            backrun,
-            backrun_gas_used,
+            backruns,
             revenue,
             target_block,
+            bribe_payment,
+            has_dust,
+            encoding_loss,
         }
     }
 
@@ -221,91 +282,221 @@ impl SandwichRecipe {
         self.revenue
     }
 
+    pub fn get_meats_ref(&self) -> &Vec<Transaction> {
+        &self.meats
+    }
+
+    /// Sum of all legs' frontrun inputs (valued in eth)
+    pub fn get_frontrun_in(&self) -> U256 {
+        self.frontrun_exposure
+            .iter()
+            .fold(U256::zero(), |acc, (_, amount)| acc + amount)
+    }
+
+    /// Frontrun input of every leg (valued in eth) with the intermediary token it buys
+    pub fn get_frontrun_exposure(&self) -> &[(Address, U256)] {
+        &self.frontrun_exposure
+    }
+
+    /// Gas used by all txs that only pay basefee (every leg except the last backrun)
+    pub fn get_frontrun_gas_used(&self) -> u64 {
+        let frontruns: u64 = self.frontruns.iter().map(|(_, gas)| gas).sum();
+        let backruns: u64 = self.backruns.iter().rev().skip(1).map(|(_, gas)| gas).sum();
+        frontruns + backruns
+    }
+
+    /// Gas used by the last backrun (the tx that pays the bribe)
+    pub fn get_backrun_gas_used(&self) -> u64 {
+        self.backruns
+            .last()
+            .map(|(_, gas)| *gas)
+            .unwrap_or_default()
+    }
+
+    pub fn get_leg_count(&self) -> usize {
+        self.frontruns.len()
+    }
+
+    /// Txs signed by the searcher (one nonce each)
+    pub fn get_searcher_tx_count(&self) -> u64 {
+        (self.frontruns.len() + self.backruns.len()) as u64
+    }
+
+    pub fn get_target_block(&self) -> BlockInfo {
+        self.target_block
+    }
+
+    pub fn get_bribe_payment(&self) -> BribePayment {
+        self.bribe_payment
+    }
+
+    pub fn has_dust(&self) -> bool {
+        self.has_dust
+    }
+
+    /// Weth left in pools or overpaid because amounts are rounded to what the contract's
+    /// calldata encodings can carry (frontrun inputs, backrun outputs & flash repayments)
+    pub fn get_encoding_loss(&self) -> U256 {
+        self.encoding_loss
+    }
+
+    /// Amount overpaid to get dust onto the sandwich contract (zero if dust is already there)
+    pub fn get_dust_overpay(&self) -> U256 {
+        if self.has_dust {
+            U256::zero()
+        } else {
+            *DUST_OVERPAY
+        }
+    }
+
+    /// Eth the sandwich contract transfers to `block.coinbase` when the bribe is paid through
+    /// the contract: `bribe_amount` plus the dust overpay, minus the backrun's basefee
+    pub fn get_coinbase_bribe(&self, bribe_amount: U256) -> Result<U256> {
+        (bribe_amount + self.get_dust_overpay())
+            .checked_sub(
+                U256::from(self.get_backrun_gas_used()) * self.target_block.base_fee_per_gas,
+            )
+            .ok_or_else(|| anyhow!("[FAILED TO CREATE BUNDLE] bribe doesn't cover backrun basefee"))
+    }
+
     /// turn recipe into a signed bundle that can be sumbitted to flashbots
-    pub async fn to_fb_bundle<M: Middleware>(
+    /// `bribe_amount` is decided by the strategy's `BribePolicy`
+    /// `nonce` is the first of the `get_searcher_tx_count` nonces reserved for this bundle
+    pub async fn to_fb_bundle(
         self,
         sandwich_address: Address,
         searcher: &LocalWallet,
-        has_dust: bool,
-        provider: Arc<M>,
+        bribe_amount: U256,
+        nonce: U256,
     ) -> Result<BundleRequest> {
-        let nonce = provider
-            .get_transaction_count(searcher.address(), Some(self.target_block.number.into()))
-            .await
-            .map_err(|e| anyhow!("FAILED TO CREATE BUNDLE: Failed to get nonce {:?}", e))?;
+        let target_block = self.target_block;
+        let (bundled_transactions, _) = self
+            .to_signed_txs(sandwich_address, searcher, bribe_amount, nonce)
+            .await?;
 
-        let frontrun_tx = Eip1559TransactionRequest {
-            to: Some(sandwich_address.into()),
-            gas: Some((U256::from(self.frontrun_gas_used) * 10) / 7),
-            value: Some(self.frontrun.value.into()),
-            data: Some(self.frontrun.data.into()),
-            nonce: Some(nonce),
-            access_list: access_list_to_ethers(self.frontrun.access_list),
-            max_fee_per_gas: Some(self.target_block.base_fee_per_gas.into()),
-            ..Default::default()
-        };
-        let signed_frontrun = sign_eip1559(frontrun_tx, &searcher).await?;
-
-        let signed_meat_txs: Vec<Bytes> = self.meats.into_iter().map(|meat| meat.rlp()).collect();
+        Ok(to_bundle_request(bundled_transactions, &target_block))
+    }
 
-        // calc bribe (bribes paid in backrun)
-        let revenue_minus_frontrun_tx_fee = self
-            .revenue
-            .checked_sub(U256::from(self.frontrun_gas_used) * self.target_block.base_fee_per_gas)
-            .ok_or_else(|| {
-                anyhow!("[FAILED TO CREATE BUNDLE] revenue doesn't cover frontrun basefee")
-            })?;
+    /// Sign the sandwich's txs (frontruns, meats, backruns) starting at searcher `nonce`
+    /// Returns the txs and the next unused searcher nonce (so several sandwiches can be merged)
+    pub async fn to_signed_txs(
+        self,
+        sandwich_address: Address,
+        searcher: &LocalWallet,
+        bribe_amount: U256,
+        mut nonce: U256,
+    ) -> Result<(Vec<Bytes>, U256)> {
+        let coinbase_bribe = self.get_coinbase_bribe(bribe_amount);
 
         // eat a loss (overpay) to get dust onto the sandwich contract (more: https://twitter.com/libevm/status/1474870661373779969)
-        let bribe_amount = if !has_dust {
-            revenue_minus_frontrun_tx_fee + *DUST_OVERPAY
-        } else {
-            // bribe away 99.9999999% of revenue lmeow
-            revenue_minus_frontrun_tx_fee * 999999999 / 1000000000
-        };
+        let bribe_amount = bribe_amount + self.get_dust_overpay();
+
+        let base_fee = self.target_block.base_fee_per_gas;
+
+        // frontruns (and every backrun but the last) only pay basefee
+        let basefee_only_tx =
+            |tx_env: TxEnv, gas_used: u64, nonce: U256| Eip1559TransactionRequest {
+                to: Some(sandwich_address.into()),
+                gas: Some((U256::from(gas_used) * 10) / 7),
+                value: Some(tx_env.value.into()),
+                data: Some(tx_env.data.into()),
+                nonce: Some(nonce),
+                access_list: access_list_to_ethers(tx_env.access_list),
+                max_fee_per_gas: Some(base_fee),
+                ..Default::default()
+            };
+
+        let mut signed_frontruns = vec![];
+        for (frontrun, gas_used) in self.frontruns {
+            signed_frontruns
+                .push(sign_eip1559(basefee_only_tx(frontrun, gas_used, nonce), &searcher).await?);
+            nonce += U256::one();
+        }
 
-        let max_fee = bribe_amount / self.backrun_gas_used;
+        let signed_meat_txs: Vec<Bytes> = self.meats.into_iter().map(|meat| meat.rlp()).collect();
 
-        ensure!(
-            max_fee >= self.target_block.base_fee_per_gas,
-            "[FAILED TO CREATE BUNDLE] backrun maxfee less than basefee"
-        );
+        let mut backruns = self.backruns;
+        let (backrun, backrun_gas_used) = backruns
+            .pop()
+            .ok_or_else(|| anyhow!("[FAILED TO CREATE BUNDLE] recipe has no backrun"))?;
 
-        let effective_miner_tip = max_fee.checked_sub(self.target_block.base_fee_per_gas);
+        let mut signed_backruns = vec![];
+        for (backrun, gas_used) in backruns {
+            signed_backruns
+                .push(sign_eip1559(basefee_only_tx(backrun, gas_used, nonce), &searcher).await?);
+            nonce += U256::one();
+        }
 
-        ensure!(
-            effective_miner_tip.is_none(),
-            "[FAILED TO CREATE BUNDLE] negative miner tip"
-        );
+        let (backrun_data, max_priority_fee, max_fee): (Bytes, U256, U256) =
+            match self.bribe_payment {
+                BribePayment::PriorityFee => {
+                    let max_fee = bribe_amount / backrun_gas_used;
+
+                    ensure!(
+                        max_fee >= base_fee,
+                        "[FAILED TO CREATE BUNDLE] backrun maxfee less than basefee"
+                    );
+
+                    let effective_miner_tip = max_fee.checked_sub(base_fee);
+
+                    ensure!(
+                        effective_miner_tip.is_some(),
+                        "[FAILED TO CREATE BUNDLE] negative miner tip"
+                    );
+
+                    (backrun.data.into(), max_fee, max_fee)
+                }
+                BribePayment::CoinbaseTransfer => {
+                    // backrun only pays basefee, the rest goes to the builder through the contract
+                    let coinbase_bribe = coinbase_bribe?;
+
+                    ensure!(
+                        coinbase_bribe <= U256::from(u128::MAX),
+                        "[FAILED TO CREATE BUNDLE] coinbase bribe does not fit in 16 bytes"
+                    );
+
+                    // replace placeholder bribe used during simulation with the exact amount
+                    let mut backrun_data = backrun.data.to_vec();
+                    let payload_len = backrun_data.len().checked_sub(16).ok_or_else(|| {
+                        anyhow!("[FAILED TO CREATE BUNDLE] missing coinbase bribe")
+                    })?;
+                    backrun_data.truncate(payload_len);
+                    backrun_data.extend(encode_coinbase_bribe(coinbase_bribe));
+
+                    (backrun_data.into(), U256::zero(), base_fee)
+                }
+            };
 
         let backrun_tx = Eip1559TransactionRequest {
             to: Some(sandwich_address.into()),
-            gas: Some((U256::from(self.backrun_gas_used) * 10) / 7),
-            value: Some(self.backrun.value.into()),
-            data: Some(self.backrun.data.into()),
-            nonce: Some(nonce + 1),
-            access_list: access_list_to_ethers(self.backrun.access_list),
-            max_priority_fee_per_gas: Some(max_fee),
+            gas: Some((U256::from(backrun_gas_used) * 10) / 7),
+            value: Some(backrun.value.into()),
+            data: Some(backrun_data),
+            nonce: Some(nonce),
+            access_list: access_list_to_ethers(backrun.access_list),
+            max_priority_fee_per_gas: Some(max_priority_fee),
             max_fee_per_gas: Some(max_fee),
             ..Default::default()
         };
-        let signed_backrun = sign_eip1559(backrun_tx, &searcher).await?;
+        signed_backruns.push(sign_eip1559(backrun_tx, &searcher).await?);
 
-        // construct bundle
-        let mut bundled_transactions: Vec<Bytes> = vec![signed_frontrun];
+        let mut bundled_transactions: Vec<Bytes> = signed_frontruns;
         bundled_transactions.append(&mut signed_meat_txs.clone());
-        bundled_transactions.push(signed_backrun);
-
-        let mut bundle_request = BundleRequest::new();
-        for tx in bundled_transactions {
-            bundle_request = bundle_request.push_transaction(tx);
-        }
+        bundled_transactions.append(&mut signed_backruns);
 
-        bundle_request = bundle_request
-            .set_block(self.target_block.number)
-            .set_simulation_block(self.target_block.number - 1)
-            .set_simulation_timestamp(self.target_block.timestamp.as_u64());
+        Ok((bundled_transactions, nonce + U256::one()))
+    }
+}
 
-        Ok(bundle_request)
+/// construct bundle targetting `target_block` from signed txs
+pub fn to_bundle_request(txs: Vec<Bytes>, target_block: &BlockInfo) -> BundleRequest {
+    let mut bundle_request = BundleRequest::new();
+    for tx in txs {
+        bundle_request = bundle_request.push_transaction(tx);
     }
+
+    bundle_request
+        .set_block(target_block.number)
+        .set_simulation_block(target_block.number - 1)
+        .set_simulation_timestamp(target_block.timestamp.as_u64())
 }
@@ -2,22 +2,22 @@ use std::{str::FromStr, sync::Arc};
 
 use cfmms::pool::{Pool, UniswapV2Pool, UniswapV3Pool};
 use ethers::{
-    prelude::Lazy,
     providers::{Middleware, Provider, Ws},
     types::{Address, Transaction, TxHash, U64},
 };
 use strategy::{
+    base_token::{BaseToken, BaseTokenConfig},
+    block_env::ChainConfig,
     bot::SandwichBot,
+    bribe_policy::{BribePayment, BribePolicyConfig},
+    guardrails::GuardrailConfig,
+    inventory::InventoryConfig,
+    search_strategy::SearchConfig,
     types::{BlockInfo, RawIngredients, StratConfig},
 };
 
 // -- consts --
 static WSS_RPC: &str = "ws://localhost:8545";
-pub static WETH_ADDRESS: Lazy<Address> = Lazy::new(|| {
-    "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
-        .parse()
-        .unwrap()
-});
 
 // -- utils --
 fn setup_logger() {
@@ -39,6 +39,13 @@ async fn setup_bot(provider: Arc<Provider<Ws>>) -> SandwichBot<Provider<Ws>> {
         searcher_signer: "0x0000000000000000000000000000000000000000000000000000000000000001"
             .parse()
             .unwrap(),
+        bribe_policy: BribePolicyConfig::default(),
+        bribe_payment: BribePayment::default(),
+        guardrails: GuardrailConfig::default(),
+        base_tokens: BaseTokenConfig::default(),
+        inventory: InventoryConfig::default(),
+        search: SearchConfig::default(),
+        chain: ChainConfig::default(),
     };
 
     SandwichBot::new(provider, strat_config)
@@ -89,7 +96,7 @@ async fn can_sandwich_uni_v2() {
             )
             .await,
         ],
-        *WETH_ADDRESS,
+        BaseToken::weth(),
         hex_to_address("0x3642Cf76c5894B4aB51c1080B2c4F5B9eA734106"),
         hex_to_univ2_pool("0x5d1dd0661E1D22697943C1F50Cc726eA3143329b", client.clone()).await,
     );
@@ -117,7 +124,7 @@ async fn can_sandwich_sushi_swap() {
             )
             .await,
         ],
-        *WETH_ADDRESS,
+        BaseToken::weth(),
         hex_to_address("0x3b484b82567a09e2588A13D54D032153f0c0aEe0"),
         hex_to_univ2_pool("0xB84C45174Bfc6b8F3EaeCBae11deE63114f5c1b2", client.clone()).await,
     );
@@ -150,7 +157,7 @@ async fn can_sandwich_multi_v2_swaps() {
             )
             .await,
         ],
-        *WETH_ADDRESS,
+        BaseToken::weth(),
         hex_to_address("0x31b16Ff7823096a227Aac78F1C094525A84ab64F"),
         hex_to_univ2_pool("0x657c6a08d49B4F0778f9cce1Dc49d196cFCe9d08", client.clone()).await,
     );
@@ -178,7 +185,7 @@ async fn can_sandwich_uni_v3() {
             )
             .await,
         ],
-        *WETH_ADDRESS,
+        BaseToken::weth(),
         hex_to_address("0x24C19F7101c1731b85F1127EaA0407732E36EcDD"),
         hex_to_univ3_pool("0x62CBac19051b130746Ec4CF96113aF5618F3A212", client.clone()).await,
     );
@@ -5,7 +5,15 @@ use std::{env, str::FromStr};
 use anyhow::{anyhow, Result};
 use ethers::{
     signers::LocalWallet,
-    types::{Address, U64},
+    types::{Address, U256, U64},
+};
+use strategy::{
+    base_token::BaseTokenConfig,
+    block_env::ChainConfig,
+    bribe_policy::{BribePayment, BribePolicyConfig},
+    guardrails::GuardrailConfig,
+    inventory::InventoryConfig,
+    search_strategy::{SearchConfig, SearchStrategyConfig},
 };
 
 pub struct Config {
@@ -14,6 +22,13 @@ pub struct Config {
     pub sandwich_address: Address,
     pub bundle_signer: LocalWallet,
     pub wss_rpc: Url,
+    pub bribe_policy: BribePolicyConfig,
+    pub bribe_payment: BribePayment,
+    pub guardrails: GuardrailConfig,
+    pub base_tokens: BaseTokenConfig,
+    pub inventory: InventoryConfig,
+    pub search: SearchConfig,
+    pub chain: ChainConfig,
 }
 
 impl Config {
@@ -44,12 +59,108 @@ impl Config {
             .parse()
             .map_err(|_| anyhow!("Failed to parse \"WSS_RPC\""))?;
 
+        // optional, falls back to the default policy when unset
+        let bribe_policy = match env::var("BRIBE_POLICY") {
+            Ok(policy) => policy
+                .parse::<BribePolicyConfig>()
+                .map_err(|e| anyhow!("Failed to parse \"BRIBE_POLICY\": {}", e))?,
+            Err(_) => BribePolicyConfig::default(),
+        };
+
+        // optional, falls back to paying bribes through priority fees when unset
+        let bribe_payment = match env::var("BRIBE_PAYMENT") {
+            Ok(payment) => payment
+                .parse::<BribePayment>()
+                .map_err(|e| anyhow!("Failed to parse \"BRIBE_PAYMENT\": {}", e))?,
+            Err(_) => BribePayment::default(),
+        };
+
+        // optional guardrails, amounts are in wei and limits are disabled when unset
+        let get_optional_wei = |var| -> Result<Option<U256>> {
+            env::var(var)
+                .ok()
+                .map(|v| {
+                    U256::from_dec_str(&v)
+                        .map_err(|e| anyhow!("Failed to parse \"{}\": {}", var, e))
+                })
+                .transpose()
+        };
+
+        let guardrails = GuardrailConfig {
+            min_profit_wei: get_optional_wei("MIN_PROFIT_WEI")?.unwrap_or_default(),
+            min_profit_bps: match env::var("MIN_PROFIT_BPS") {
+                Ok(bps) => bps
+                    .parse::<u64>()
+                    .map_err(|e| anyhow!("Failed to parse \"MIN_PROFIT_BPS\": {}", e))?,
+                Err(_) => 0,
+            },
+            max_frontrun_per_sandwich: get_optional_wei("MAX_FRONTRUN_WEI")?,
+            max_frontrun_per_block: get_optional_wei("MAX_BLOCK_FRONTRUN_WEI")?,
+            max_token_exposure: get_optional_wei("MAX_TOKEN_EXPOSURE_WEI")?,
+            max_daily_loss: get_optional_wei("MAX_DAILY_LOSS_WEI")?,
+        };
+
+        // optional, weth is always a base token and stables are used when unset
+        let base_tokens = match env::var("BASE_TOKENS") {
+            Ok(tokens) => tokens
+                .parse::<BaseTokenConfig>()
+                .map_err(|e| anyhow!("Failed to parse \"BASE_TOKENS\": {}", e))?,
+            Err(_) => BaseTokenConfig::default(),
+        };
+
+        // optional, inventory sandwiches are disabled when no basket is set
+        let mut inventory = match env::var("INVENTORY_TOKENS") {
+            Ok(tokens) => tokens
+                .parse::<InventoryConfig>()
+                .map_err(|e| anyhow!("Failed to parse \"INVENTORY_TOKENS\": {}", e))?,
+            Err(_) => InventoryConfig::default(),
+        };
+        if let Ok(blocks) = env::var("INVENTORY_REBALANCE_BLOCKS") {
+            inventory.rebalance_interval = blocks
+                .parse::<u64>()
+                .map_err(|e| anyhow!("Failed to parse \"INVENTORY_REBALANCE_BLOCKS\": {}", e))?;
+        }
+        if let Ok(bps) = env::var("INVENTORY_REBALANCE_BAND_BPS") {
+            inventory.rebalance_band_bps = bps
+                .parse::<u64>()
+                .map_err(|e| anyhow!("Failed to parse \"INVENTORY_REBALANCE_BAND_BPS\": {}", e))?;
+        }
+
+        // optional, each pool type falls back to the parallel grid search when unset
+        let get_search_strategy = |var| -> Result<SearchStrategyConfig> {
+            match env::var(var) {
+                Ok(strategy) => strategy
+                    .parse::<SearchStrategyConfig>()
+                    .map_err(|e| anyhow!("Failed to parse \"{}\": {}", var, e)),
+                Err(_) => Ok(SearchStrategyConfig::default()),
+            }
+        };
+        let search = SearchConfig {
+            v2: get_search_strategy("SEARCH_STRATEGY_V2")?,
+            v3: get_search_strategy("SEARCH_STRATEGY_V3")?,
+        };
+
+        // optional, defaults to mainnet
+        let chain = match env::var("CHAIN_CONFIG") {
+            Ok(chain) => chain
+                .parse::<ChainConfig>()
+                .map_err(|e| anyhow!("Failed to parse \"CHAIN_CONFIG\": {}", e))?,
+            Err(_) => ChainConfig::default(),
+        };
+
         Ok(Self {
             searcher_signer,
             sandwich_inception_block,
             sandwich_address,
             bundle_signer,
             wss_rpc,
+            bribe_policy,
+            bribe_payment,
+            guardrails,
+            base_tokens,
+            inventory,
+            search,
+            chain,
         })
     }
 }
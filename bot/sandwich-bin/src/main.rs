@@ -61,6 +61,13 @@ async fn main() -> Result<()> {
         sandwich_address: config.sandwich_address,
         sandwich_inception_block: config.sandwich_inception_block,
         searcher_signer,
+        bribe_policy: config.bribe_policy,
+        bribe_payment: config.bribe_payment,
+        guardrails: config.guardrails,
+        base_tokens: config.base_tokens,
+        inventory: config.inventory,
+        search: config.search,
+        chain: config.chain,
     };
     let strategy = SandwichBot::new(provider.clone(), configs);
     engine.add_strategy(Box::new(strategy));
@@ -171,6 +171,34 @@ contract SandwichTest is Test {
         assertTrue(s, "calling swap failed");
     }
 
+    function testV3BackrunPaysCoinbase() public {
+        IUniswapV3Pool pool = IUniswapV3Pool(0xC2e9F25Be6257c210d7Adf0D4Cd6E3E881ba25f8);
+        address daiAddress = pool.token0();
+        address sugarDaddy = 0x5d3a536E4D6DbD6114cc1Ead35777bAB948E3643;
+        address builder = address(0x69420);
+        uint128 bribeAmount = 0.0123456789 ether;
+
+        // fund sandwich maker with dai and eth (coinbase bribes are paid from the contract's eth balance)
+        vm.prank(sugarDaddy);
+        ERC20(daiAddress).transfer(sandwichMaker, 1000 ether);
+        vm.deal(sandwichMaker, 1 ether);
+        vm.coinbase(builder);
+
+        uint256 preSwapWethBalance = weth.balanceOf(sandwichMaker);
+        uint256 preSwapBuilderBalance = builder.balance;
+
+        // append exact bribe amount after the backrun payload
+        bytes memory payload = V3SandwichUtility.v3CreateBackrunPayload(pool, daiAddress, int256(1000 ether));
+        payload = abi.encodePacked(payload, bribeAmount);
+
+        vm.prank(searcher, searcher);
+        (bool s,) = address(sandwichMaker).call(payload);
+        assertTrue(s, "swap failed");
+
+        assertEq(builder.balance - preSwapBuilderBalance, bribeAmount, "builder did not receive exact bribe");
+        assertGt(weth.balanceOf(sandwichMaker), preSwapWethBalance, "backrun did not swap with appended bribe");
+    }
+
     // +-------------------------------+
     // |        Generic Tests          |
     // +-------------------------------+
@@ -249,6 +277,48 @@ contract SandwichTest is Test {
         );
     }
 
+    function testV2FrontrunBaseToken(uint256 inputUsdcAmount) public {
+        address usdcAddress = 0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48;
+        address daiAddress = 0x6B175474E89094C44Da98b954EedeAC495271d0F;
+
+        // fund sandwich maker with usdc (base token)
+        deal(usdcAddress, sandwichMaker, 1_000_000e6);
+
+        // make sure fuzzed value is within bounds
+        inputUsdcAmount =
+            bound(inputUsdcAmount, WethEncodingUtils.encodeMultiple(), ERC20(usdcAddress).balanceOf(sandwichMaker));
+
+        // capture pre swap state
+        uint256 preSwapUsdcBalance = ERC20(usdcAddress).balanceOf(sandwichMaker);
+        uint256 preSwapDaiBalance = ERC20(daiAddress).balanceOf(sandwichMaker);
+
+        // calculate expected values
+        uint256 actualUsdcInput = WethEncodingUtils.decode(WethEncodingUtils.encode(inputUsdcAmount));
+        uint256 actualDaiOutput = GeneralHelper.getAmountOut(usdcAddress, daiAddress, actualUsdcInput);
+        uint256 expectedDaiOutput = FiveBytesEncodingUtils.decode(FiveBytesEncodingUtils.encode(actualDaiOutput));
+
+        // need this to pass because: https://github.com/Uniswap/v2-core/blob/master/contracts/UniswapV2Pair.sol#L160
+        vm.assume(expectedDaiOutput > 0);
+
+        (bytes memory calldataPayload, uint256 encodedValue) =
+            V2SandwichUtility.v2CreateBaseTokenFrontrunPayload(usdcAddress, daiAddress, inputUsdcAmount);
+        vm.prank(searcher);
+        (bool s,) = address(sandwichMaker).call{value: encodedValue}(calldataPayload);
+        assertTrue(s);
+
+        // check values after swap
+        assertEq(
+            ERC20(daiAddress).balanceOf(sandwichMaker) - preSwapDaiBalance,
+            expectedDaiOutput,
+            "did not get expected dai amount out from swap"
+        );
+        assertEq(
+            preSwapUsdcBalance - ERC20(usdcAddress).balanceOf(sandwichMaker),
+            actualUsdcInput,
+            "unexpected amount of usdc used in swap"
+        );
+    }
+
     function testV2BackrunWeth0(uint256 inputSuperAmount) public {
         address superAddress = 0xe53EC727dbDEB9E2d5456c3be40cFF031AB40A55; // superfarm token
         address sugarDaddy = 0xF977814e90dA44bFA03b6295A0616a897441aceC;
@@ -334,4 +404,114 @@ contract SandwichTest is Test {
             "unexpected amount of dai used in swap"
         );
     }
+
+    function testV2BackrunPaysCoinbase() public {
+        address daiAddress = 0x6B175474E89094C44Da98b954EedeAC495271d0F; // DAI
+        address sugarDaddy = 0x47ac0Fb4F2D84898e4D9E7b4DaB3C24507a6D503;
+        address builder = address(0x69420);
+        uint128 bribeAmount = 0.0123456789 ether;
+
+        // fund sandwich maker with dai and eth (coinbase bribes are paid from the contract's eth balance)
+        vm.prank(sugarDaddy);
+        ERC20(daiAddress).transfer(sandwichMaker, 1000 ether);
+        vm.deal(sandwichMaker, 1 ether);
+        vm.coinbase(builder);
+
+        uint256 preSwapWethBalance = weth.balanceOf(sandwichMaker);
+        uint256 preSwapBuilderBalance = builder.balance;
+
+        // append exact bribe amount after the backrun payload
+        (bytes memory calldataPayload, uint256 wethEncodedValue) =
+            V2SandwichUtility.v2CreateBackrunPayload(daiAddress, 1000 ether);
+        calldataPayload = abi.encodePacked(calldataPayload, bribeAmount);
+
+        vm.prank(searcher);
+        (bool s,) = address(sandwichMaker).call{value: wethEncodedValue}(calldataPayload);
+        assertTrue(s, "swap failed");
+
+        assertEq(builder.balance - preSwapBuilderBalance, bribeAmount, "builder did not receive exact bribe");
+        assertGt(weth.balanceOf(sandwichMaker), preSwapWethBalance, "backrun did not swap with appended bribe");
+    }
+
+    function testV2FlashSwap(uint256 inputUsdcAmount) public {
+        address usdcAddress = 0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48;
+        address sushiPair = 0x397FF1542f962076d0BFE58eA045FfA2d347ACa0; // USDC - WETH (sushiswap)
+
+        // make sure fuzzed value is within bounds
+        inputUsdcAmount = bound(inputUsdcAmount, 1e6, 100_000e6);
+
+        uint256 preSwapWethBalance = weth.balanceOf(sandwichMaker);
+        uint256 preSwapUsdcBalance = ERC20(usdcAddress).balanceOf(sandwichMaker);
+
+        (bytes memory calldataPayload, uint256 wethEncodedValue, uint256 wethOut) =
+            V2SandwichUtility.v2CreateFlashPayload(usdcAddress, sushiPair, inputUsdcAmount);
+
+        // need this to pass because: https://github.com/Uniswap/v2-core/blob/master/contracts/UniswapV2Pair.sol#L160
+        vm.assume(wethOut > 0);
+
+        vm.prank(searcher);
+        (bool s,) = address(sandwichMaker).call{value: wethEncodedValue}(calldataPayload);
+        assertTrue(s, "flash swap failed");
+
+        // borrowed tokens are sold within the same tx
+        assertEq(
+            ERC20(usdcAddress).balanceOf(sandwichMaker), preSwapUsdcBalance, "sandwich maker should not hold usdc"
+        );
+        assertEq(
+            weth.balanceOf(sandwichMaker),
+            preSwapWethBalance + wethOut - WethEncodingUtils.decode(wethEncodedValue),
+            "unexpected weth balance after flash swap"
+        );
+    }
+
+    function testV2FlashSwapBackrun(uint256 inputUsdcAmount) public {
+        address usdcAddress = 0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48;
+        address sushiPair = 0x397FF1542f962076d0BFE58eA045FfA2d347ACa0; // USDC - WETH (sushiswap)
+        address uniPair = 0xB4e16d0168e52d35CaCD2c6185b44281Ec28C9Dc; // USDC - WETH (uniswap v2)
+
+        // make sure fuzzed value is within bounds
+        inputUsdcAmount = bound(inputUsdcAmount, 1e6, 100_000e6);
+
+        uint256 preSwapWethBalance = weth.balanceOf(sandwichMaker);
+        uint256 preSwapUsdcBalance = ERC20(usdcAddress).balanceOf(sandwichMaker);
+
+        // backruns borrow back from the sandwiched pair, which isn't a uniswap v2 pair
+        (bytes memory calldataPayload, uint256 wethEncodedValue, uint256 wethOut) =
+            V2SandwichUtility.v2CreateFlashPayloadFrom(usdcAddress, sushiPair, uniPair, inputUsdcAmount);
+        vm.assume(wethOut > 0);
+
+        vm.prank(searcher);
+        (bool s,) = address(sandwichMaker).call{value: wethEncodedValue}(calldataPayload);
+        assertTrue(s, "flash swap from sushiswap pair failed");
+
+        assertEq(
+            ERC20(usdcAddress).balanceOf(sandwichMaker), preSwapUsdcBalance, "sandwich maker should not hold usdc"
+        );
+        assertEq(
+            weth.balanceOf(sandwichMaker),
+            preSwapWethBalance + wethOut - WethEncodingUtils.decode(wethEncodedValue),
+            "unexpected weth balance after flash swap"
+        );
+    }
+
+    function testUnauthorizedAccessToV2Callback(address trespasser, uint256 amount) public {
+        // no address can send txs as the zero address (the flash pair slot starts out zeroed)
+        vm.assume(trespasser != address(0));
+        vm.startPrank(trespasser);
+        /*
+           function uniswapV2Call(
+             address sender,
+             uint amount0,
+             uint amount1,
+             bytes data
+           ) external
+
+           custom data = abi.encodePacked(wethRepayAmount, sellPair, token, encodedWethOut)
+        */
+        bytes memory data = abi.encodePacked(uint256(0), trespasser, address(weth), uint40(0));
+        bytes memory payload = abi.encodeWithSelector(0x10d1e85c, sandwichMaker, amount, uint256(0), data);
+        (bool s,) = sandwichMaker.call(payload);
+        assertFalse(s, "only the pair being flash borrowed from should be able to call callback");
+    }
 }
+
@@ -37,11 +37,19 @@ library GeneralHelper {
         public
         view
         returns (uint256 amountIn)
+    {
+        amountIn = getAmountInFromPair(_getUniswapPair(inputToken, outputToken), inputToken, outputToken, amountOut);
+    }
+
+    /// @notice Same as `getAmountIn` on any uniswap v2 fork's pair (0.3% fee)
+    function getAmountInFromPair(address pair, address inputToken, address outputToken, uint256 amountOut)
+        public
+        view
+        returns (uint256 amountIn)
     {
         IUniswapV2Router02 univ2Router = IUniswapV2Router02(0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D);
 
-        (uint256 reserveToken0, uint256 reserveToken1,) =
-            IUniswapV2Pair(_getUniswapPair(inputToken, outputToken)).getReserves();
+        (uint256 reserveToken0, uint256 reserveToken1,) = IUniswapV2Pair(pair).getReserves();
 
         uint256 reserveIn;
         uint256 reserveOut;
@@ -107,12 +107,11 @@ library SandwichCommon {
      * @return JUMPDEST location in bytecode
      */
     function getJumpDestFromSig(string memory functionName) public pure returns (uint8) {
-        uint8 startingIndex = 0x05;
-
-        // array mapped in same order as on sandwich contract
-        string[11] memory functionNames = [
+        // arrays mapped in same order as on sandwich contract
+        string[13] memory functionNames = [
             "v2_backrun0",
             "v2_frontrun0",
+            "uniswapV2Callback",
             "v2_backrun1",
             "v2_frontrun1",
             "v3_backrun0",
@@ -121,13 +120,17 @@ library SandwichCommon {
             "v3_frontrun1",
             "seppuku",
             "recoverEth",
-            "recoverWeth"
+            "recoverWeth",
+            "v2_flash"
         ];
+        // gates are 5 bytes apart, except for the padding that puts the v2 callback on 0x10
+        uint8[13] memory jumpDests =
+            [0x05, 0x0a, 0x10, 0x15, 0x1a, 0x1f, 0x24, 0x29, 0x2e, 0x33, 0x38, 0x3d, 0x42];
 
         // find index of associated JUMPDEST (sig)
         for (uint256 i = 0; i < functionNames.length; i++) {
             if (keccak256(abi.encodePacked(functionNames[i])) == keccak256(abi.encodePacked(functionName))) {
-                return (uint8(i) * 5) + startingIndex;
+                return jumpDests[i];
             }
         }
 
@@ -73,4 +73,99 @@ library V2SandwichUtility {
 
         encodedValue = WethEncodingUtils.encode(amountIn);
     }
+
+    /**
+     * @notice Utility function to create payload for v2 frontruns that pay with a base token other than WETH
+     * @dev Base token address is appended after the frontrun payload
+     * @return payload Calldata bytes to execute frontruns
+     * @return encodedValue Encoded `tx.value` indicating base token amount to send
+     */
+    function v2CreateBaseTokenFrontrunPayload(address baseToken, address outputToken, uint256 amountIn)
+        public
+        view
+        returns (bytes memory payload, uint256 encodedValue)
+    {
+        IUniswapV2Factory univ2Factory = IUniswapV2Factory(0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f);
+        address pair = address(IUniswapV2Pair(univ2Factory.getPair(baseToken, address(outputToken))));
+
+        // Encode amountIn here (so we can use it for next step)
+        uint256 amountInActual = WethEncodingUtils.decode(WethEncodingUtils.encode(amountIn));
+
+        // Get amounts out and encode it
+        FiveBytesEncodingUtils.EncodingMetaData memory fiveByteParams =
+            FiveBytesEncodingUtils.encode(GeneralHelper.getAmountOut(baseToken, outputToken, amountInActual));
+
+        string memory functionSignature = baseToken < outputToken ? "v2_frontrun0" : "v2_frontrun1";
+        uint8 jumpDest = SandwichCommon.getJumpDestFromSig(functionSignature);
+
+        payload = abi.encodePacked(
+            jumpDest, // type of swap to make
+            address(pair), // univ2 pair
+            FiveBytesEncodingUtils.finalzeForParamIndex(fiveByteParams, baseToken < outputToken ? 1 : 0),
+            baseToken // token paid into the pair
+        );
+
+        encodedValue = WethEncodingUtils.encode(amountIn);
+    }
+
+    /**
+     * @notice Utility function to create payload for our v2 flash swaps
+     * @dev Borrows `tokenAmount` of `otherToken` from its univ2 pair and sells it into `sellPair`
+     * @return payload Calldata bytes to execute the flash swap
+     * @return encodedValue Encoded `tx.value` indicating WETH amount repaid to the univ2 pair
+     * @return wethOut WETH amount received from `sellPair`
+     */
+    function v2CreateFlashPayload(address otherToken, address sellPair, uint256 tokenAmount)
+        public
+        view
+        returns (bytes memory payload, uint256 encodedValue, uint256 wethOut)
+    {
+        address weth = address(0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2);
+        IUniswapV2Factory univ2Factory = IUniswapV2Factory(0x5C69bEe701ef814a2B6a3EDD4B1652CB9cc5aA6f);
+        address flashPair = address(IUniswapV2Pair(univ2Factory.getPair(weth, address(otherToken))));
+
+        return v2CreateFlashPayloadFrom(otherToken, flashPair, sellPair, tokenAmount);
+    }
+
+    /**
+     * @notice Utility function to create payload for our v2 flash swaps from any pair
+     * @dev Borrows `tokenAmount` of `otherToken` from `flashPair` (any uniswap v2 fork, e.g. the sandwiched
+     * pair when backrunning) and sells it into `sellPair`
+     * @return payload Calldata bytes to execute the flash swap
+     * @return encodedValue Encoded `tx.value` indicating WETH amount repaid to `flashPair`
+     * @return wethOut WETH amount received from `sellPair`
+     */
+    function v2CreateFlashPayloadFrom(address otherToken, address flashPair, address sellPair, uint256 tokenAmount)
+        public
+        view
+        returns (bytes memory payload, uint256 encodedValue, uint256 wethOut)
+    {
+        // Declare uniswapv2 types
+        address weth = address(0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2);
+        IUniswapV2Router02 univ2Router = IUniswapV2Router02(0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D);
+
+        // encode amount borrowed
+        FiveBytesEncodingUtils.EncodingMetaData memory tokenParams = FiveBytesEncodingUtils.encode(tokenAmount);
+        uint256 tokenAmountActual = FiveBytesEncodingUtils.decode(tokenParams);
+
+        // weth repaid to flash pair is rounded up so that the pair's k check passes
+        uint256 wethRepay = GeneralHelper.getAmountInFromPair(flashPair, weth, otherToken, tokenAmountActual);
+        encodedValue = (wethRepay + WethEncodingUtils.encodeMultiple() - 1) / WethEncodingUtils.encodeMultiple();
+
+        // weth received from selling borrowed tokens into sell pair
+        (uint256 reserve0, uint256 reserve1,) = IUniswapV2Pair(sellPair).getReserves();
+        (uint256 reserveIn, uint256 reserveOut) = otherToken < weth ? (reserve0, reserve1) : (reserve1, reserve0);
+        FiveBytesEncodingUtils.EncodingMetaData memory wethParams =
+            FiveBytesEncodingUtils.encode(univ2Router.getAmountOut(tokenAmountActual, reserveIn, reserveOut));
+        wethOut = FiveBytesEncodingUtils.decode(wethParams);
+
+        payload = abi.encodePacked(
+            SandwichCommon.getJumpDestFromSig("v2_flash"),
+            address(flashPair), // univ2 pair we borrow from
+            address(sellPair), // pair we sell into
+            address(otherToken), // borrowed token
+            FiveBytesEncodingUtils.finalzeForParamIndex(wethParams, weth < otherToken ? 0 : 1),
+            FiveBytesEncodingUtils.finalzeForParamIndex(tokenParams, otherToken < weth ? 0 : 1)
+        );
+    }
 }